@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+
+DROP TRIGGER IF EXISTS responses_notify_edited ON responses;
+DROP TRIGGER IF EXISTS posts_notify_edited ON posts;
+DROP TRIGGER IF EXISTS comments_notify_added ON comments;
+DROP TRIGGER IF EXISTS responses_notify_added ON responses;
+
+DROP FUNCTION IF EXISTS notify_post_row_edited();
+DROP FUNCTION IF EXISTS notify_post_edited();
+DROP FUNCTION IF EXISTS notify_comment_added();
+DROP FUNCTION IF EXISTS notify_response_added();
@@ -0,0 +1,65 @@
+-- Raise an event on the `axtell_events` channel for every write that subscriptions watch.
+-- Payloads are deserialized by `db::events::DbEvent`.
+
+CREATE OR REPLACE FUNCTION notify_response_added() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('axtell_events', json_build_object(
+        'kind', 'response_added',
+        'challenge_id', NEW.challenge_id,
+        'response_id', NEW.post_id
+    )::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION notify_comment_added() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('axtell_events', json_build_object(
+        'kind', 'comment_added',
+        'post_id', NEW.post_id,
+        'comment_id', NEW.id
+    )::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION notify_post_edited() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('axtell_events', json_build_object(
+        'kind', 'post_edited',
+        'post_id', NEW.post_id
+    )::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- `posts` rows are keyed by `id` rather than `post_id`
+CREATE OR REPLACE FUNCTION notify_post_row_edited() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('axtell_events', json_build_object(
+        'kind', 'post_edited',
+        'post_id', NEW.id
+    )::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER responses_notify_added
+    AFTER INSERT ON responses
+    FOR EACH ROW EXECUTE FUNCTION notify_response_added();
+
+CREATE TRIGGER comments_notify_added
+    AFTER INSERT ON comments
+    FOR EACH ROW EXECUTE FUNCTION notify_comment_added();
+
+CREATE TRIGGER posts_notify_edited
+    AFTER UPDATE OF title, body ON posts
+    FOR EACH ROW
+    WHEN (OLD.title IS DISTINCT FROM NEW.title OR OLD.body IS DISTINCT FROM NEW.body)
+    EXECUTE FUNCTION notify_post_row_edited();
+
+CREATE TRIGGER responses_notify_edited
+    AFTER UPDATE OF code ON responses
+    FOR EACH ROW
+    WHEN (OLD.code IS DISTINCT FROM NEW.code)
+    EXECUTE FUNCTION notify_post_edited();
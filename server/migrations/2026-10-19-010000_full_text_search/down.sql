@@ -0,0 +1,15 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS comments_search_vector_idx;
+ALTER TABLE comments DROP COLUMN IF EXISTS search_vector;
+
+DROP TRIGGER IF EXISTS responses_update_search_vector ON responses;
+DROP TRIGGER IF EXISTS posts_update_search_vector ON posts;
+
+DROP FUNCTION IF EXISTS update_response_search_vector();
+DROP FUNCTION IF EXISTS update_post_search_vector();
+
+DROP INDEX IF EXISTS posts_search_vector_idx;
+ALTER TABLE posts DROP COLUMN IF EXISTS search_vector;
+
+DROP FUNCTION IF EXISTS post_search_vector(TEXT, TEXT, TEXT);
@@ -0,0 +1,52 @@
+-- Stored, weighted search vectors for `search`. Title > body > code, matching the default
+-- weights of `ts_rank`. Queries must use the same `english` configuration (see `models::search`).
+
+CREATE FUNCTION post_search_vector(title TEXT, body TEXT, code TEXT) RETURNS tsvector AS $$
+    SELECT setweight(to_tsvector('english', title), 'A')
+        || setweight(to_tsvector('english', body), 'B')
+        || setweight(to_tsvector('english', coalesce(code, '')), 'C');
+$$ LANGUAGE SQL IMMUTABLE;
+
+ALTER TABLE posts ADD COLUMN search_vector tsvector NOT NULL DEFAULT '';
+
+UPDATE posts SET search_vector = post_search_vector(
+    title,
+    body,
+    (SELECT code FROM responses WHERE responses.post_id = posts.id)
+);
+
+CREATE INDEX posts_search_vector_idx ON posts USING GIN (search_vector);
+
+-- A response's code lives in its own table, so it can't be part of a generated column
+CREATE OR REPLACE FUNCTION update_post_search_vector() RETURNS trigger AS $$
+BEGIN
+    NEW.search_vector := post_search_vector(
+        NEW.title,
+        NEW.body,
+        (SELECT code FROM responses WHERE responses.post_id = NEW.id)
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION update_response_search_vector() RETURNS trigger AS $$
+BEGIN
+    UPDATE posts
+        SET search_vector = post_search_vector(posts.title, posts.body, NEW.code)
+        WHERE posts.id = NEW.post_id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER posts_update_search_vector
+    BEFORE INSERT OR UPDATE OF title, body ON posts
+    FOR EACH ROW EXECUTE FUNCTION update_post_search_vector();
+
+CREATE TRIGGER responses_update_search_vector
+    AFTER INSERT OR UPDATE OF code ON responses
+    FOR EACH ROW EXECUTE FUNCTION update_response_search_vector();
+
+ALTER TABLE comments ADD COLUMN search_vector tsvector NOT NULL
+    GENERATED ALWAYS AS (setweight(to_tsvector('english', body), 'B')) STORED;
+
+CREATE INDEX comments_search_vector_idx ON comments USING GIN (search_vector);
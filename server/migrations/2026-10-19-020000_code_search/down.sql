@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS responses_language_idx;
+ALTER TABLE responses DROP COLUMN IF EXISTS language;
+
+DROP INDEX IF EXISTS responses_code_trgm_idx;
+DROP EXTENSION IF EXISTS pg_trgm;
@@ -0,0 +1,9 @@
+-- Trigram index for `searchCode`, which supports both LIKE and regex matches.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX responses_code_trgm_idx ON responses USING GIN (code gin_trgm_ops);
+
+-- The language a response is written in, as named by its author. Searches match it case-insensitively.
+ALTER TABLE responses ADD COLUMN language VARCHAR(64);
+
+CREATE INDEX responses_language_idx ON responses (lower(language));
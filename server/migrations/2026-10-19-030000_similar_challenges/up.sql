@@ -0,0 +1,2 @@
+-- Lets `similarChallenges` find titles with `%` (trigram similarity) through an index.
+CREATE INDEX posts_title_trgm_idx ON posts USING GIN (title gin_trgm_ops);
@@ -0,0 +1,2 @@
+-- Links users to the `sub` claim of the tokens the authority issues for them.
+ALTER TABLE users ADD COLUMN auth_subject VARCHAR(255) UNIQUE;
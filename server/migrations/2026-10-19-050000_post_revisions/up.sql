@@ -0,0 +1,23 @@
+-- Every version of every post. Each row is a full snapshot of the post after an edit; the first
+-- revision of a post is its original content.
+CREATE TABLE post_revisions (
+    id SERIAL PRIMARY KEY,
+    post_id INT NOT NULL REFERENCES posts(id),
+    editor_id INT NOT NULL REFERENCES users(id),
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    -- only set for responses
+    code TEXT,
+    summary VARCHAR(256),
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX post_revisions_post_id_idx ON post_revisions (post_id, id);
+
+-- Earlier history is lost, so existing posts start out with their current contents.
+INSERT INTO post_revisions (post_id, editor_id, title, body, code, created_at)
+SELECT posts.id, posts.user_id, posts.title, posts.body, responses.code,
+    coalesce(posts.updated_at, posts.created_at)
+FROM posts
+LEFT JOIN responses ON responses.post_id = posts.id
+ORDER BY posts.id;
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS suggested_edits;
+
+DROP TYPE IF EXISTS suggested_edit_status;
+
+ALTER TABLE users DROP COLUMN IF EXISTS role;
+
+DROP TYPE IF EXISTS user_role;
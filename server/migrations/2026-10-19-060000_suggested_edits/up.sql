@@ -0,0 +1,34 @@
+CREATE TYPE user_role AS ENUM ('user', 'moderator', 'admin');
+
+ALTER TABLE users ADD COLUMN role user_role NOT NULL DEFAULT 'user';
+
+CREATE TYPE suggested_edit_status AS ENUM ('pending', 'approved', 'rejected');
+
+-- Edits proposed by users who don't own the post. They only change the post once the author or a
+-- moderator approves them.
+CREATE TABLE suggested_edits (
+    id SERIAL PRIMARY KEY,
+    post_id INT NOT NULL REFERENCES posts(id),
+    suggester_id INT NOT NULL REFERENCES users(id),
+    -- the revision the suggestion was made against. Approving it after the post has moved on
+    -- would silently undo the edits made since.
+    base_revision_id INT NOT NULL REFERENCES post_revisions(id),
+    -- NULL keeps the post's current value
+    title TEXT,
+    body TEXT,
+    comment VARCHAR(256) NOT NULL,
+    status suggested_edit_status NOT NULL DEFAULT 'pending',
+    reviewer_id INT REFERENCES users(id),
+    review_comment VARCHAR(256),
+    -- the revision an approved suggestion was applied as
+    revision_id INT REFERENCES post_revisions(id),
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    reviewed_at TIMESTAMP,
+    CHECK (title IS NOT NULL OR body IS NOT NULL)
+);
+
+CREATE INDEX suggested_edits_post_id_idx ON suggested_edits (post_id, id);
+
+-- A user may only have one suggestion awaiting review per post.
+CREATE UNIQUE INDEX suggested_edits_pending_idx ON suggested_edits (post_id, suggester_id)
+WHERE status = 'pending';
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS flags;
+
+DROP TYPE IF EXISTS flag_outcome;
+
+DROP TYPE IF EXISTS flag_reason;
@@ -0,0 +1,37 @@
+CREATE TYPE flag_reason AS ENUM ('spam', 'offensive', 'low_quality', 'needs_moderator', 'other');
+
+CREATE TYPE flag_outcome AS ENUM ('helpful', 'declined', 'disputed');
+
+-- Reports of problems with a post, comment or user, for moderators to handle.
+CREATE TABLE flags (
+    id SERIAL PRIMARY KEY,
+    flagger_id INT NOT NULL REFERENCES users(id),
+    -- exactly one of the targets is set
+    post_id INT REFERENCES posts(id),
+    comment_id INT REFERENCES comments(id),
+    user_id INT REFERENCES users(id),
+    reason flag_reason NOT NULL,
+    details VARCHAR(1000),
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    -- set together once a moderator handles the flag
+    handler_id INT REFERENCES users(id),
+    outcome flag_outcome,
+    resolution_note VARCHAR(256),
+    resolved_at TIMESTAMP,
+    CHECK (num_nonnulls(post_id, comment_id, user_id) = 1),
+    CHECK (num_nulls(handler_id, outcome, resolved_at) IN (0, 3))
+);
+
+CREATE INDEX flags_open_idx ON flags (id) WHERE outcome IS NULL;
+
+CREATE INDEX flags_post_id_idx ON flags (post_id) WHERE post_id IS NOT NULL;
+
+CREATE INDEX flags_comment_id_idx ON flags (comment_id) WHERE comment_id IS NOT NULL;
+
+CREATE INDEX flags_user_id_idx ON flags (user_id) WHERE user_id IS NOT NULL;
+
+-- A user may only have one open flag per target.
+CREATE UNIQUE INDEX flags_open_target_idx ON flags (
+    flagger_id, coalesce(post_id, 0), coalesce(comment_id, 0), coalesce(user_id, 0)
+)
+WHERE outcome IS NULL;
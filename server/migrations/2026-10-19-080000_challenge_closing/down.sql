@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS close_votes;
+
+DROP TYPE IF EXISTS close_vote_kind;
+
+ALTER TABLE challenges
+    DROP COLUMN IF EXISTS duplicate_of_id,
+    DROP COLUMN IF EXISTS close_reason,
+    DROP COLUMN IF EXISTS closed_at;
+
+DROP TYPE IF EXISTS close_reason;
@@ -0,0 +1,35 @@
+CREATE TYPE close_reason AS ENUM (
+    'duplicate', 'unclear', 'needs_details', 'no_winning_criterion', 'off_topic'
+);
+
+ALTER TABLE challenges
+    ADD COLUMN closed_at TIMESTAMP,
+    ADD COLUMN close_reason close_reason,
+    ADD COLUMN duplicate_of_id INT REFERENCES challenges(post_id),
+    ADD CHECK ((closed_at IS NULL) = (close_reason IS NULL)),
+    ADD CHECK (duplicate_of_id IS NULL OR close_reason = 'duplicate'),
+    ADD CHECK (duplicate_of_id <> post_id);
+
+CREATE TYPE close_vote_kind AS ENUM ('close', 'reopen');
+
+-- Votes to close or reopen a challenge. Once enough votes of a kind accumulate, or a moderator
+-- votes, the challenge is closed or reopened and every pending vote on it is marked spent.
+CREATE TABLE close_votes (
+    id SERIAL PRIMARY KEY,
+    challenge_id INT NOT NULL REFERENCES challenges(post_id),
+    voter_id INT NOT NULL REFERENCES users(id),
+    kind close_vote_kind NOT NULL,
+    -- close votes only
+    reason close_reason,
+    duplicate_of_id INT REFERENCES challenges(post_id),
+    -- cast by a moderator, and so took effect immediately
+    binding BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    spent_at TIMESTAMP,
+    CHECK ((kind = 'close') = (reason IS NOT NULL)),
+    CHECK (duplicate_of_id IS NULL OR reason = 'duplicate')
+);
+
+-- A user may only have one pending vote of each kind per challenge.
+CREATE UNIQUE INDEX close_votes_pending_idx ON close_votes (challenge_id, voter_id, kind)
+WHERE spent_at IS NULL;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS post_locks;
+
+DROP TYPE IF EXISTS lock_type;
@@ -0,0 +1,23 @@
+CREATE TYPE lock_type AS ENUM ('content_dispute', 'historical_significance', 'comments_only');
+
+-- Moderator locks on posts. A lock stops applying once it's lifted or its expiry passes; expired
+-- locks are marked lifted by a background task.
+CREATE TABLE post_locks (
+    id SERIAL PRIMARY KEY,
+    post_id INT NOT NULL REFERENCES posts(id),
+    lock_type lock_type NOT NULL,
+    reason VARCHAR(256),
+    locked_by_id INT NOT NULL REFERENCES users(id),
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    expires_at TIMESTAMP,
+    lifted_at TIMESTAMP,
+    -- NULL if the lock expired
+    lifted_by_id INT REFERENCES users(id),
+    CHECK (lifted_by_id IS NULL OR lifted_at IS NOT NULL)
+);
+
+-- A post has at most one lock that hasn't been lifted.
+CREATE UNIQUE INDEX post_locks_post_id_idx ON post_locks (post_id) WHERE lifted_at IS NULL;
+
+CREATE INDEX post_locks_expires_at_idx ON post_locks (expires_at)
+WHERE lifted_at IS NULL AND expires_at IS NOT NULL;
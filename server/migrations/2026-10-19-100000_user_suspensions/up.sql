@@ -0,0 +1,22 @@
+-- When the user's current suspension ends. Kept in sync with `user_suspensions` so that checking
+-- whether a user may act doesn't need a join.
+ALTER TABLE users ADD COLUMN suspended_until TIMESTAMP;
+
+-- Every suspension handed out, including ones that have ended, as a record of moderator actions.
+CREATE TABLE user_suspensions (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users(id),
+    moderator_id INT NOT NULL REFERENCES users(id),
+    reason VARCHAR(256) NOT NULL,
+    -- shown to the suspended user
+    message TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    ends_at TIMESTAMP NOT NULL,
+    lifted_at TIMESTAMP,
+    lifted_by_id INT REFERENCES users(id),
+    lift_note VARCHAR(256),
+    CHECK (ends_at > created_at),
+    CHECK ((lifted_at IS NULL) = (lifted_by_id IS NULL))
+);
+
+CREATE INDEX user_suspensions_user_id_idx ON user_suspensions (user_id, id);
@@ -0,0 +1,43 @@
+CREATE TYPE audit_action AS ENUM (
+    'lock_post',
+    'unlock_post',
+    'close_challenge',
+    'reopen_challenge',
+    'review_suggested_edit',
+    'resolve_flag',
+    'suspend_user',
+    'unsuspend_user'
+);
+
+-- A record of moderator and admin actions. Rows are written in the same transaction as the action
+-- they describe, and can't be changed or removed afterwards.
+CREATE TABLE audit_log (
+    id SERIAL PRIMARY KEY,
+    actor_id INT NOT NULL REFERENCES users(id),
+    action audit_action NOT NULL,
+    -- the target's node ID, split into its table and primary key
+    target_table VARCHAR(32) NOT NULL,
+    target_id INT NOT NULL,
+    -- what the action changed, before and after it; NULL where it didn't exist
+    before_state JSONB,
+    after_state JSONB,
+    ip VARCHAR(45),
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX audit_log_actor_id_idx ON audit_log (actor_id, id);
+CREATE INDEX audit_log_target_idx ON audit_log (target_table, target_id, id);
+
+CREATE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_no_update_or_delete
+BEFORE UPDATE OR DELETE ON audit_log
+FOR EACH ROW EXECUTE FUNCTION audit_log_append_only();
+
+CREATE TRIGGER audit_log_no_truncate
+BEFORE TRUNCATE ON audit_log
+FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
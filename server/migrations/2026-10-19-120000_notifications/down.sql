@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS notifications;
+
+DROP TYPE IF EXISTS notification_kind;
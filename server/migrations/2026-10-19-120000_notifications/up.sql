@@ -0,0 +1,28 @@
+CREATE TYPE notification_kind AS ENUM ('response', 'comment', 'mention');
+
+-- Tells users about activity involving them. Events of the same kind on the same post are
+-- batched into the recipient's unread notification for it, if they have one, so a busy post
+-- doesn't flood their inbox.
+CREATE TABLE notifications (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users(id),
+    kind notification_kind NOT NULL,
+    -- the post the activity happened on
+    post_id INT NOT NULL REFERENCES posts(id),
+    -- who caused the latest event, and the response or comment it was about, if any
+    actor_id INT NOT NULL REFERENCES users(id),
+    response_id INT REFERENCES responses(post_id),
+    comment_id INT REFERENCES comments(id),
+    event_count INT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    updated_at TIMESTAMP NOT NULL DEFAULT now(),
+    read_at TIMESTAMP
+);
+
+-- Notifications are listed by when they last had an event, newest first.
+CREATE INDEX notifications_user_id_updated_at_idx
+ON notifications (user_id, updated_at DESC, id DESC);
+
+-- The unread notification new events are batched into.
+CREATE UNIQUE INDEX notifications_unread_idx ON notifications (user_id, kind, post_id)
+WHERE read_at IS NULL;
@@ -0,0 +1,15 @@
+-- Users @-mentioned in a post's body or in a comment, resolved when the text is written. Exactly
+-- one of post_id and comment_id is set.
+CREATE TABLE mentions (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users(id),
+    post_id INT REFERENCES posts(id),
+    comment_id INT REFERENCES comments(id),
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    CHECK ((post_id IS NULL) <> (comment_id IS NULL))
+);
+
+CREATE UNIQUE INDEX mentions_post_idx ON mentions (post_id, user_id) WHERE post_id IS NOT NULL;
+CREATE UNIQUE INDEX mentions_comment_idx ON mentions (comment_id, user_id)
+WHERE comment_id IS NOT NULL;
+CREATE INDEX mentions_user_id_idx ON mentions (user_id);
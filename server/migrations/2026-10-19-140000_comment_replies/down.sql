@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS comments_parent_comment_id_idx;
+
+ALTER TABLE comments
+    DROP COLUMN IF EXISTS parent_comment_id,
+    DROP COLUMN IF EXISTS depth;
@@ -0,0 +1,7 @@
+-- Comments can reply to another comment on the same post. depth is 0 for top-level comments and
+-- one more than the parent's for replies.
+ALTER TABLE comments
+    ADD COLUMN parent_comment_id INT REFERENCES comments(id),
+    ADD COLUMN depth INT NOT NULL DEFAULT 0 CHECK (depth >= 0);
+
+CREATE INDEX comments_parent_comment_id_idx ON comments (parent_comment_id);
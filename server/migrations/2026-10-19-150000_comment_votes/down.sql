@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+
+DROP TRIGGER IF EXISTS comment_votes_update_score ON comment_votes;
+
+DROP FUNCTION IF EXISTS update_comment_score;
+
+DROP INDEX IF EXISTS comments_post_id_score_idx;
+
+ALTER TABLE comments DROP COLUMN IF EXISTS score;
+
+DROP TABLE IF EXISTS comment_votes;
@@ -0,0 +1,29 @@
+-- Upvotes on comments, at most one per user and comment. comments.score caches the count so
+-- comments can be sorted by it; triggers keep it in step.
+CREATE TABLE comment_votes (
+    user_id INT NOT NULL REFERENCES users(id),
+    comment_id INT NOT NULL REFERENCES comments(id),
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, comment_id)
+);
+
+CREATE INDEX comment_votes_comment_id_idx ON comment_votes (comment_id);
+
+ALTER TABLE comments ADD COLUMN score INT NOT NULL DEFAULT 0;
+
+CREATE INDEX comments_post_id_score_idx ON comments (post_id, score DESC, id);
+
+CREATE OR REPLACE FUNCTION update_comment_score() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        UPDATE comments SET score = score + 1 WHERE id = NEW.comment_id;
+    ELSE
+        UPDATE comments SET score = score - 1 WHERE id = OLD.comment_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER comment_votes_update_score
+    AFTER INSERT OR DELETE ON comment_votes
+    FOR EACH ROW EXECUTE FUNCTION update_comment_score();
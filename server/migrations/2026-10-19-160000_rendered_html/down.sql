@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE comments DROP COLUMN IF EXISTS body_html;
+
+ALTER TABLE post_revisions DROP COLUMN IF EXISTS body_html;
+
+ALTER TABLE posts DROP COLUMN IF EXISTS body_html;
@@ -0,0 +1,7 @@
+-- Rendered Markdown, stored whenever a body is written. Rows written before this are rendered
+-- whenever they're read instead, until they're next edited.
+ALTER TABLE posts ADD COLUMN body_html TEXT;
+
+ALTER TABLE post_revisions ADD COLUMN body_html TEXT;
+
+ALTER TABLE comments ADD COLUMN body_html TEXT;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE responses DROP COLUMN IF EXISTS code_html;
@@ -0,0 +1,3 @@
+-- Highlighted code, stored whenever a response's code is written. Responses written before this
+-- are highlighted whenever they're read instead, until their code is next edited.
+ALTER TABLE responses ADD COLUMN code_html TEXT;
@@ -0,0 +1,19 @@
+-- Files users have uploaded, kept in the storage backend under storage_key. post_id is set once
+-- a post's body refers to the file.
+CREATE TABLE attachments (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users(id),
+    post_id INT REFERENCES posts(id),
+    storage_key VARCHAR(64) NOT NULL UNIQUE,
+    -- as uploaded; only for display
+    filename VARCHAR(256) NOT NULL,
+    content_type VARCHAR(64) NOT NULL,
+    size INT NOT NULL,
+    -- only set for images
+    width INT,
+    height INT,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX attachments_user_id_idx ON attachments (user_id, id);
+CREATE INDEX attachments_post_id_idx ON attachments (post_id);
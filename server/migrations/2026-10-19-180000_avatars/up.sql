@@ -0,0 +1,3 @@
+-- The key shared by every size of the user's uploaded avatar; see storage::avatars. Users without
+-- one are shown an identicon.
+ALTER TABLE users ADD COLUMN avatar_key VARCHAR(64);
@@ -0,0 +1,73 @@
+use juniper::graphql_object;
+
+use crate::{
+    api::{Context, NodeId, NodeValue, User, errors::ApiResult, post::PostValue},
+    models::{self, SchemaTable},
+    storage::STORAGE,
+};
+
+/// A file a user uploaded to use in a post. Images are re-encoded on upload, so their metadata
+/// is gone.
+pub struct Attachment {
+    model: models::Attachment,
+}
+
+#[graphql_object]
+#[graphql(impl = [NodeValue], context = Context)]
+impl Attachment {
+    pub fn id(&self) -> NodeId {
+        NodeId::from((SchemaTable::Attachments, self.model.id))
+    }
+
+    /// Where the file can be downloaded from. Linking to it from a post's body attaches it to
+    /// the post.
+    pub fn url(&self) -> String {
+        STORAGE.url(&self.model.storage_key)
+    }
+
+    /// The name of the file on the uploader's machine.
+    pub fn filename(&self) -> &String {
+        &self.model.filename
+    }
+
+    pub fn content_type(&self) -> &String {
+        &self.model.content_type
+    }
+
+    /// The size of the stored file, in bytes.
+    pub fn size(&self) -> i32 {
+        self.model.size
+    }
+
+    /// The image's width in pixels. Null if the file isn't an image.
+    pub fn width(&self) -> Option<i32> {
+        self.model.width
+    }
+
+    /// The image's height in pixels. Null if the file isn't an image.
+    pub fn height(&self) -> Option<i32> {
+        self.model.height
+    }
+
+    pub async fn uploader(&self, ctx: &Context) -> ApiResult<User> {
+        Ok(ctx.loader.users.try_load(self.model.user_id).await??.into())
+    }
+
+    /// The post the file is attached to. Null until a post links to it.
+    pub async fn post(&self, ctx: &Context) -> ApiResult<Option<PostValue>> {
+        match self.model.post_id {
+            Some(post_id) => Ok(Some(PostValue::try_from_db_id(post_id, ctx).await?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn created_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.model.created_at.and_utc()
+    }
+}
+
+impl From<models::Attachment> for Attachment {
+    fn from(model: models::Attachment) -> Self {
+        Self { model }
+    }
+}
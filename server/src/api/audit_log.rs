@@ -0,0 +1,122 @@
+use juniper::{GraphQLEnum, graphql_object};
+
+use crate::{
+    api::{Context, NodeId, NodeValue, User, errors::ApiResult},
+    models::{self, SchemaTable, audit_log},
+    relayify,
+};
+
+#[derive(GraphQLEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AuditAction {
+    LockPost,
+    UnlockPost,
+    /// A moderator's binding vote closed a challenge.
+    CloseChallenge,
+    /// A moderator's binding vote reopened a challenge.
+    ReopenChallenge,
+    /// A moderator reviewed a suggested edit on someone else's post.
+    ReviewSuggestedEdit,
+    ResolveFlag,
+    SuspendUser,
+    UnsuspendUser,
+}
+
+impl From<AuditAction> for audit_log::AuditAction {
+    fn from(value: AuditAction) -> Self {
+        match value {
+            AuditAction::LockPost => Self::LockPost,
+            AuditAction::UnlockPost => Self::UnlockPost,
+            AuditAction::CloseChallenge => Self::CloseChallenge,
+            AuditAction::ReopenChallenge => Self::ReopenChallenge,
+            AuditAction::ReviewSuggestedEdit => Self::ReviewSuggestedEdit,
+            AuditAction::ResolveFlag => Self::ResolveFlag,
+            AuditAction::SuspendUser => Self::SuspendUser,
+            AuditAction::UnsuspendUser => Self::UnsuspendUser,
+        }
+    }
+}
+
+impl From<audit_log::AuditAction> for AuditAction {
+    fn from(value: audit_log::AuditAction) -> Self {
+        match value {
+            audit_log::AuditAction::LockPost => Self::LockPost,
+            audit_log::AuditAction::UnlockPost => Self::UnlockPost,
+            audit_log::AuditAction::CloseChallenge => Self::CloseChallenge,
+            audit_log::AuditAction::ReopenChallenge => Self::ReopenChallenge,
+            audit_log::AuditAction::ReviewSuggestedEdit => Self::ReviewSuggestedEdit,
+            audit_log::AuditAction::ResolveFlag => Self::ResolveFlag,
+            audit_log::AuditAction::SuspendUser => Self::SuspendUser,
+            audit_log::AuditAction::UnsuspendUser => Self::UnsuspendUser,
+        }
+    }
+}
+
+/// A moderator or admin action, as recorded when it happened. Only visible to admins.
+pub struct AuditLogEntry {
+    model: models::AuditLogEntry,
+}
+
+#[graphql_object]
+#[graphql(impl = [NodeValue], context = Context)]
+impl AuditLogEntry {
+    pub fn id(&self) -> NodeId {
+        NodeId::from((SchemaTable::AuditLog, self.model.id))
+    }
+
+    pub async fn actor(&self, ctx: &Context) -> ApiResult<User> {
+        Ok(ctx
+            .loader
+            .users
+            .try_load(self.model.actor_id)
+            .await??
+            .into())
+    }
+
+    pub fn action(&self) -> AuditAction {
+        self.model.action.into()
+    }
+
+    pub fn target_id(&self) -> ApiResult<NodeId> {
+        Ok(self.model.target()?.into())
+    }
+
+    /// What the action was done to, as it is now. Null if it no longer exists.
+    pub async fn target(&self, ctx: &Context) -> ApiResult<Option<NodeValue>> {
+        let (table, id) = self.model.target()?;
+        let mut cnx = ctx.db.get().await?;
+        Ok(table.find(id, &mut cnx).await?.map(NodeValue::from))
+    }
+
+    /// What the action changed, as it was before, JSON-encoded. Null if the action created it.
+    pub fn before(&self) -> Option<String> {
+        self.model.before_state.as_ref().map(|v| v.to_string())
+    }
+
+    /// What the action changed, as it was after, JSON-encoded.
+    pub fn after(&self) -> Option<String> {
+        self.model.after_state.as_ref().map(|v| v.to_string())
+    }
+
+    /// The address the request came from.
+    pub fn ip(&self) -> Option<&String> {
+        self.model.ip.as_ref()
+    }
+
+    pub fn created_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.model.created_at.and_utc()
+    }
+}
+
+impl AuditLogEntry {
+    pub fn is_visible_to(viewer: Option<&models::User>) -> bool {
+        viewer.is_some_and(|v| v.role == models::user::UserRole::Admin)
+    }
+}
+
+relayify!(AuditLogEntry);
+
+impl From<models::AuditLogEntry> for AuditLogEntry {
+    fn from(model: models::AuditLogEntry) -> Self {
+        Self { model }
+    }
+}
@@ -1,12 +1,24 @@
 use async_lock::OnceCell;
-use juniper::{FieldResult, graphql_object};
+use juniper::{GraphQLEnum, GraphQLObject, graphql_object};
 
 use crate::{
     api::{
-        Context, NodeId, NodeValue, comment::CommentConnection, post::PostValue,
-        response::ResponseConnection, user::User,
+        Context, NodeId, NodeValue,
+        attachment::Attachment,
+        comment::{CommentConnection, CommentOrder, TopComments},
+        errors::{ApiError, ApiResult},
+        post::PostValue,
+        post_lock::PostLock,
+        post_revision::PostRevisionConnection,
+        response::ResponseConnection,
+        suggested_edit::SuggestedEditConnection,
+        user::User,
     },
-    models, relayify,
+    models::{
+        self, SchemaTable, challenge,
+        close_vote::{CloseVote, CloseVoteKind},
+    },
+    relayify,
 };
 
 pub struct ChallengeType {
@@ -34,13 +46,48 @@ impl ChallengeType {
 impl From<models::ChallengeType> for ChallengeType {
     fn from(value: models::ChallengeType) -> Self {
         Self {
-            id: NodeId::from(("challenge_types", value.id)),
+            id: NodeId::from((SchemaTable::ChallengeTypes, value.id)),
             name: value.name,
             description: value.description,
         }
     }
 }
 
+#[derive(GraphQLEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CloseReason {
+    /// Asks the same thing as another challenge, given by `duplicateOf`.
+    Duplicate,
+    Unclear,
+    NeedsDetails,
+    /// Doesn't say objectively how responses are scored.
+    NoWinningCriterion,
+    OffTopic,
+}
+
+impl From<CloseReason> for challenge::CloseReason {
+    fn from(value: CloseReason) -> Self {
+        match value {
+            CloseReason::Duplicate => Self::Duplicate,
+            CloseReason::Unclear => Self::Unclear,
+            CloseReason::NeedsDetails => Self::NeedsDetails,
+            CloseReason::NoWinningCriterion => Self::NoWinningCriterion,
+            CloseReason::OffTopic => Self::OffTopic,
+        }
+    }
+}
+
+impl From<challenge::CloseReason> for CloseReason {
+    fn from(value: challenge::CloseReason) -> Self {
+        match value {
+            challenge::CloseReason::Duplicate => Self::Duplicate,
+            challenge::CloseReason::Unclear => Self::Unclear,
+            challenge::CloseReason::NeedsDetails => Self::NeedsDetails,
+            challenge::CloseReason::NoWinningCriterion => Self::NoWinningCriterion,
+            challenge::CloseReason::OffTopic => Self::OffTopic,
+        }
+    }
+}
+
 pub struct Challenge {
     db_id: i32,
     db_model: OnceCell<models::Challenge>,
@@ -50,12 +97,15 @@ pub struct Challenge {
 #[graphql(impl = [NodeValue, PostValue], context = Context)]
 impl Challenge {
     #[graphql(skip)]
-    async fn fetch_from_db<'c>(&self, ctx: &'c Context) -> FieldResult<&models::Challenge> {
+    async fn fetch_from_db(&self, ctx: &Context) -> ApiResult<&models::Challenge> {
         self.db_model
             .get_or_try_init(async || {
-                let mut cnx = ctx.db.get().await?;
-                let model = models::Challenge::find(&mut cnx, self.db_id).await?;
-                Ok(model)
+                Ok(ctx
+                    .loader
+                    .challenges
+                    .try_load(self.db_id)
+                    .await
+                    .map_err(|_| ApiError::not_found(self.id()))??)
             })
             .await
     }
@@ -79,28 +129,30 @@ impl Challenge {
     }
 
     pub fn id(&self) -> NodeId {
-        NodeId::from(("challenges", self.db_id))
+        NodeId::from((SchemaTable::Challenges, self.db_id))
     }
 
-    pub async fn title<'c>(&self, ctx: &'c Context) -> FieldResult<&String> {
+    pub async fn title(&self, ctx: &Context) -> ApiResult<&String> {
         Ok(&self.fetch_from_db(ctx).await?.post.title)
     }
 
-    pub async fn body<'c>(&self, ctx: &'c Context) -> FieldResult<&String> {
+    pub async fn body(&self, ctx: &Context) -> ApiResult<&String> {
         Ok(&self.fetch_from_db(ctx).await?.post.body)
     }
 
-    pub async fn created_at<'c>(
-        &self,
-        ctx: &'c Context,
-    ) -> FieldResult<chrono::DateTime<chrono::Utc>> {
+    /// The body rendered as sanitized HTML.
+    pub async fn body_html(&self, ctx: &Context) -> ApiResult<String> {
+        Ok(self.fetch_from_db(ctx).await?.post.render_body())
+    }
+
+    pub async fn created_at(&self, ctx: &Context) -> ApiResult<chrono::DateTime<chrono::Utc>> {
         Ok(self.fetch_from_db(ctx).await?.post.created_at.and_utc())
     }
 
-    pub async fn updated_at<'c>(
+    pub async fn updated_at(
         &self,
-        ctx: &'c Context,
-    ) -> FieldResult<Option<chrono::DateTime<chrono::Utc>>> {
+        ctx: &Context,
+    ) -> ApiResult<Option<chrono::DateTime<chrono::Utc>>> {
         Ok(self
             .fetch_from_db(ctx)
             .await?
@@ -109,10 +161,10 @@ impl Challenge {
             .map(|dt| dt.and_utc()))
     }
 
-    pub async fn deleted_at<'c>(
+    pub async fn deleted_at(
         &self,
-        ctx: &'c Context,
-    ) -> FieldResult<Option<chrono::DateTime<chrono::Utc>>> {
+        ctx: &Context,
+    ) -> ApiResult<Option<chrono::DateTime<chrono::Utc>>> {
         Ok(self
             .fetch_from_db(ctx)
             .await?
@@ -121,35 +173,167 @@ impl Challenge {
             .map(|dt| dt.and_utc()))
     }
 
-    pub async fn author<'c>(&self, ctx: &'c Context) -> FieldResult<User> {
+    pub async fn author(&self, ctx: &Context) -> ApiResult<User> {
         let user_id = self.fetch_from_db(ctx).await?.post.user_id;
         Ok(ctx.loader.users.try_load(user_id).await??.into())
     }
 
-    pub async fn challenge_type<'c>(&self, ctx: &'c Context) -> FieldResult<ChallengeType> {
+    /// The moderator lock currently restricting the post, if any.
+    pub async fn lock(&self, ctx: &Context) -> ApiResult<Option<PostLock>> {
+        let mut cnx = ctx.db.get().await?;
+        Ok(models::PostLock::find_active(&mut cnx, self.db_id)
+            .await?
+            .map(Into::into))
+    }
+
+    /// The users the post's body `@`-mentions.
+    pub async fn mentions(&self, ctx: &Context) -> ApiResult<Vec<User>> {
+        let mut cnx = ctx.db.get().await?;
+        Ok(models::mention::filter_by_post(&mut cnx, self.db_id)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// The uploaded files the post's body links to, oldest first.
+    pub async fn attachments(&self, ctx: &Context) -> ApiResult<Vec<Attachment>> {
+        let mut cnx = ctx.db.get().await?;
+        Ok(models::Attachment::filter_by_post(&mut cnx, self.db_id)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    pub async fn challenge_type(&self, ctx: &Context) -> ApiResult<ChallengeType> {
         Ok(self.fetch_from_db(ctx).await?.challenge_type.clone().into())
     }
 
-    pub async fn responses<'c>(
+    /// When the challenge was closed to new responses. Null while it's open.
+    pub async fn closed_at(
+        &self,
+        ctx: &Context,
+    ) -> ApiResult<Option<chrono::DateTime<chrono::Utc>>> {
+        Ok(self
+            .fetch_from_db(ctx)
+            .await?
+            .data
+            .closed_at
+            .map(|dt| dt.and_utc()))
+    }
+
+    pub async fn close_reason(&self, ctx: &Context) -> ApiResult<Option<CloseReason>> {
+        Ok(self
+            .fetch_from_db(ctx)
+            .await?
+            .data
+            .close_reason
+            .map(Into::into))
+    }
+
+    /// The challenge this one was closed as a duplicate of.
+    pub async fn duplicate_of(&self, ctx: &Context) -> ApiResult<Option<Challenge>> {
+        Ok(self
+            .fetch_from_db(ctx)
+            .await?
+            .data
+            .duplicate_of_id
+            .map(Challenge::new))
+    }
+
+    /// Votes cast toward closing the challenge, or toward reopening it if it's closed, that
+    /// haven't taken effect yet.
+    pub async fn pending_close_votes(&self, ctx: &Context) -> ApiResult<i32> {
+        let kind = if self.fetch_from_db(ctx).await?.data.is_closed() {
+            CloseVoteKind::Reopen
+        } else {
+            CloseVoteKind::Close
+        };
+        let mut cnx = ctx.db.get().await?;
+        Ok(CloseVote::count_pending(&mut cnx, self.db_id, kind)
+            .await?
+            .try_into()?)
+    }
+
+    pub async fn responses(
         &self,
-        ctx: &'c Context,
+        ctx: &Context,
         first: Option<i32>,
         after: Option<NodeId>,
         last: Option<i32>,
         before: Option<NodeId>,
-    ) -> FieldResult<ResponseConnection> {
+    ) -> ApiResult<ResponseConnection> {
         ResponseConnection::try_from_challenge(self.db_id, ctx, first, after, last, before).await
     }
 
-    pub async fn comments<'c>(
+    /// Comments on the challenge, oldest first unless ordered otherwise. Replies are left out if
+    /// `topLevelOnly` is set, to be fetched through their parents.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn comments(
         &self,
-        ctx: &'c Context,
+        ctx: &Context,
+        #[graphql(default = false)] top_level_only: bool,
+        order_by: Option<CommentOrder>,
         first: Option<i32>,
         after: Option<NodeId>,
         last: Option<i32>,
         before: Option<NodeId>,
-    ) -> FieldResult<CommentConnection> {
-        CommentConnection::try_from_challenge(self.db_id, ctx, first, after, last, before).await
+    ) -> ApiResult<CommentConnection> {
+        CommentConnection::try_from_challenge(
+            self.db_id,
+            ctx,
+            top_level_only,
+            order_by.unwrap_or_default(),
+            first,
+            after,
+            last,
+            before,
+        )
+        .await
+    }
+
+    /// The `count` most upvoted comments, oldest first, and how many others there are: the rest
+    /// are meant to be shown collapsed. Replies are left out if `topLevelOnly` is set.
+    pub async fn top_comments(
+        &self,
+        ctx: &Context,
+        #[graphql(default = 5)] count: i32,
+        #[graphql(default = false)] top_level_only: bool,
+    ) -> ApiResult<TopComments> {
+        TopComments::for_post(ctx, self.db_id, top_level_only, count).await
+    }
+
+    pub async fn revisions(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<PostRevisionConnection> {
+        PostRevisionConnection::try_from_post(self.db_id, ctx, first, after, last, before).await
+    }
+
+    pub async fn suggested_edits(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<SuggestedEditConnection> {
+        SuggestedEditConnection::try_from_post(self.db_id, ctx, first, after, last, before).await
+    }
+
+    /// Other challenges that look like this one, most similar first.
+    pub async fn related(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+    ) -> ApiResult<Vec<SimilarChallenge>> {
+        let post = &self.fetch_from_db(ctx).await?.post;
+        SimilarChallenge::find(ctx, &post.title, &post.body, Some(self.db_id), first).await
     }
 }
 
@@ -160,6 +344,52 @@ relayify!(
     })
 );
 
+/// How many similar challenges are returned when `first` isn't given.
+const DEFAULT_SIMILAR_CHALLENGES: i32 = 5;
+const MAX_SIMILAR_CHALLENGES: i32 = 25;
+
+#[derive(GraphQLObject)]
+#[graphql(context = Context)]
+pub struct SimilarChallenge {
+    challenge: Challenge,
+    /// How similar the challenge is. Only comparable between results of the same query.
+    score: f64,
+}
+
+impl SimilarChallenge {
+    pub(super) async fn find(
+        ctx: &Context,
+        title: &str,
+        body: &str,
+        exclude_id: Option<i32>,
+        first: Option<i32>,
+    ) -> ApiResult<Vec<Self>> {
+        let first = first.unwrap_or(DEFAULT_SIMILAR_CHALLENGES);
+        if !(0..=MAX_SIMILAR_CHALLENGES).contains(&first) {
+            return Err(ApiError::validation(
+                "first",
+                format!("must be between 0 and {MAX_SIMILAR_CHALLENGES}"),
+            ));
+        }
+        let mut cnx = ctx.db.get().await?;
+        let similar = models::challenge::SimilarChallenge::find(
+            &mut cnx,
+            title,
+            body,
+            exclude_id,
+            first.into(),
+        )
+        .await?;
+        Ok(similar
+            .into_iter()
+            .map(|s| Self {
+                challenge: Challenge::new(s.id),
+                score: s.score.into(),
+            })
+            .collect())
+    }
+}
+
 impl From<models::Challenge> for Challenge {
     fn from(value: models::Challenge) -> Self {
         Self::from_model(value)
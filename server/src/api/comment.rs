@@ -1,11 +1,40 @@
 use async_lock::OnceCell;
-use juniper::{FieldResult, graphql_object};
+use juniper::{GraphQLEnum, GraphQLObject, graphql_object};
 
 use crate::{
-    api::{Context, NodeId, NodeValue, User, post::PostValue},
-    models, relayify,
+    api::{
+        Context, NodeId, NodeValue, User,
+        errors::{ApiError, ApiResult},
+        post::PostValue,
+        relay::{build_connection, relay_connection_closure_args},
+    },
+    models::{self, SchemaTable, comment},
+    relayify,
 };
 
+const DEFAULT_MENTION_SUGGESTIONS: i32 = 5;
+const MAX_MENTION_SUGGESTIONS: i32 = 20;
+
+const MAX_TOP_COMMENTS: i32 = 100;
+
+#[derive(GraphQLEnum, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum CommentOrder {
+    /// Oldest first.
+    #[default]
+    Created,
+    /// Most upvoted first, then oldest first.
+    Score,
+}
+
+impl From<CommentOrder> for comment::CommentOrder {
+    fn from(value: CommentOrder) -> Self {
+        match value {
+            CommentOrder::Created => Self::Created,
+            CommentOrder::Score => Self::Score,
+        }
+    }
+}
+
 pub struct Comment {
     db_id: i32,
     db_model: OnceCell<models::Comment>,
@@ -15,12 +44,15 @@ pub struct Comment {
 #[graphql(impl = [NodeValue], context = Context)]
 impl Comment {
     #[graphql(skip)]
-    async fn fetch_from_db<'c>(&self, ctx: &'c Context) -> FieldResult<&models::Comment> {
+    async fn fetch_from_db(&self, ctx: &Context) -> ApiResult<&models::Comment> {
         self.db_model
             .get_or_try_init(async || {
-                let mut cnx = ctx.db.get().await?;
-                let model = models::Comment::find(&mut cnx, self.db_id).await?;
-                Ok(model)
+                Ok(ctx
+                    .loader
+                    .comments
+                    .try_load(self.db_id)
+                    .await
+                    .map_err(|_| ApiError::not_found(self.id()))??)
             })
             .await
     }
@@ -44,24 +76,27 @@ impl Comment {
     }
 
     pub fn id(&self) -> NodeId {
-        NodeId::from(("comments", self.db_id))
+        NodeId::from((SchemaTable::Comments, self.db_id))
     }
 
-    pub async fn body<'c>(&self, ctx: &'c Context) -> FieldResult<&String> {
+    pub async fn body(&self, ctx: &Context) -> ApiResult<&String> {
         Ok(&self.fetch_from_db(ctx).await?.body)
     }
 
-    pub async fn created_at<'c>(
-        &self,
-        ctx: &'c Context,
-    ) -> FieldResult<chrono::DateTime<chrono::Utc>> {
+    /// The body rendered as HTML. Only inline formatting is kept: emphasis, strikethrough, code
+    /// and links.
+    pub async fn body_html(&self, ctx: &Context) -> ApiResult<String> {
+        Ok(self.fetch_from_db(ctx).await?.render_body())
+    }
+
+    pub async fn created_at(&self, ctx: &Context) -> ApiResult<chrono::DateTime<chrono::Utc>> {
         Ok(self.fetch_from_db(ctx).await?.created_at.and_utc())
     }
 
-    pub async fn updated_at<'c>(
+    pub async fn updated_at(
         &self,
-        ctx: &'c Context,
-    ) -> FieldResult<Option<chrono::DateTime<chrono::Utc>>> {
+        ctx: &Context,
+    ) -> ApiResult<Option<chrono::DateTime<chrono::Utc>>> {
         Ok(self
             .fetch_from_db(ctx)
             .await?
@@ -69,15 +104,124 @@ impl Comment {
             .map(|dt| dt.and_utc()))
     }
 
-    pub async fn author<'c>(&self, ctx: &'c Context) -> FieldResult<User> {
+    pub async fn author(&self, ctx: &Context) -> ApiResult<User> {
         let user_id = self.fetch_from_db(ctx).await?.user_id;
         Ok(ctx.loader.users.try_load(user_id).await??.into())
     }
 
-    pub async fn post<'c>(&self, ctx: &'c Context) -> FieldResult<PostValue> {
+    pub async fn post(&self, ctx: &Context) -> ApiResult<PostValue> {
         let post_id = self.fetch_from_db(ctx).await?.post_id;
         PostValue::try_from_db_id(post_id, ctx).await
     }
+
+    /// How many users have upvoted the comment.
+    pub async fn score(&self, ctx: &Context) -> ApiResult<i32> {
+        Ok(self.fetch_from_db(ctx).await?.score)
+    }
+
+    /// Whether the viewer has upvoted the comment. False if not signed in.
+    pub async fn viewer_has_upvoted(&self, ctx: &Context) -> ApiResult<bool> {
+        let Some(viewer) = ctx.viewer().await? else {
+            return Ok(false);
+        };
+        let mut cnx = ctx.db.get().await?;
+        Ok(models::Comment::has_upvoted(&mut cnx, self.db_id, viewer.id).await?)
+    }
+
+    /// The comment this one replies to. Null for top-level comments.
+    pub async fn parent(&self, ctx: &Context) -> ApiResult<Option<Comment>> {
+        Ok(self
+            .fetch_from_db(ctx)
+            .await?
+            .parent_comment_id
+            .map(Comment::new))
+    }
+
+    /// How deeply the comment is nested: 0 for top-level comments, 1 for replies to them, and so
+    /// on.
+    pub async fn depth(&self, ctx: &Context) -> ApiResult<i32> {
+        Ok(self.fetch_from_db(ctx).await?.depth)
+    }
+
+    /// Direct replies to the comment, oldest first.
+    pub async fn replies(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<CommentConnection> {
+        CommentConnection::try_from_comment(self.db_id, ctx, first, after, last, before).await
+    }
+
+    /// The users the comment `@`-mentions.
+    pub async fn mentions(&self, ctx: &Context) -> ApiResult<Vec<User>> {
+        let mut cnx = ctx.db.get().await?;
+        Ok(models::mention::filter_by_comment(&mut cnx, self.db_id)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+}
+
+/// Users a comment on the post could mention whose names start with `prefix`, in name order.
+pub(super) async fn mention_suggestions(
+    ctx: &Context,
+    post_id: i32,
+    prefix: &str,
+    first: Option<i32>,
+) -> ApiResult<Vec<User>> {
+    let first = first.unwrap_or(DEFAULT_MENTION_SUGGESTIONS);
+    if !(0..=MAX_MENTION_SUGGESTIONS).contains(&first) {
+        return Err(ApiError::validation(
+            "first",
+            format!("must be between 0 and {MAX_MENTION_SUGGESTIONS}"),
+        ));
+    }
+    let prefix = prefix.strip_prefix('@').unwrap_or(prefix);
+    let mut cnx = ctx.db.get().await?;
+    Ok(
+        models::mention::suggest(&mut cnx, post_id, prefix, first as usize)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+    )
+}
+
+/// The highest-scoring comments on a post, with the rest collapsed.
+#[derive(GraphQLObject)]
+#[graphql(context = Context)]
+pub struct TopComments {
+    /// The top comments, oldest first.
+    comments: Vec<Comment>,
+    /// How many comments were left out.
+    hidden_count: i32,
+}
+
+impl TopComments {
+    pub(super) async fn for_post(
+        ctx: &Context,
+        post_id: i32,
+        top_level_only: bool,
+        count: i32,
+    ) -> ApiResult<Self> {
+        if !(0..=MAX_TOP_COMMENTS).contains(&count) {
+            return Err(ApiError::validation(
+                "count",
+                format!("must be between 0 and {MAX_TOP_COMMENTS}"),
+            ));
+        }
+        let mut cnx = ctx.db.get().await?;
+        let (comments, total) =
+            models::Comment::top_by_post(&mut cnx, post_id, top_level_only, count.into()).await?;
+        Ok(Self {
+            hidden_count: (total - comments.len() as i64).try_into()?,
+            comments: comments.into_iter().map(Comment::from).collect(),
+        })
+    }
 }
 
 impl From<models::Comment> for Comment {
@@ -91,13 +235,79 @@ relayify!(
     (User, async |cnx, user_id, after, before, limit| {
         models::Comment::filter_by_user(cnx, user_id, after, before, limit).await
     }),
-    (
-        Challenge,
-        async |cnx, challenge_id, after, before, limit| {
-            models::Comment::filter_by_challenge(cnx, challenge_id, after, before, limit).await
-        }
-    ),
-    (Response, async |cnx, response_id, after, before, limit| {
-        models::Comment::filter_by_response(cnx, response_id, after, before, limit).await
+    (Comment, async |cnx, parent_id, after, before, limit| {
+        models::Comment::filter_by_parent(cnx, parent_id, after, before, limit).await
     })
 );
+
+impl CommentConnection {
+    /// Comments on a challenge in the given order. Replies are left out if `top_level_only` is
+    /// set.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn try_from_challenge(
+        challenge_id: i32,
+        ctx: &Context,
+        top_level_only: bool,
+        order: CommentOrder,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<Self> {
+        let (after, before, limit) = relay_connection_closure_args(first, after, before);
+        let mut cnx = ctx.db.get().await?;
+        let nodes = models::Comment::filter_by_challenge(
+            &mut cnx,
+            challenge_id,
+            top_level_only,
+            order.into(),
+            after,
+            before,
+            limit,
+        )
+        .await?;
+        Self::from_nodes(first, last, nodes)
+    }
+
+    /// Comments on a response in the given order. Replies are left out if `top_level_only` is
+    /// set.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn try_from_response(
+        response_id: i32,
+        ctx: &Context,
+        top_level_only: bool,
+        order: CommentOrder,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<Self> {
+        let (after, before, limit) = relay_connection_closure_args(first, after, before);
+        let mut cnx = ctx.db.get().await?;
+        let nodes = models::Comment::filter_by_response(
+            &mut cnx,
+            response_id,
+            top_level_only,
+            order.into(),
+            after,
+            before,
+            limit,
+        )
+        .await?;
+        Self::from_nodes(first, last, nodes)
+    }
+
+    fn from_nodes(
+        first: Option<i32>,
+        last: Option<i32>,
+        nodes: Vec<models::Comment>,
+    ) -> ApiResult<Self> {
+        let (page_info, edges) = build_connection(
+            first,
+            last,
+            nodes.into_iter().map(Comment::from).collect(),
+            CommentConnectionEdge::new,
+        )?;
+        Ok(Self::new(edges, page_info))
+    }
+}
@@ -0,0 +1,211 @@
+use std::{fmt::Display, sync::Arc};
+
+use juniper::{FieldError, IntoFieldError, ScalarValue, Value, graphql_value};
+
+use crate::models::{
+    InvalidPrimaryKeyError, InvalidTableNameError, UnknownPostKindError,
+    close_vote::CloseVoteError, response::ResponseCreateError, search::CodeSearchError,
+};
+
+/// Internal error messages (database errors, etc.) are only sent to clients by debug builds.
+const EXPOSE_INTERNAL_ERRORS: bool = cfg!(debug_assertions);
+
+/// A validation failure on a single input field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldViolation {
+    pub field: String,
+    pub message: String,
+}
+
+/// Errors returned by resolvers. Each variant is reported to clients with a stable
+/// `extensions.code`, so they can tell e.g. a missing node from a forbidden one.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request isn't signed in as a user, but needs to be.
+    Unauthenticated,
+    NotFound(String),
+    Forbidden(String),
+    Validation(Vec<FieldViolation>),
+    RateLimited {
+        retry_after_secs: Option<i32>,
+    },
+    Internal(anyhow::Error),
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+impl ApiError {
+    pub fn not_found(what: impl Display) -> Self {
+        Self::NotFound(what.to_string())
+    }
+
+    pub fn forbidden(reason: impl Display) -> Self {
+        Self::Forbidden(reason.to_string())
+    }
+
+    /// A validation error on a single field.
+    pub fn validation(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::Validation(vec![FieldViolation {
+            field: field.into(),
+            message: message.into(),
+        }])
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Unauthenticated => "UNAUTHENTICATED",
+            Self::NotFound(_) => "NOT_FOUND",
+            Self::Forbidden(_) => "FORBIDDEN",
+            Self::Validation(_) => "VALIDATION",
+            Self::RateLimited { .. } => "RATE_LIMITED",
+            Self::Internal(_) => "INTERNAL",
+        }
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unauthenticated => write!(f, "you must be signed in"),
+            Self::NotFound(what) => write!(f, "not found: {what}"),
+            Self::Forbidden(reason) => write!(f, "forbidden: {reason}"),
+            Self::Validation(violations) => {
+                write!(f, "invalid input")?;
+                for (i, v) in violations.iter().enumerate() {
+                    let sep = if i == 0 { ": " } else { "; " };
+                    write!(f, "{sep}{}: {}", v.field, v.message)?;
+                }
+                Ok(())
+            }
+            Self::RateLimited { .. } => write!(f, "rate limited"),
+            Self::Internal(e) if EXPOSE_INTERNAL_ERRORS => write!(f, "internal error: {e:#}"),
+            Self::Internal(_) => write!(f, "internal error"),
+        }
+    }
+}
+
+impl<S: ScalarValue> IntoFieldError<S> for ApiError {
+    fn into_field_error(self) -> FieldError<S> {
+        let code = self.code();
+        let extensions: Value<S> = match &self {
+            Self::Validation(violations) => {
+                let fields: Vec<Value<S>> = violations
+                    .iter()
+                    .map(|v| {
+                        graphql_value!({
+                            "field": (v.field.as_str()),
+                            "message": (v.message.as_str()),
+                        })
+                    })
+                    .collect();
+                graphql_value!({ "code": code, "fields": (Value::list(fields)) })
+            }
+            Self::RateLimited {
+                retry_after_secs: Some(secs),
+            } => graphql_value!({ "code": code, "retryAfter": (*secs) }),
+            Self::Internal(e) => {
+                log::error!("internal error: {e:#}");
+                graphql_value!({ "code": code })
+            }
+            _ => graphql_value!({ "code": code }),
+        };
+        FieldError::new(self, extensions)
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(value: anyhow::Error) -> Self {
+        if let Some(diesel::result::Error::NotFound) = value.downcast_ref() {
+            Self::not_found("record")
+        } else if let Some(e) = value.downcast_ref::<InvalidPrimaryKeyError>() {
+            Self::validation("id", e.to_string())
+        } else {
+            Self::Internal(value)
+        }
+    }
+}
+
+/// Errors shared between every key of a failed dataloader batch.
+impl From<Arc<anyhow::Error>> for ApiError {
+    fn from(value: Arc<anyhow::Error>) -> Self {
+        match value.downcast_ref() {
+            Some(diesel::result::Error::NotFound) => Self::not_found("record"),
+            _ => Self::Internal(anyhow::anyhow!("{value:#}")),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for ApiError {
+    fn from(value: diesel::result::Error) -> Self {
+        anyhow::Error::from(value).into()
+    }
+}
+
+impl From<crate::db::PoolError> for ApiError {
+    fn from(value: crate::db::PoolError) -> Self {
+        Self::Internal(value.into())
+    }
+}
+
+/// The dataloader reports keys missing from a batch as [`std::io::ErrorKind::NotFound`].
+impl From<std::io::Error> for ApiError {
+    fn from(value: std::io::Error) -> Self {
+        match value.kind() {
+            std::io::ErrorKind::NotFound => Self::not_found("record"),
+            _ => Self::Internal(value.into()),
+        }
+    }
+}
+
+impl From<std::num::TryFromIntError> for ApiError {
+    fn from(value: std::num::TryFromIntError) -> Self {
+        Self::Internal(value.into())
+    }
+}
+
+impl From<actix_web::error::BlockingError> for ApiError {
+    fn from(value: actix_web::error::BlockingError) -> Self {
+        Self::Internal(value.into())
+    }
+}
+
+impl From<UnknownPostKindError> for ApiError {
+    fn from(value: UnknownPostKindError) -> Self {
+        Self::Internal(value.into())
+    }
+}
+
+/// A table name stored in the database that no longer names a [`crate::models::SchemaTable`].
+impl From<InvalidTableNameError> for ApiError {
+    fn from(value: InvalidTableNameError) -> Self {
+        Self::Internal(value.into())
+    }
+}
+
+impl From<CodeSearchError> for ApiError {
+    fn from(value: CodeSearchError) -> Self {
+        match value {
+            CodeSearchError::Query(e) => e.into(),
+            e => Self::validation("pattern", e.to_string()),
+        }
+    }
+}
+
+impl From<CloseVoteError> for ApiError {
+    fn from(value: CloseVoteError) -> Self {
+        match value {
+            CloseVoteError::Query(e) => e.into(),
+            CloseVoteError::InvalidDuplicate => Self::validation("duplicateOf", value.to_string()),
+            e => Self::validation("id", e.to_string()),
+        }
+    }
+}
+
+impl From<ResponseCreateError> for ApiError {
+    fn from(value: ResponseCreateError) -> Self {
+        match value {
+            ResponseCreateError::Query(e) => e.into(),
+            e => Self::forbidden(e),
+        }
+    }
+}
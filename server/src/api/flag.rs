@@ -0,0 +1,184 @@
+use juniper::{GraphQLEnum, GraphQLUnion, graphql_object};
+
+use crate::{
+    api::{
+        Challenge, Comment, Context, NodeId, NodeValue, Response, User,
+        errors::ApiResult,
+        post::{PostValue, PostValueEnum},
+    },
+    models::{self, SchemaTable, flag},
+    relayify,
+};
+
+#[derive(GraphQLEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FlagReason {
+    Spam,
+    Offensive,
+    LowQuality,
+    /// Needs a moderator's attention for a reason not covered by the others. Requires details.
+    NeedsModerator,
+    /// Requires details.
+    Other,
+}
+
+impl From<FlagReason> for flag::FlagReason {
+    fn from(value: FlagReason) -> Self {
+        match value {
+            FlagReason::Spam => Self::Spam,
+            FlagReason::Offensive => Self::Offensive,
+            FlagReason::LowQuality => Self::LowQuality,
+            FlagReason::NeedsModerator => Self::NeedsModerator,
+            FlagReason::Other => Self::Other,
+        }
+    }
+}
+
+impl From<flag::FlagReason> for FlagReason {
+    fn from(value: flag::FlagReason) -> Self {
+        match value {
+            flag::FlagReason::Spam => Self::Spam,
+            flag::FlagReason::Offensive => Self::Offensive,
+            flag::FlagReason::LowQuality => Self::LowQuality,
+            flag::FlagReason::NeedsModerator => Self::NeedsModerator,
+            flag::FlagReason::Other => Self::Other,
+        }
+    }
+}
+
+#[derive(GraphQLEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FlagOutcome {
+    /// The flag pointed out a real problem, which was dealt with.
+    Helpful,
+    /// Nothing needed to be done.
+    Declined,
+    /// Moderators couldn't agree whether the flag was valid.
+    Disputed,
+}
+
+impl From<FlagOutcome> for flag::FlagOutcome {
+    fn from(value: FlagOutcome) -> Self {
+        match value {
+            FlagOutcome::Helpful => Self::Helpful,
+            FlagOutcome::Declined => Self::Declined,
+            FlagOutcome::Disputed => Self::Disputed,
+        }
+    }
+}
+
+impl From<flag::FlagOutcome> for FlagOutcome {
+    fn from(value: flag::FlagOutcome) -> Self {
+        match value {
+            flag::FlagOutcome::Helpful => Self::Helpful,
+            flag::FlagOutcome::Declined => Self::Declined,
+            flag::FlagOutcome::Disputed => Self::Disputed,
+        }
+    }
+}
+
+#[derive(GraphQLEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FlagTargetType {
+    Post,
+    Comment,
+    User,
+}
+
+impl From<FlagTargetType> for flag::FlagTargetKind {
+    fn from(value: FlagTargetType) -> Self {
+        match value {
+            FlagTargetType::Post => Self::Post,
+            FlagTargetType::Comment => Self::Comment,
+            FlagTargetType::User => Self::User,
+        }
+    }
+}
+
+#[derive(GraphQLUnion)]
+#[graphql(context = Context)]
+pub enum FlagTarget {
+    Challenge(Challenge),
+    Response(Response),
+    Comment(Comment),
+    User(User),
+}
+
+/// A report that a post, comment or user needs a moderator's attention. Only visible to the user
+/// who raised it and to moderators.
+pub struct Flag {
+    model: models::Flag,
+}
+
+#[graphql_object]
+#[graphql(impl = [NodeValue], context = Context)]
+impl Flag {
+    pub fn id(&self) -> NodeId {
+        NodeId::from((SchemaTable::Flags, self.model.id))
+    }
+
+    pub async fn flagger(&self, ctx: &Context) -> ApiResult<User> {
+        Ok(ctx
+            .loader
+            .users
+            .try_load(self.model.flagger_id)
+            .await??
+            .into())
+    }
+
+    pub async fn target(&self, ctx: &Context) -> ApiResult<FlagTarget> {
+        Ok(match self.model.target() {
+            flag::FlagTarget::Post(id) => match PostValue::try_from_db_id(id, ctx).await? {
+                PostValueEnum::Challenge(c) => FlagTarget::Challenge(c),
+                PostValueEnum::Response(r) => FlagTarget::Response(r),
+            },
+            flag::FlagTarget::Comment(id) => FlagTarget::Comment(Comment::new(id)),
+            flag::FlagTarget::User(id) => FlagTarget::User(User::new(id)),
+        })
+    }
+
+    pub fn reason(&self) -> FlagReason {
+        self.model.reason.into()
+    }
+
+    pub fn details(&self) -> Option<&String> {
+        self.model.details.as_ref()
+    }
+
+    pub fn created_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.model.created_at.and_utc()
+    }
+
+    /// The moderator who resolved the flag. Null while it's open.
+    pub async fn handler(&self, ctx: &Context) -> ApiResult<Option<User>> {
+        match self.model.handler_id {
+            Some(id) => Ok(Some(ctx.loader.users.try_load(id).await??.into())),
+            None => Ok(None),
+        }
+    }
+
+    /// Null while the flag is open.
+    pub fn outcome(&self) -> Option<FlagOutcome> {
+        self.model.outcome.map(Into::into)
+    }
+
+    pub fn resolution_note(&self) -> Option<&String> {
+        self.model.resolution_note.as_ref()
+    }
+
+    pub fn resolved_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.model.resolved_at.map(|t| t.and_utc())
+    }
+}
+
+impl Flag {
+    /// Whether `viewer` may see the flag: moderators see every flag, other users only their own.
+    pub fn is_visible_to(model: &models::Flag, viewer: Option<&models::User>) -> bool {
+        viewer.is_some_and(|v| v.role.is_moderator() || v.id == model.flagger_id)
+    }
+}
+
+relayify!(Flag);
+
+impl From<models::Flag> for Flag {
+    fn from(model: models::Flag) -> Self {
+        Self { model }
+    }
+}
@@ -0,0 +1,98 @@
+use juniper::{GraphQLEnum, GraphQLObject};
+
+use crate::{
+    api::{Context, User, errors::ApiResult, mutation::MAX_COMMENT_LENGTH},
+    models::mention,
+    render::markdown,
+};
+
+/// Where Markdown is being written, which decides how it's rendered.
+#[derive(GraphQLEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MarkdownContext {
+    /// The body of a challenge or response.
+    Post,
+    /// A comment. Only inline formatting is rendered.
+    Comment,
+}
+
+#[derive(GraphQLObject)]
+pub struct CodeBlock {
+    /// The first word of a fenced block's info string, if any.
+    language: Option<String>,
+    code: String,
+}
+
+impl From<markdown::CodeBlock> for CodeBlock {
+    fn from(value: markdown::CodeBlock) -> Self {
+        Self {
+            language: value.language,
+            code: value.code,
+        }
+    }
+}
+
+/// How text would come out if it were saved.
+#[derive(GraphQLObject)]
+#[graphql(context = Context)]
+pub struct MarkdownPreview {
+    /// The sanitized HTML `bodyHtml` would return.
+    html: String,
+    /// The users the text would mention.
+    mentions: Vec<User>,
+    code_blocks: Vec<CodeBlock>,
+    /// Reasons the text would be rejected, or would come out differently than it looks like it
+    /// should.
+    warnings: Vec<String>,
+}
+
+impl MarkdownPreview {
+    pub(super) async fn render(
+        ctx: &Context,
+        text: &str,
+        context: MarkdownContext,
+    ) -> ApiResult<Self> {
+        let mut warnings = Vec::new();
+        let code_blocks = markdown::code_blocks(text);
+        let names = mention::parse_mentions(text);
+        let mut cnx = ctx.db.get().await?;
+        let mentions = mention::find_mentioned_users(&mut cnx, text).await?;
+        warn_unmatched(&mut warnings, &names, &mentions);
+        let html = match context {
+            MarkdownContext::Post => markdown::render_post(text),
+            MarkdownContext::Comment => {
+                if text.trim().is_empty() {
+                    warnings.push("comment must not be blank".to_owned());
+                }
+                if text.chars().count() > MAX_COMMENT_LENGTH {
+                    warnings.push(format!(
+                        "comment must be at most {MAX_COMMENT_LENGTH} characters"
+                    ));
+                }
+                if !code_blocks.is_empty() {
+                    warnings.push(
+                        "comments can't contain code blocks, they'll show as plain text".to_owned(),
+                    );
+                }
+                markdown::render_comment(text)
+            }
+        };
+        Ok(Self {
+            html,
+            mentions: mentions.into_iter().map(Into::into).collect(),
+            code_blocks: code_blocks.into_iter().map(Into::into).collect(),
+            warnings,
+        })
+    }
+}
+
+/// Warns about each of the mentioned `names` that doesn't belong to one of the `matched` users.
+fn warn_unmatched(warnings: &mut Vec<String>, names: &[&str], matched: &[crate::models::User]) {
+    for name in names {
+        if !matched
+            .iter()
+            .any(|u| u.name.to_lowercase() == name.to_lowercase())
+        {
+            warnings.push(format!("@{name} doesn't match any user"));
+        }
+    }
+}
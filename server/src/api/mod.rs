@@ -1,38 +1,68 @@
-use std::{fmt::Display, str::FromStr};
+use std::{collections::HashMap, fmt::Display, str::FromStr, sync::Mutex};
 
 use crate::{
     api::{
-        challenge::{ChallengeConnection, ChallengeConnectionEdge},
+        attachment::Attachment,
+        audit_log::{
+            AuditAction, AuditLogEntry, AuditLogEntryConnection, AuditLogEntryConnectionEdge,
+        },
+        challenge::{ChallengeConnection, ChallengeConnectionEdge, SimilarChallenge},
+        errors::{ApiError, ApiResult},
+        flag::{Flag, FlagConnection, FlagConnectionEdge, FlagReason, FlagTargetType},
+        markdown_preview::{MarkdownContext, MarkdownPreview},
+        notification::Notification,
         post::PostValue,
+        post_revision::{PostRevision, RevisionDiff},
         relay::{build_connection, relay_connection_closure_args},
+        search::{CodeSearchConnection, SearchConnection, SearchResultType},
+        suggested_edit::SuggestedEdit,
+        upload::UploadedFile,
+        user_suspension::UserSuspension,
     },
-    db::{DB_POOL, DbPool, Loader, Repository},
+    db::{DB_EVENTS, DB_POOL, DbPool, EventBus, Loader},
     models::{self, SchemaModel, SchemaTable},
 };
+use async_lock::OnceCell;
 use base64::{Engine, prelude::BASE64_URL_SAFE};
+use diesel::OptionalExtension;
 use juniper::{
-    DefaultScalarValue, EmptyMutation, EmptySubscription, FieldResult, GraphQLScalar, ID,
-    InputValue, ParseScalarResult, ParseScalarValue, RootNode, ScalarToken, ScalarValue, Value,
-    graphql_interface, graphql_object,
+    DefaultScalarValue, GraphQLScalar, ID, InputValue, ParseScalarResult, ParseScalarValue,
+    RootNode, ScalarToken, ScalarValue, Value, graphql_interface, graphql_object,
 };
 
+pub mod attachment;
+pub mod audit_log;
 pub mod challenge;
 pub mod comment;
+pub mod errors;
+pub mod flag;
+pub mod markdown_preview;
+pub mod mutation;
+pub mod notification;
 pub mod post;
+pub mod post_lock;
+pub mod post_revision;
 pub mod response;
+pub mod search;
+pub mod subscription;
+pub mod suggested_edit;
+pub mod upload;
 pub mod user;
+pub mod user_suspension;
 
 pub mod relay;
 
 pub use challenge::{Challenge, ChallengeType};
 pub use comment::Comment;
+pub use mutation::Mutation;
 pub use post::Post;
 pub use response::Response;
+pub use subscription::Subscription;
 pub use user::User;
 
 #[derive(GraphQLScalar, Clone, Debug, PartialEq, Eq)]
 #[graphql(with = Self)]
-pub struct NodeId(pub String, pub i32);
+pub struct NodeId(pub SchemaTable, pub i32);
 
 impl NodeId {
     fn to_output<S: ScalarValue>(&self) -> Value<S> {
@@ -46,7 +76,7 @@ impl NodeId {
             .as_string_value()
             .ok_or_else(|| format!("Expected `String`, found {input}"))
             .and_then(|str| {
-                let (table, id) = decode_id(&ID::new(str)).map_err(|e| e.message().to_string())?;
+                let (table, id) = decode_id(&ID::new(str)).map_err(|e| e.to_string())?;
                 Ok(Self(table, id))
             })
     }
@@ -54,15 +84,27 @@ impl NodeId {
     fn parse_token<S: ScalarValue>(t: ScalarToken<'_>) -> ParseScalarResult<S> {
         <String as ParseScalarValue<S>>::from_str(t)
     }
+
+    /// Returns the database id if this id belongs to one of `tables`. `field` names the
+    /// argument the id was passed as, for error reporting.
+    pub fn expect_table(&self, field: &str, tables: &[SchemaTable]) -> ApiResult<i32> {
+        if tables.contains(&self.0) {
+            Ok(self.1)
+        } else {
+            Err(ApiError::validation(
+                field,
+                format!("unexpected {} id: {self}", self.0),
+            ))
+        }
+    }
 }
 
-impl<T, U> From<(T, U)> for NodeId
+impl<U> From<(SchemaTable, U)> for NodeId
 where
-    T: Into<String>,
     U: Into<i32>,
 {
-    fn from(value: (T, U)) -> Self {
-        Self(value.0.into(), value.1.into())
+    fn from(value: (SchemaTable, U)) -> Self {
+        Self(value.0, value.1.into())
     }
 }
 
@@ -71,21 +113,22 @@ impl FromStr for NodeId {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         decode_id(&ID::new(s))
             .map(Self::from)
-            .map_err(|e| e.message().to_string())
+            .map_err(|e| e.to_string())
     }
 }
 
-impl ToString for NodeId {
-    fn to_string(&self) -> String {
-        self.to_output::<DefaultScalarValue>()
-            .as_string_value()
-            .unwrap()
-            .to_string()
+impl Display for NodeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            self.to_output::<DefaultScalarValue>()
+                .as_string_value()
+                .unwrap(),
+        )
     }
 }
 
 #[graphql_interface]
-#[graphql(for = [User, Challenge, ChallengeType, Response, Comment, PostValue], context = Context)]
+#[graphql(for = [User, Challenge, ChallengeType, Response, Comment, PostValue, PostRevision, SuggestedEdit, Flag, UserSuspension, AuditLogEntry, Notification, Attachment], context = Context)]
 pub struct Node {
     id: NodeId,
 }
@@ -93,11 +136,20 @@ pub struct Node {
 impl From<SchemaModel> for NodeValue {
     fn from(value: SchemaModel) -> Self {
         match value {
+            SchemaModel::Attachment(m) => NodeValueEnum::Attachment(Attachment::from(m)),
+            SchemaModel::AuditLogEntry(m) => NodeValueEnum::AuditLogEntry(AuditLogEntry::from(m)),
             SchemaModel::Challenge(m) => NodeValueEnum::Challenge(Challenge::from(m)),
             SchemaModel::User(m) => NodeValueEnum::User(User::from(m)),
             SchemaModel::ChallengeType(m) => NodeValueEnum::ChallengeType(ChallengeType::from(m)),
             SchemaModel::Comment(m) => NodeValueEnum::Comment(Comment::from(m)),
+            SchemaModel::Flag(m) => NodeValueEnum::Flag(Flag::from(m)),
+            SchemaModel::Notification(m) => NodeValueEnum::Notification(Notification::from(m)),
             SchemaModel::Response(m) => NodeValueEnum::Response(Response::from(m)),
+            SchemaModel::PostRevision(m) => NodeValueEnum::PostRevision(PostRevision::from(m)),
+            SchemaModel::SuggestedEdit(m) => NodeValueEnum::SuggestedEdit(SuggestedEdit::from(m)),
+            SchemaModel::UserSuspension(m) => {
+                NodeValueEnum::UserSuspension(UserSuspension::from(m))
+            }
         }
     }
 }
@@ -105,15 +157,89 @@ impl From<SchemaModel> for NodeValue {
 pub struct Context {
     pub loader: Loader,
     pub db: DbPool,
+    pub events: EventBus,
+    /// The `sub` claim of the request's token, if it has one.
+    subject: Option<String>,
+    /// The address the request came from, if known.
+    ip: Option<String>,
+    viewer: OnceCell<Option<models::User>>,
+    /// Files sent with a multipart request, by the name of the form field they came in.
+    uploads: Mutex<HashMap<String, UploadedFile>>,
 }
 
 impl Context {
+    /// A context for a request that isn't signed in.
     pub fn try_new() -> anyhow::Result<Self> {
+        Self::try_with_subject(None)
+    }
+
+    pub fn try_with_subject(subject: Option<String>) -> anyhow::Result<Self> {
         Ok(Self {
             loader: Loader::new(&DB_POOL),
             db: DB_POOL.clone(),
+            events: DB_EVENTS.clone(),
+            subject,
+            ip: None,
+            viewer: OnceCell::new(),
+            uploads: Mutex::new(HashMap::new()),
         })
     }
+
+    /// Sets the address the request came from, which is recorded in the audit log.
+    pub fn with_ip(self, ip: Option<String>) -> Self {
+        Self { ip, ..self }
+    }
+
+    /// Makes the files sent with a multipart request available to `Upload` arguments.
+    pub fn with_uploads(self, uploads: HashMap<String, UploadedFile>) -> Self {
+        Self {
+            uploads: Mutex::new(uploads),
+            ..self
+        }
+    }
+
+    pub fn ip(&self) -> Option<&str> {
+        self.ip.as_deref()
+    }
+
+    /// The user the request is signed in as. `None` if the request has no token, or if no
+    /// user is linked to the token's subject.
+    pub async fn viewer(&self) -> ApiResult<Option<&models::User>> {
+        let Some(subject) = &self.subject else {
+            return Ok(None);
+        };
+        let viewer = self
+            .viewer
+            .get_or_try_init(async || {
+                let mut cnx = self.db.get().await?;
+                ApiResult::Ok(
+                    models::User::find_by_auth_subject(&mut cnx, subject)
+                        .await
+                        .optional()?,
+                )
+            })
+            .await?;
+        Ok(viewer.as_ref())
+    }
+
+    /// Like [`Self::viewer`], but fails with [`ApiError::Unauthenticated`] instead of
+    /// returning `None`.
+    pub async fn require_viewer(&self) -> ApiResult<&models::User> {
+        self.viewer().await?.ok_or(ApiError::Unauthenticated)
+    }
+
+    /// Like [`Self::require_viewer`], but also fails if the viewer is suspended. Every mutation
+    /// goes through this, since suspended users may only read.
+    pub async fn require_active_viewer(&self) -> ApiResult<&models::User> {
+        let viewer = self.require_viewer().await?;
+        match viewer.suspended_until {
+            Some(until) if viewer.is_suspended() => Err(ApiError::forbidden(format!(
+                "your account is suspended until {}",
+                until.and_utc().format("%Y-%m-%d %H:%M UTC")
+            ))),
+            _ => Ok(viewer),
+        }
+    }
 }
 
 impl juniper::Context for Context {}
@@ -138,21 +264,41 @@ impl Query {
         "0.1"
     }
 
-    async fn node<'c>(id: NodeId, ctx: &'c Context) -> FieldResult<Option<NodeValue>> {
-        let NodeId(table_name, db_id) = id;
-        let table = SchemaTable::from_str(&table_name)?;
+    /// The user the request is signed in as. Null if it isn't signed in.
+    async fn viewer(ctx: &Context) -> ApiResult<Option<User>> {
+        Ok(ctx.viewer().await?.cloned().map(User::from))
+    }
+
+    async fn node(id: NodeId, ctx: &Context) -> ApiResult<Option<NodeValue>> {
+        let NodeId(table, db_id) = id;
         let mut cnx = ctx.db.get().await?;
-        let model = table.find(db_id, &mut cnx).await;
-        Ok(model.ok().map(NodeValue::from))
+        let model = table.find(db_id, &mut cnx).await?;
+        let visible = match &model {
+            Some(SchemaModel::Flag(flag)) => Flag::is_visible_to(flag, ctx.viewer().await?),
+            Some(SchemaModel::Notification(notification)) => {
+                Notification::is_visible_to(notification, ctx.viewer().await?)
+            }
+            Some(SchemaModel::AuditLogEntry(_)) => {
+                AuditLogEntry::is_visible_to(ctx.viewer().await?)
+            }
+            Some(SchemaModel::UserSuspension(suspension)) => {
+                UserSuspension::is_visible_to(suspension.user_id, ctx.viewer().await?)
+            }
+            _ => true,
+        };
+        if !visible {
+            return Ok(None);
+        }
+        Ok(model.map(NodeValue::from))
     }
 
-    async fn newest_challenges<'c>(
-        ctx: &'c Context,
+    async fn newest_challenges(
+        ctx: &Context,
         first: Option<i32>,
         after: Option<NodeId>,
         last: Option<i32>,
         before: Option<NodeId>,
-    ) -> FieldResult<ChallengeConnection> {
+    ) -> ApiResult<ChallengeConnection> {
         let (after, before, limit) = relay_connection_closure_args(first, after, before);
         let mut cnx = ctx.db.get().await?;
         let nodes = models::Challenge::newest(&mut cnx, after, before, limit).await?;
@@ -164,18 +310,182 @@ impl Query {
         )?;
         Ok(ChallengeConnection::new(edges, page_info))
     }
+
+    /// Existing challenges resembling a draft with the given title and body, most similar
+    /// first. Meant to warn authors before they post a duplicate, so it needs no challenge id;
+    /// `Challenge.related` is the same search for a challenge that already exists.
+    async fn similar_challenges(
+        ctx: &Context,
+        title: String,
+        body: String,
+        first: Option<i32>,
+    ) -> ApiResult<Vec<SimilarChallenge>> {
+        SimilarChallenge::find(ctx, &title, &body, None, first).await
+    }
+
+    /// Flags for moderators to handle, oldest first. Only open flags are included unless
+    /// `resolved` is true. Moderators only.
+    #[allow(clippy::too_many_arguments)]
+    async fn flag_queue(
+        ctx: &Context,
+        reasons: Option<Vec<FlagReason>>,
+        target_type: Option<FlagTargetType>,
+        #[graphql(default = false)] resolved: bool,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<FlagConnection> {
+        if !ctx.require_viewer().await?.role.is_moderator() {
+            return Err(ApiError::forbidden(
+                "only moderators may see the flag queue",
+            ));
+        }
+        let filter = models::flag::FlagFilter {
+            reasons: reasons.map(|r| r.into_iter().map(Into::into).collect()),
+            target_kind: target_type.map(Into::into),
+            resolved: Some(resolved),
+        };
+        let (after, before, limit) = relay_connection_closure_args(first, after, before);
+        let mut cnx = ctx.db.get().await?;
+        let nodes = models::Flag::queue(&mut cnx, &filter, after, before, limit).await?;
+        let (page_info, edges) = build_connection(
+            first,
+            last,
+            nodes.into_iter().map(Flag::from).collect(),
+            FlagConnectionEdge::new,
+        )?;
+        Ok(FlagConnection::new(edges, page_info))
+    }
+
+    /// Moderator and admin actions, oldest first, optionally narrowed down to those taken by
+    /// `actor`, on `target` or of one of `actions`. Admins only.
+    #[allow(clippy::too_many_arguments)]
+    async fn audit_log(
+        ctx: &Context,
+        actor: Option<NodeId>,
+        target: Option<NodeId>,
+        actions: Option<Vec<AuditAction>>,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<AuditLogEntryConnection> {
+        if !AuditLogEntry::is_visible_to(Some(ctx.require_viewer().await?)) {
+            return Err(ApiError::forbidden("only admins may see the audit log"));
+        }
+        let actor_id = actor
+            .map(|a| a.expect_table("actor", &[SchemaTable::Users]))
+            .transpose()?;
+        let filter = models::audit_log::AuditLogFilter {
+            actor_id,
+            // posts are logged under `posts`, whichever kind they are
+            target: target.map(|NodeId(table, id)| match table {
+                SchemaTable::Challenges | SchemaTable::Responses => (SchemaTable::Posts, id),
+                table => (table, id),
+            }),
+            actions: actions.map(|a| a.into_iter().map(Into::into).collect()),
+        };
+        let (after, before, limit) = relay_connection_closure_args(first, after, before);
+        let mut cnx = ctx.db.get().await?;
+        let nodes = models::AuditLogEntry::filter(&mut cnx, &filter, after, before, limit).await?;
+        let (page_info, edges) = build_connection(
+            first,
+            last,
+            nodes.into_iter().map(AuditLogEntry::from).collect(),
+            AuditLogEntryConnectionEdge::new,
+        )?;
+        Ok(AuditLogEntryConnection::new(edges, page_info))
+    }
+
+    /// Unified diffs between two revisions of the same post.
+    async fn revision_diff(ctx: &Context, from: NodeId, to: NodeId) -> ApiResult<RevisionDiff> {
+        let from_id = from.expect_table("from", &[SchemaTable::PostRevisions])?;
+        let to_id = to.expect_table("to", &[SchemaTable::PostRevisions])?;
+        let mut cnx = ctx.db.get().await?;
+        let from = models::PostRevision::find(&mut cnx, from_id)
+            .await
+            .optional()?
+            .ok_or_else(|| ApiError::not_found(&from))?;
+        let to = models::PostRevision::find(&mut cnx, to_id)
+            .await
+            .optional()?
+            .ok_or_else(|| ApiError::not_found(&to))?;
+        RevisionDiff::between(&from, &to)
+    }
+
+    /// People a comment on the post can `@`-mention whose names start with `prefix`, ignoring
+    /// case, for autocompletion: the post's author, its editors and its commenters.
+    async fn mention_suggestions(
+        ctx: &Context,
+        post_id: NodeId,
+        prefix: String,
+        first: Option<i32>,
+    ) -> ApiResult<Vec<User>> {
+        let post_id = post_id.expect_table("postId", mutation::POST_TABLES)?;
+        comment::mention_suggestions(ctx, post_id, &prefix, first).await
+    }
+
+    /// Renders text as it would be rendered if saved as a post body or comment, without saving
+    /// anything.
+    async fn preview_markdown(
+        ctx: &Context,
+        text: String,
+        #[graphql(name = "context")] kind: MarkdownContext,
+    ) -> ApiResult<MarkdownPreview> {
+        MarkdownPreview::render(ctx, &text, kind).await
+    }
+
+    /// Searches challenges, responses and comments, best matches first. All words must match
+    /// unless separated by `or`; `"quoted words"` match as a phrase, `word*` matches as a prefix
+    /// and `-word` excludes results. `types` restricts the kinds of results, defaulting to all.
+    async fn search(
+        ctx: &Context,
+        query: String,
+        types: Option<Vec<SearchResultType>>,
+        first: Option<i32>,
+        after: Option<NodeId>,
+    ) -> ApiResult<SearchConnection> {
+        SearchConnection::search(ctx, &query, types, first, after).await
+    }
+
+    /// Finds responses whose code contains `pattern` on a single line, either as is or, if
+    /// `regex` is set, as a POSIX regular expression. `language` is matched case-insensitively.
+    #[allow(clippy::too_many_arguments)]
+    async fn search_code(
+        ctx: &Context,
+        pattern: String,
+        language: Option<String>,
+        #[graphql(default = false)] regex: bool,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<CodeSearchConnection> {
+        CodeSearchConnection::search(
+            ctx,
+            &pattern,
+            language.as_deref(),
+            regex,
+            first,
+            after,
+            last,
+            before,
+        )
+        .await
+    }
 }
 
-fn decode_id(id: &ID) -> FieldResult<(String, i32)> {
+fn decode_id(id: &ID) -> anyhow::Result<(SchemaTable, i32)> {
     let raw_id = String::from_utf8(BASE64_URL_SAFE.decode(id.as_bytes())?)?;
     let (table_name, db_id_str) = raw_id
         .split_once(":")
         .ok_or(IDFormatError(id.to_string()))?;
-    Ok((table_name.to_string(), db_id_str.parse()?))
+    Ok((SchemaTable::from_str(table_name)?, db_id_str.parse()?))
 }
 
-pub type Schema = RootNode<'static, Query, EmptyMutation<Context>, EmptySubscription<Context>>;
+pub type Schema = RootNode<'static, Query, Mutation, Subscription>;
 
 pub fn schema() -> Schema {
-    Schema::new(Query, EmptyMutation::new(), EmptySubscription::new())
+    Schema::new(Query, Mutation, Subscription)
 }
@@ -0,0 +1,1043 @@
+use actix_web::web;
+use diesel::{
+    OptionalExtension,
+    result::{DatabaseErrorKind, Error as DieselError},
+};
+use diesel_async::{AsyncConnection, AsyncPgConnection, scoped_futures::ScopedFutureExt};
+use juniper::graphql_object;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        Challenge, Context, NodeId, Response, User,
+        attachment::Attachment,
+        challenge::CloseReason,
+        comment::Comment,
+        errors::{ApiError, ApiResult},
+        flag::{Flag, FlagOutcome, FlagReason},
+        post::PostValue,
+        post_lock::LockType,
+        suggested_edit::SuggestedEdit,
+        upload::Upload,
+    },
+    models::{
+        self, AuditLogEntry, SchemaTable,
+        attachment::NewAttachment,
+        audit_log::{AuditAction, NewAuditLogEntry},
+        close_vote::{Ballot, CLOSE_VOTE_THRESHOLD, CloseVote},
+        flag::FlagTarget,
+        notification::Notification,
+        post::PostKind,
+        post_lock::{self, PostLock},
+        post_revision::{PostEdit, PostRevision},
+        response::NewResponse,
+        user::UserRole,
+    },
+    storage::{STORAGE, avatars, files},
+};
+
+/// Longest edit summary or review comment accepted, matching the columns they're stored in.
+const MAX_SUMMARY_LENGTH: usize = 256;
+
+/// Longest flag details accepted, matching `flags.details`.
+const MAX_FLAG_DETAILS_LENGTH: usize = 1000;
+
+/// Longest language name accepted, matching `responses.language`.
+const MAX_LANGUAGE_LENGTH: usize = 64;
+
+/// Longest comment accepted, matching `comments.body`.
+pub(super) const MAX_COMMENT_LENGTH: usize = 256;
+
+/// Longest message to a suspended user accepted.
+const MAX_SUSPENSION_MESSAGE_LENGTH: usize = 1000;
+
+const MAX_SUSPENSION_DAYS: i32 = 365;
+
+/// Longest uploaded file name accepted, matching `attachments.filename`.
+const MAX_FILENAME_LENGTH: usize = 256;
+
+pub(super) const POST_TABLES: &[SchemaTable] = &[
+    SchemaTable::Challenges,
+    SchemaTable::Responses,
+    SchemaTable::Posts,
+];
+
+pub struct Mutation;
+
+#[graphql_object]
+#[graphql(context = Context)]
+impl Mutation {
+    /// Changes a post's title, body or (for responses) code, recording the result as a new
+    /// revision. Only the post's author may edit it.
+    async fn edit_post(
+        ctx: &Context,
+        id: NodeId,
+        title: Option<String>,
+        body: Option<String>,
+        code: Option<String>,
+        summary: Option<String>,
+    ) -> ApiResult<PostValue> {
+        let post_id = id.expect_table("id", POST_TABLES)?;
+        if title.is_none() && body.is_none() && code.is_none() {
+            return Err(ApiError::validation(
+                "id",
+                "an edit must change the title, body or code",
+            ));
+        }
+        if title.as_ref().is_some_and(|t| t.trim().is_empty()) {
+            return Err(ApiError::validation("title", "title must not be blank"));
+        }
+        check_summary("summary", summary.as_deref())?;
+        let viewer = ctx.require_active_viewer().await?;
+        let mut cnx = ctx.db.get().await?;
+        cnx.transaction(|cnx| {
+            async move {
+                authorize_post_edit(cnx, viewer, post_id).await?;
+                if code.is_some() {
+                    let kind = PostKind::find_by_ids(cnx, &[post_id]).await?.pop();
+                    if !matches!(kind, Some((_, PostKind::Response))) {
+                        return Err(ApiError::validation("code", "only responses have code"));
+                    }
+                }
+
+                let edit = PostEdit { title, body, code };
+                PostRevision::record_edit(cnx, post_id, viewer.id, edit, summary.as_deref())
+                    .await?;
+                Ok::<_, ApiError>(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+        load_edited_post(&mut cnx, post_id).await
+    }
+
+    /// Restores a post to one of its revisions, recording the result as a new revision. Only
+    /// the post's author may roll it back.
+    async fn rollback_post(
+        ctx: &Context,
+        id: NodeId,
+        revision_id: NodeId,
+        summary: Option<String>,
+    ) -> ApiResult<PostValue> {
+        let post_id = id.expect_table("id", POST_TABLES)?;
+        let revision_id = revision_id.expect_table("revisionId", &[SchemaTable::PostRevisions])?;
+        check_summary("summary", summary.as_deref())?;
+        let viewer = ctx.require_active_viewer().await?;
+        let mut cnx = ctx.db.get().await?;
+        cnx.transaction(|cnx| {
+            async move {
+                authorize_post_edit(cnx, viewer, post_id).await?;
+                let revision = PostRevision::find(cnx, revision_id)
+                    .await
+                    .optional()?
+                    .filter(|r| r.post_id == post_id)
+                    .ok_or_else(|| {
+                        ApiError::validation("revisionId", "not a revision of this post")
+                    })?;
+
+                let summary = summary.unwrap_or_else(|| {
+                    format!(
+                        "Rolled back to the revision of {}",
+                        revision.created_at.and_utc().format("%Y-%m-%d %H:%M UTC")
+                    )
+                });
+                let edit = PostEdit {
+                    title: Some(revision.title),
+                    body: Some(revision.body),
+                    code: revision.code,
+                };
+                PostRevision::record_edit(cnx, post_id, viewer.id, edit, Some(&summary)).await?;
+                Ok::<_, ApiError>(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+        load_edited_post(&mut cnx, post_id).await
+    }
+
+    /// Proposes a change to someone else's post. It's applied once the post's author or a
+    /// moderator approves it with `reviewSuggestedEdit`.
+    async fn suggest_edit(
+        ctx: &Context,
+        post_id: NodeId,
+        title: Option<String>,
+        body: Option<String>,
+        comment: String,
+    ) -> ApiResult<SuggestedEdit> {
+        let post_id = post_id.expect_table("postId", POST_TABLES)?;
+        if title.is_none() && body.is_none() {
+            return Err(ApiError::validation(
+                "postId",
+                "a suggestion must change the title or body",
+            ));
+        }
+        if title.as_ref().is_some_and(|t| t.trim().is_empty()) {
+            return Err(ApiError::validation("title", "title must not be blank"));
+        }
+        if comment.trim().is_empty() {
+            return Err(ApiError::validation(
+                "comment",
+                "explain why the change is needed",
+            ));
+        }
+        check_summary("comment", Some(&comment))?;
+        let viewer = ctx.require_active_viewer().await?;
+        let mut cnx = ctx.db.get().await?;
+        let post = find_live_post(&mut cnx, post_id).await?;
+        if post.user_id == viewer.id {
+            return Err(ApiError::validation(
+                "postId",
+                "edit your own posts with editPost instead",
+            ));
+        }
+        check_lock(&mut cnx, viewer, post_id, post_lock::LockType::blocks_edits).await?;
+
+        let suggestion = models::SuggestedEdit::create(
+            &mut cnx,
+            post_id,
+            viewer.id,
+            title.as_deref(),
+            body.as_deref(),
+            &comment,
+        )
+        .await
+        .map_err(|e| match e {
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                ApiError::validation(
+                    "postId",
+                    "you already have a suggestion awaiting review for this post",
+                )
+            }
+            e => e.into(),
+        })?;
+        Ok(suggestion.into())
+    }
+
+    /// Approves or rejects a pending suggested edit. Approving applies it as a new revision
+    /// credited to the suggester, and is refused once the post has been edited since the
+    /// suggestion was made. Only the post's author or a moderator may review it.
+    async fn review_suggested_edit(
+        ctx: &Context,
+        id: NodeId,
+        approve: bool,
+        comment: Option<String>,
+    ) -> ApiResult<SuggestedEdit> {
+        let suggestion_id = id.expect_table("id", &[SchemaTable::SuggestedEdits])?;
+        check_summary("comment", comment.as_deref())?;
+        let viewer = ctx.require_active_viewer().await?;
+        let mut cnx = ctx.db.get().await?;
+        let suggestion = models::SuggestedEdit::find(&mut cnx, suggestion_id)
+            .await
+            .optional()?
+            .ok_or_else(|| ApiError::not_found(&id))?;
+        let post = find_live_post(&mut cnx, suggestion.post_id).await?;
+        if post.user_id != viewer.id && !viewer.role.is_moderator() {
+            return Err(ApiError::forbidden(
+                "only the author or a moderator may review suggested edits",
+            ));
+        }
+
+        // reviewing someone else's post is a moderator action
+        let audited = post.user_id != viewer.id;
+        let reviewed = cnx
+            .transaction(|cnx| {
+                async move {
+                    if approve {
+                        // keeps the post as checked until the suggestion is applied
+                        models::Post::find_for_update(cnx, post.id).await?;
+                        check_lock(cnx, viewer, post.id, post_lock::LockType::blocks_edits).await?;
+                        let latest = PostRevision::latest_for_post(cnx, post.id).await?;
+                        if latest.id != suggestion.base_revision_id {
+                            return Err(ApiError::validation(
+                                "id",
+                                "post has been edited since the suggestion was made",
+                            ));
+                        }
+                    }
+                    let reviewed = models::SuggestedEdit::review(
+                        cnx,
+                        suggestion_id,
+                        viewer.id,
+                        approve,
+                        comment.as_deref(),
+                    )
+                    .await?
+                    .ok_or_else(|| {
+                        ApiError::validation("id", "suggestion has already been reviewed")
+                    })?;
+                    if audited {
+                        let target = (SchemaTable::SuggestedEdits, suggestion_id);
+                        let action = AuditAction::ReviewSuggestedEdit;
+                        audit(cnx, ctx, action, target, Some(&suggestion), Some(&reviewed)).await?;
+                    }
+                    Ok::<_, ApiError>(reviewed)
+                }
+                .scope_boxed()
+            })
+            .await?;
+        Ok(reviewed.into())
+    }
+
+    /// Reports a post, comment or user to the moderators. `details` are required for
+    /// `NEEDS_MODERATOR` and `OTHER` flags.
+    async fn flag(
+        ctx: &Context,
+        target_id: NodeId,
+        reason: FlagReason,
+        details: Option<String>,
+    ) -> ApiResult<Flag> {
+        let details = details.filter(|d| !d.trim().is_empty());
+        if details.is_none() && matches!(reason, FlagReason::NeedsModerator | FlagReason::Other) {
+            return Err(ApiError::validation(
+                "details",
+                "describe the problem for the moderators",
+            ));
+        }
+        if details
+            .as_ref()
+            .is_some_and(|d| d.chars().count() > MAX_FLAG_DETAILS_LENGTH)
+        {
+            return Err(ApiError::validation(
+                "details",
+                format!("details must be at most {MAX_FLAG_DETAILS_LENGTH} characters"),
+            ));
+        }
+        let viewer = ctx.require_active_viewer().await?;
+        let mut cnx = ctx.db.get().await?;
+        let NodeId(table, db_id) = target_id;
+        let (target, owner_id) = match table {
+            SchemaTable::Challenges | SchemaTable::Responses | SchemaTable::Posts => {
+                let post = find_live_post(&mut cnx, db_id).await?;
+                (FlagTarget::Post(post.id), post.user_id)
+            }
+            SchemaTable::Comments => {
+                let comment = models::Comment::find(&mut cnx, db_id)
+                    .await
+                    .optional()?
+                    .ok_or_else(|| ApiError::not_found(&target_id))?;
+                (FlagTarget::Comment(comment.id), comment.user_id)
+            }
+            SchemaTable::Users => {
+                let user = ctx
+                    .loader
+                    .users
+                    .try_load(db_id)
+                    .await
+                    .map_err(|_| ApiError::not_found(&target_id))??;
+                if user.deleted_at.is_some() {
+                    return Err(ApiError::not_found(&target_id));
+                }
+                (FlagTarget::User(user.id), user.id)
+            }
+            _ => {
+                return Err(ApiError::validation(
+                    "targetId",
+                    format!("{table} can't be flagged"),
+                ));
+            }
+        };
+        if owner_id == viewer.id {
+            return Err(ApiError::validation(
+                "targetId",
+                "you can't flag yourself or your own content",
+            ));
+        }
+
+        let flag = models::Flag::create(
+            &mut cnx,
+            viewer.id,
+            target,
+            reason.into(),
+            details.as_deref(),
+        )
+        .await
+        .map_err(|e| match e {
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                ApiError::validation("targetId", "you have already flagged this")
+            }
+            e => e.into(),
+        })?;
+        Ok(flag.into())
+    }
+
+    /// Closes an open flag, recording the outcome and who handled it. Moderators only.
+    async fn resolve_flag(
+        ctx: &Context,
+        id: NodeId,
+        outcome: FlagOutcome,
+        note: Option<String>,
+    ) -> ApiResult<Flag> {
+        let flag_id = id.expect_table("id", &[SchemaTable::Flags])?;
+        check_summary("note", note.as_deref())?;
+        let viewer = ctx.require_active_viewer().await?;
+        if !viewer.role.is_moderator() {
+            return Err(ApiError::forbidden("only moderators may resolve flags"));
+        }
+        let mut cnx = ctx.db.get().await?;
+        let resolved = cnx
+            .transaction(|cnx| {
+                async move {
+                    let resolved = models::Flag::resolve(
+                        cnx,
+                        flag_id,
+                        viewer.id,
+                        outcome.into(),
+                        note.as_deref(),
+                    )
+                    .await?;
+                    if let Some(flag) = &resolved {
+                        let before = models::Flag {
+                            handler_id: None,
+                            outcome: None,
+                            resolution_note: None,
+                            resolved_at: None,
+                            ..flag.clone()
+                        };
+                        let target = (SchemaTable::Flags, flag_id);
+                        let action = AuditAction::ResolveFlag;
+                        audit(cnx, ctx, action, target, Some(&before), Some(flag)).await?;
+                    }
+                    Ok::<_, ApiError>(resolved)
+                }
+                .scope_boxed()
+            })
+            .await?;
+        if let Some(flag) = resolved {
+            return Ok(flag.into());
+        }
+        match models::Flag::find(&mut cnx, flag_id).await.optional()? {
+            Some(_) => Err(ApiError::validation("id", "flag has already been resolved")),
+            None => Err(ApiError::not_found(&id)),
+        }
+    }
+
+    /// Posts a response to an open challenge.
+    async fn create_response(
+        ctx: &Context,
+        challenge_id: NodeId,
+        title: String,
+        body: String,
+        code: String,
+        language: Option<String>,
+    ) -> ApiResult<Response> {
+        let challenge_id = challenge_id.expect_table("challengeId", &[SchemaTable::Challenges])?;
+        if title.trim().is_empty() {
+            return Err(ApiError::validation("title", "title must not be blank"));
+        }
+        if code.is_empty() {
+            return Err(ApiError::validation("code", "code must not be empty"));
+        }
+        let language = language.filter(|l| !l.trim().is_empty());
+        if language
+            .as_ref()
+            .is_some_and(|l| l.chars().count() > MAX_LANGUAGE_LENGTH)
+        {
+            return Err(ApiError::validation(
+                "language",
+                format!("language must be at most {MAX_LANGUAGE_LENGTH} characters"),
+            ));
+        }
+        let viewer = ctx.require_active_viewer().await?;
+        let mut cnx = ctx.db.get().await?;
+        let challenge = find_live_post(&mut cnx, challenge_id).await?;
+
+        let response = cnx
+            .transaction(|cnx| {
+                async move {
+                    // keeps the challenge from being locked until the response is created
+                    models::Post::find_for_update(cnx, challenge_id).await?;
+                    check_lock(
+                        cnx,
+                        viewer,
+                        challenge_id,
+                        post_lock::LockType::blocks_responses,
+                    )
+                    .await?;
+                    let response = models::Response::create(
+                        cnx,
+                        NewResponse {
+                            challenge_id,
+                            user_id: viewer.id,
+                            title: &title,
+                            body: &body,
+                            code: &code,
+                            language: language.as_deref(),
+                        },
+                    )
+                    .await?;
+                    Notification::notify_response(cnx, &response, challenge.user_id).await?;
+                    Ok::<_, ApiError>(response)
+                }
+                .scope_boxed()
+            })
+            .await?;
+        Ok(response.into())
+    }
+
+    /// Votes to close a challenge. It's closed once enough users vote, or immediately if a
+    /// moderator votes. `duplicateOf` is required when voting to close as a duplicate.
+    async fn vote_to_close(
+        ctx: &Context,
+        id: NodeId,
+        reason: CloseReason,
+        duplicate_of: Option<NodeId>,
+    ) -> ApiResult<Challenge> {
+        let challenge_id = id.expect_table("id", &[SchemaTable::Challenges])?;
+        let duplicate_of_id = duplicate_of
+            .map(|d| d.expect_table("duplicateOf", &[SchemaTable::Challenges]))
+            .transpose()?;
+        match (reason, duplicate_of_id) {
+            (CloseReason::Duplicate, None) => {
+                return Err(ApiError::validation(
+                    "duplicateOf",
+                    "name the challenge this one duplicates",
+                ));
+            }
+            (CloseReason::Duplicate, Some(_)) | (_, None) => {}
+            (_, Some(_)) => {
+                return Err(ApiError::validation(
+                    "duplicateOf",
+                    "only duplicate votes name another challenge",
+                ));
+            }
+        }
+        let ballot = Ballot::Close {
+            reason: reason.into(),
+            duplicate_of_id,
+        };
+        cast_close_vote(ctx, challenge_id, ballot).await
+    }
+
+    /// Votes to reopen a closed challenge. It's reopened once enough users vote, or
+    /// immediately if a moderator votes.
+    async fn vote_to_reopen(ctx: &Context, id: NodeId) -> ApiResult<Challenge> {
+        let challenge_id = id.expect_table("id", &[SchemaTable::Challenges])?;
+        cast_close_vote(ctx, challenge_id, Ballot::Reopen).await
+    }
+
+    /// Comments on a post, as a reply to `parentId` if given. The parent must be on the same
+    /// post, and replies can't nest deeper than the configured maximum.
+    async fn create_comment(
+        ctx: &Context,
+        post_id: NodeId,
+        body: String,
+        parent_id: Option<NodeId>,
+    ) -> ApiResult<Comment> {
+        let post_id = post_id.expect_table("postId", POST_TABLES)?;
+        let parent_id = parent_id
+            .map(|id| id.expect_table("parentId", &[SchemaTable::Comments]))
+            .transpose()?;
+        if body.trim().is_empty() {
+            return Err(ApiError::validation("body", "comment must not be blank"));
+        }
+        if body.chars().count() > MAX_COMMENT_LENGTH {
+            return Err(ApiError::validation(
+                "body",
+                format!("comment must be at most {MAX_COMMENT_LENGTH} characters"),
+            ));
+        }
+        let viewer = ctx.require_active_viewer().await?;
+        let mut cnx = ctx.db.get().await?;
+        let post = find_live_post(&mut cnx, post_id).await?;
+        let parent = match parent_id {
+            Some(parent_id) => {
+                let parent = models::Comment::find(&mut cnx, parent_id)
+                    .await
+                    .optional()?
+                    .ok_or_else(|| {
+                        ApiError::not_found(NodeId::from((SchemaTable::Comments, parent_id)))
+                    })?;
+                if parent.post_id != post_id {
+                    return Err(ApiError::validation(
+                        "parentId",
+                        "parent comment is on a different post",
+                    ));
+                }
+                if !parent.accepts_replies() {
+                    return Err(ApiError::validation(
+                        "parentId",
+                        "replies can't be nested any deeper",
+                    ));
+                }
+                Some(parent)
+            }
+            None => None,
+        };
+
+        let comment = cnx
+            .transaction(|cnx| {
+                async move {
+                    // keeps the post from being locked until the comment is created
+                    models::Post::find_for_update(cnx, post_id).await?;
+                    check_lock(cnx, viewer, post_id, post_lock::LockType::blocks_comments).await?;
+                    let comment =
+                        models::Comment::create(cnx, post_id, viewer.id, parent.as_ref(), &body)
+                            .await?;
+                    Notification::notify_comment(cnx, &comment, post.user_id).await?;
+                    Ok::<_, ApiError>(comment)
+                }
+                .scope_boxed()
+            })
+            .await?;
+        Ok(comment.into())
+    }
+
+    /// Upvotes a comment. Upvoting a comment twice has no further effect, and users can't upvote
+    /// their own comments.
+    async fn upvote_comment(ctx: &Context, id: NodeId) -> ApiResult<Comment> {
+        let comment_id = id.expect_table("id", &[SchemaTable::Comments])?;
+        let viewer = ctx.require_active_viewer().await?;
+        let mut cnx = ctx.db.get().await?;
+        let comment = find_comment_on_live_post(&mut cnx, comment_id).await?;
+        if comment.user_id == viewer.id {
+            return Err(ApiError::forbidden("you can't upvote your own comment"));
+        }
+        models::Comment::upvote(&mut cnx, comment_id, viewer.id).await?;
+        Ok(models::Comment::find(&mut cnx, comment_id).await?.into())
+    }
+
+    /// Takes back the viewer's upvote on a comment, if they gave one.
+    async fn remove_comment_upvote(ctx: &Context, id: NodeId) -> ApiResult<Comment> {
+        let comment_id = id.expect_table("id", &[SchemaTable::Comments])?;
+        let viewer = ctx.require_active_viewer().await?;
+        let mut cnx = ctx.db.get().await?;
+        find_comment_on_live_post(&mut cnx, comment_id).await?;
+        models::Comment::remove_upvote(&mut cnx, comment_id, viewer.id).await?;
+        Ok(models::Comment::find(&mut cnx, comment_id).await?.into())
+    }
+
+    /// Marks the viewer's notifications as read: those in `ids`, or all of them if `ids` is
+    /// omitted. Returns the viewer.
+    async fn mark_notifications_read(ctx: &Context, ids: Option<Vec<NodeId>>) -> ApiResult<User> {
+        let ids = ids
+            .map(|ids| {
+                ids.iter()
+                    .map(|id| id.expect_table("ids", &[SchemaTable::Notifications]))
+                    .collect::<ApiResult<Vec<_>>>()
+            })
+            .transpose()?;
+        let viewer = ctx.require_active_viewer().await?;
+        let mut cnx = ctx.db.get().await?;
+        Notification::mark_read(&mut cnx, viewer.id, ids.as_deref()).await?;
+        Ok(viewer.clone().into())
+    }
+
+    /// Locks a post against edits, comments or responses, depending on `type`. Without
+    /// `expiresAt` the lock lasts until it's lifted with `unlockPost`. Moderators only.
+    async fn lock_post(
+        ctx: &Context,
+        id: NodeId,
+        #[graphql(name = "type")] lock_type: LockType,
+        reason: Option<String>,
+        expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> ApiResult<PostValue> {
+        let post_id = id.expect_table("id", POST_TABLES)?;
+        let reason = reason.filter(|r| !r.trim().is_empty());
+        check_summary("reason", reason.as_deref())?;
+        if expires_at.is_some_and(|t| t <= chrono::Utc::now()) {
+            return Err(ApiError::validation(
+                "expiresAt",
+                "expiry must be in the future",
+            ));
+        }
+        let viewer = ctx.require_active_viewer().await?;
+        if !viewer.role.is_moderator() {
+            return Err(ApiError::forbidden("only moderators may lock posts"));
+        }
+        let mut cnx = ctx.db.get().await?;
+        find_live_post(&mut cnx, post_id).await?;
+
+        cnx.transaction(|cnx| {
+            async move {
+                // waits for edits that already passed `authorize_post_edit` to be written
+                models::Post::find_for_update(cnx, post_id).await?;
+                let lock = PostLock::create(
+                    cnx,
+                    post_id,
+                    viewer.id,
+                    lock_type.into(),
+                    reason.as_deref(),
+                    expires_at.map(|t| t.naive_utc()),
+                )
+                .await
+                .map_err(|e| match e {
+                    DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                        ApiError::validation("id", "post is already locked")
+                    }
+                    e => e.into(),
+                })?;
+                let target = (SchemaTable::Posts, post_id);
+                audit(cnx, ctx, AuditAction::LockPost, target, None, Some(&lock)).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+        load_edited_post(&mut cnx, post_id).await
+    }
+
+    /// Lifts a post's lock before it expires. Moderators only.
+    async fn unlock_post(ctx: &Context, id: NodeId) -> ApiResult<PostValue> {
+        let post_id = id.expect_table("id", POST_TABLES)?;
+        let viewer = ctx.require_active_viewer().await?;
+        if !viewer.role.is_moderator() {
+            return Err(ApiError::forbidden("only moderators may unlock posts"));
+        }
+        let mut cnx = ctx.db.get().await?;
+        find_live_post(&mut cnx, post_id).await?;
+
+        cnx.transaction(|cnx| {
+            async move {
+                let lifted = PostLock::lift(cnx, post_id, viewer.id)
+                    .await?
+                    .ok_or_else(|| ApiError::validation("id", "post isn't locked"))?;
+                let before = PostLock {
+                    lifted_at: None,
+                    lifted_by_id: None,
+                    ..lifted.clone()
+                };
+                let target = (SchemaTable::Posts, post_id);
+                let action = AuditAction::UnlockPost;
+                audit(cnx, ctx, action, target, Some(&before), Some(&lifted)).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+        load_edited_post(&mut cnx, post_id).await
+    }
+
+    /// Suspends a user for `days` days, during which they may only read. `reason` is kept for
+    /// the record; `message` is shown to the user. Moderators only, and only admins may suspend
+    /// other moderators.
+    async fn suspend_user(
+        ctx: &Context,
+        id: NodeId,
+        days: i32,
+        reason: String,
+        message: Option<String>,
+    ) -> ApiResult<User> {
+        let user_id = id.expect_table("id", &[SchemaTable::Users])?;
+        if !(1..=MAX_SUSPENSION_DAYS).contains(&days) {
+            return Err(ApiError::validation(
+                "days",
+                format!("suspensions last between 1 and {MAX_SUSPENSION_DAYS} days"),
+            ));
+        }
+        if reason.trim().is_empty() {
+            return Err(ApiError::validation(
+                "reason",
+                "give a reason for the suspension",
+            ));
+        }
+        check_summary("reason", Some(&reason))?;
+        let message = message.filter(|m| !m.trim().is_empty());
+        if message
+            .as_ref()
+            .is_some_and(|m| m.chars().count() > MAX_SUSPENSION_MESSAGE_LENGTH)
+        {
+            return Err(ApiError::validation(
+                "message",
+                format!("message must be at most {MAX_SUSPENSION_MESSAGE_LENGTH} characters"),
+            ));
+        }
+        let viewer = ctx.require_active_viewer().await?;
+        if !viewer.role.is_moderator() {
+            return Err(ApiError::forbidden("only moderators may suspend users"));
+        }
+        if user_id == viewer.id {
+            return Err(ApiError::validation("id", "you can't suspend yourself"));
+        }
+        let mut cnx = ctx.db.get().await?;
+        let user = find_live_user(&mut cnx, user_id).await?;
+        if user.role.is_moderator() && viewer.role != UserRole::Admin {
+            return Err(ApiError::forbidden("only admins may suspend moderators"));
+        }
+
+        let ends_at = chrono::Utc::now().naive_utc() + chrono::Duration::days(days.into());
+        cnx.transaction(|cnx| {
+            async move {
+                let suspension = models::UserSuspension::create(
+                    cnx,
+                    user_id,
+                    viewer.id,
+                    &reason,
+                    message.as_deref(),
+                    ends_at,
+                )
+                .await?
+                .ok_or_else(|| ApiError::validation("id", "user is already suspended"))?;
+                let target = (SchemaTable::Users, user_id);
+                let action = AuditAction::SuspendUser;
+                audit(cnx, ctx, action, target, None, Some(&suspension)).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+        Ok(models::User::find(&mut cnx, user_id).await?.into())
+    }
+
+    /// Ends a user's suspension early. Moderators only.
+    async fn unsuspend_user(ctx: &Context, id: NodeId, note: Option<String>) -> ApiResult<User> {
+        let user_id = id.expect_table("id", &[SchemaTable::Users])?;
+        check_summary("note", note.as_deref())?;
+        let viewer = ctx.require_active_viewer().await?;
+        if !viewer.role.is_moderator() {
+            return Err(ApiError::forbidden("only moderators may unsuspend users"));
+        }
+        let mut cnx = ctx.db.get().await?;
+        find_live_user(&mut cnx, user_id).await?;
+
+        cnx.transaction(|cnx| {
+            async move {
+                let lifted = models::UserSuspension::lift(cnx, user_id, viewer.id, note.as_deref())
+                    .await?
+                    .ok_or_else(|| ApiError::validation("id", "user isn't suspended"))?;
+                let before = models::UserSuspension {
+                    lifted_at: None,
+                    lifted_by_id: None,
+                    lift_note: None,
+                    ..lifted.clone()
+                };
+                let target = (SchemaTable::Users, user_id);
+                let action = AuditAction::UnsuspendUser;
+                audit(cnx, ctx, action, target, Some(&before), Some(&lifted)).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+        Ok(models::User::find(&mut cnx, user_id).await?.into())
+    }
+
+    /// Replaces the viewer's avatar with an uploaded image, sent as a multipart request. The
+    /// image is cropped to a square around its center.
+    async fn set_avatar(ctx: &Context, file: Upload) -> ApiResult<User> {
+        let viewer = ctx.require_active_viewer().await?;
+        let upload = ctx.take_upload("file", &file)?;
+        let images = web::block(move || avatars::resize(&upload.data))
+            .await?
+            .map_err(|e| ApiError::validation("file", e.to_string()))?;
+        let avatar_key = avatars::store(&images).await?;
+        let mut cnx = ctx.db.get().await?;
+        let user = match models::User::set_avatar(&mut cnx, viewer.id, Some(&avatar_key)).await {
+            Ok(user) => user,
+            Err(e) => {
+                avatars::delete(&avatar_key).await;
+                return Err(e.into());
+            }
+        };
+        if let Some(old) = &viewer.avatar_key {
+            avatars::delete(old).await;
+        }
+        Ok(user.into())
+    }
+
+    /// Removes the viewer's avatar, so they're shown with an identicon again.
+    async fn remove_avatar(ctx: &Context) -> ApiResult<User> {
+        let viewer = ctx.require_active_viewer().await?;
+        let mut cnx = ctx.db.get().await?;
+        let user = models::User::set_avatar(&mut cnx, viewer.id, None).await?;
+        if let Some(old) = &viewer.avatar_key {
+            avatars::delete(old).await;
+        }
+        Ok(user.into())
+    }
+
+    /// Uploads a file to use in a post, sent as a multipart request. The file isn't attached to
+    /// any post until one links to its URL.
+    async fn upload_attachment(ctx: &Context, file: Upload) -> ApiResult<Attachment> {
+        let viewer = ctx.require_active_viewer().await?;
+        let upload = ctx.take_upload("file", &file)?;
+        let filename = upload.filename.unwrap_or_default();
+        if filename.chars().count() > MAX_FILENAME_LENGTH {
+            return Err(ApiError::validation(
+                "file",
+                format!("file name must be at most {MAX_FILENAME_LENGTH} characters"),
+            ));
+        }
+        // decoding and re-encoding images is slow, so it's kept off the async workers
+        let clean = web::block(move || files::clean(&upload.data))
+            .await?
+            .map_err(|e| ApiError::validation("file", e.to_string()))?;
+        let key = files::new_key(clean.extension);
+        STORAGE.put(&key, &clean.data).await?;
+        let new = NewAttachment {
+            user_id: viewer.id,
+            storage_key: &key,
+            filename: &filename,
+            content_type: clean.content_type,
+            size: clean.data.len().try_into()?,
+            width: clean.dimensions.map(|(w, _)| w.try_into()).transpose()?,
+            height: clean.dimensions.map(|(_, h)| h.try_into()).transpose()?,
+        };
+        let mut cnx = ctx.db.get().await?;
+        match models::Attachment::create(&mut cnx, new).await {
+            Ok(attachment) => Ok(attachment.into()),
+            Err(e) => {
+                // don't leave a file behind that nothing refers to
+                let _ = STORAGE.delete(&key).await;
+                Err(e.into())
+            }
+        }
+    }
+}
+
+fn check_summary(field: &str, summary: Option<&str>) -> ApiResult<()> {
+    if summary.is_some_and(|s| s.chars().count() > MAX_SUMMARY_LENGTH) {
+        return Err(ApiError::validation(
+            field,
+            format!("{field} must be at most {MAX_SUMMARY_LENGTH} characters"),
+        ));
+    }
+    Ok(())
+}
+
+/// Loads a post that hasn't been deleted.
+async fn find_live_post(cnx: &mut AsyncPgConnection, post_id: i32) -> ApiResult<models::Post> {
+    models::Post::find(cnx, post_id)
+        .await
+        .optional()?
+        .filter(|p| p.deleted_at.is_none())
+        .ok_or_else(|| ApiError::not_found(NodeId::from((SchemaTable::Posts, post_id))))
+}
+
+/// Loads a comment whose post hasn't been deleted.
+async fn find_comment_on_live_post(
+    cnx: &mut AsyncPgConnection,
+    comment_id: i32,
+) -> ApiResult<models::Comment> {
+    let not_found = || ApiError::not_found(NodeId::from((SchemaTable::Comments, comment_id)));
+    let comment = models::Comment::find(cnx, comment_id)
+        .await
+        .optional()?
+        .ok_or_else(not_found)?;
+    if models::Post::find(cnx, comment.post_id)
+        .await?
+        .deleted_at
+        .is_some()
+    {
+        return Err(not_found());
+    }
+    Ok(comment)
+}
+
+/// Loads a user that hasn't been deleted.
+async fn find_live_user(cnx: &mut AsyncPgConnection, user_id: i32) -> ApiResult<models::User> {
+    models::User::find(cnx, user_id)
+        .await
+        .optional()?
+        .filter(|u| u.deleted_at.is_none())
+        .ok_or_else(|| ApiError::not_found(NodeId::from((SchemaTable::Users, user_id))))
+}
+
+/// Checks that `viewer` may edit the post, which must exist and not be deleted. Call it inside
+/// the edit's transaction: the post row stays locked until the edit is written, so a lock
+/// placed in the meantime can't be skipped.
+async fn authorize_post_edit(
+    cnx: &mut AsyncPgConnection,
+    viewer: &models::User,
+    post_id: i32,
+) -> ApiResult<models::Post> {
+    let post = models::Post::find_for_update(cnx, post_id)
+        .await
+        .optional()?
+        .filter(|p| p.deleted_at.is_none())
+        .ok_or_else(|| ApiError::not_found(NodeId::from((SchemaTable::Posts, post_id))))?;
+    if post.user_id != viewer.id {
+        return Err(ApiError::forbidden("only the author may edit this post"));
+    }
+    check_lock(cnx, viewer, post_id, post_lock::LockType::blocks_edits).await?;
+    Ok(post)
+}
+
+/// Fails if the post has a lock that `blocks` the action. Moderators aren't held back by locks.
+async fn check_lock(
+    cnx: &mut AsyncPgConnection,
+    viewer: &models::User,
+    post_id: i32,
+    blocks: fn(post_lock::LockType) -> bool,
+) -> ApiResult<()> {
+    if viewer.role.is_moderator() {
+        return Ok(());
+    }
+    match PostLock::find_active(cnx, post_id).await? {
+        Some(lock) if blocks(lock.lock_type) => Err(ApiError::forbidden("post is locked")),
+        _ => Ok(()),
+    }
+}
+
+/// Records a moderator action taken by the viewer in the audit log. `before` and `after` are
+/// what the action changed, if it existed before or after. Call it inside the action's
+/// transaction.
+async fn audit<T: Serialize>(
+    cnx: &mut AsyncPgConnection,
+    ctx: &Context,
+    action: AuditAction,
+    (table, target_id): (SchemaTable, i32),
+    before: Option<&T>,
+    after: Option<&T>,
+) -> ApiResult<()> {
+    let snapshot = |value: Option<&T>| {
+        value
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(anyhow::Error::from)
+    };
+    let entry = NewAuditLogEntry {
+        actor_id: ctx.require_viewer().await?.id,
+        action,
+        target_table: table.name(),
+        target_id,
+        before_state: snapshot(before)?,
+        after_state: snapshot(after)?,
+        ip: ctx.ip(),
+    };
+    AuditLogEntry::record(cnx, entry).await?;
+    Ok(())
+}
+
+async fn load_edited_post(cnx: &mut AsyncPgConnection, post_id: i32) -> ApiResult<PostValue> {
+    PostValue::load_fresh(cnx, post_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(NodeId::from((SchemaTable::Posts, post_id))))
+}
+
+/// Casts the viewer's vote on a challenge. Moderators' votes take effect immediately.
+async fn cast_close_vote(ctx: &Context, challenge_id: i32, ballot: Ballot) -> ApiResult<Challenge> {
+    let viewer = ctx.require_active_viewer().await?;
+    let mut cnx = ctx.db.get().await?;
+    find_live_post(&mut cnx, challenge_id).await?;
+    let binding = viewer.role.is_moderator();
+    let action = match ballot {
+        Ballot::Close { .. } => AuditAction::CloseChallenge,
+        Ballot::Reopen => AuditAction::ReopenChallenge,
+    };
+    cnx.transaction(|cnx| {
+        async move {
+            // only binding votes are moderator actions
+            let before = match binding {
+                true => Some(models::Challenge::find(cnx, challenge_id).await?.data),
+                false => None,
+            };
+            let after = CloseVote::cast(
+                cnx,
+                challenge_id,
+                viewer.id,
+                ballot,
+                binding,
+                *CLOSE_VOTE_THRESHOLD,
+            )
+            .await?;
+            match before {
+                Some(before) => {
+                    let target = (SchemaTable::Posts, challenge_id);
+                    audit(cnx, ctx, action, target, Some(&before), Some(&after)).await
+                }
+                None => Ok(()),
+            }
+        }
+        .scope_boxed()
+    })
+    .await?;
+    Ok(models::Challenge::find(&mut cnx, challenge_id)
+        .await?
+        .into())
+}
@@ -0,0 +1,104 @@
+use juniper::{GraphQLEnum, graphql_object};
+
+use crate::{
+    api::{
+        Comment, Context, NodeId, NodeValue, Response, User, errors::ApiResult, post::PostValue,
+    },
+    models::{self, SchemaTable, notification},
+    relayify,
+};
+
+#[derive(GraphQLEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NotificationKind {
+    /// Someone responded to the viewer's challenge.
+    Response,
+    /// Someone commented on the viewer's post.
+    Comment,
+    /// Someone `@`-mentioned the viewer.
+    Mention,
+}
+
+impl From<notification::NotificationKind> for NotificationKind {
+    fn from(value: notification::NotificationKind) -> Self {
+        match value {
+            notification::NotificationKind::Response => Self::Response,
+            notification::NotificationKind::Comment => Self::Comment,
+            notification::NotificationKind::Mention => Self::Mention,
+        }
+    }
+}
+
+/// Activity on a post involving the viewer. Events of the same kind on the same post are
+/// batched into one notification until it's read. Only visible to its recipient.
+pub struct Notification {
+    model: models::Notification,
+}
+
+#[graphql_object]
+#[graphql(impl = [NodeValue], context = Context)]
+impl Notification {
+    pub fn id(&self) -> NodeId {
+        NodeId::from((SchemaTable::Notifications, self.model.id))
+    }
+
+    pub fn kind(&self) -> NotificationKind {
+        self.model.kind.into()
+    }
+
+    /// The post the activity happened on.
+    pub async fn post(&self, ctx: &Context) -> ApiResult<PostValue> {
+        PostValue::try_from_db_id(self.model.post_id, ctx).await
+    }
+
+    /// Who caused the latest event.
+    pub async fn actor(&self, ctx: &Context) -> ApiResult<User> {
+        Ok(ctx
+            .loader
+            .users
+            .try_load(self.model.actor_id)
+            .await??
+            .into())
+    }
+
+    /// The latest response, for `RESPONSE` notifications.
+    pub fn response(&self) -> Option<Response> {
+        self.model.response_id.map(Response::new)
+    }
+
+    /// The latest comment, for `COMMENT` notifications and mentions in comments.
+    pub fn comment(&self) -> Option<Comment> {
+        self.model.comment_id.map(Comment::new)
+    }
+
+    /// How many events are batched into the notification.
+    pub fn count(&self) -> i32 {
+        self.model.event_count
+    }
+
+    pub fn created_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.model.created_at.and_utc()
+    }
+
+    /// When the latest event happened.
+    pub fn updated_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.model.updated_at.and_utc()
+    }
+
+    pub fn read_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.model.read_at.map(|t| t.and_utc())
+    }
+}
+
+impl Notification {
+    pub fn is_visible_to(model: &models::Notification, viewer: Option<&models::User>) -> bool {
+        viewer.is_some_and(|v| v.id == model.user_id)
+    }
+}
+
+relayify!(Notification);
+
+impl From<models::Notification> for Notification {
+    fn from(model: models::Notification) -> Self {
+        Self { model }
+    }
+}
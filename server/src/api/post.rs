@@ -1,8 +1,19 @@
-use juniper::{FieldResult, graphql_interface};
+use diesel_async::AsyncPgConnection;
+use juniper::graphql_interface;
 
 use crate::{
-    api::{Challenge, Context, NodeId, NodeValue, Response, User},
-    models::{self, post::FromPost},
+    api::{
+        Challenge, Context, NodeId, NodeValue, Response, User,
+        attachment::Attachment,
+        errors::{ApiError, ApiResult},
+        post_lock::PostLock,
+        post_revision::PostRevisionConnection,
+        suggested_edit::SuggestedEditConnection,
+    },
+    models::{
+        SchemaModel, SchemaTable,
+        post::{FromPost, PostKind},
+    },
 };
 
 #[allow(async_fn_in_trait)] // juniper relies on `async` being present in the fn def for proper codegen
@@ -12,7 +23,7 @@ pub trait Post: Sized {
     type ModelType: FromPost;
 
     #[graphql(skip)]
-    async fn fetch_from_db<'c, 'a>(&'a self, ctx: &'c Context) -> FieldResult<&'a Self::ModelType>
+    async fn fetch_from_db<'c, 'a>(&'a self, ctx: &'c Context) -> ApiResult<&'a Self::ModelType>
     where
         Self::ModelType: 'a;
 
@@ -22,36 +33,75 @@ pub trait Post: Sized {
     #[graphql(skip)]
     fn from_model(model: Self::ModelType) -> Self;
 
-    async fn id<'c>(&self, ctx: &'c Context) -> FieldResult<&NodeId>;
+    async fn id(&self, ctx: &Context) -> ApiResult<&NodeId>;
 
-    async fn title<'c>(&self, ctx: &'c Context) -> FieldResult<&String>;
+    async fn title(&self, ctx: &Context) -> ApiResult<&String>;
 
-    async fn body<'c>(&self, ctx: &'c Context) -> FieldResult<&String>;
+    async fn body(&self, ctx: &Context) -> ApiResult<&String>;
 
-    async fn created_at<'c>(&self, ctx: &'c Context) -> FieldResult<chrono::DateTime<chrono::Utc>>;
+    /// The body rendered as sanitized HTML. Markdown follows CommonMark, plus GitHub's tables,
+    /// strikethrough and task lists.
+    async fn body_html(&self, ctx: &Context) -> ApiResult<String>;
 
-    async fn updated_at<'c>(
-        &self,
-        ctx: &'c Context,
-    ) -> FieldResult<Option<chrono::DateTime<chrono::Utc>>>;
+    async fn created_at(&self, ctx: &Context) -> ApiResult<chrono::DateTime<chrono::Utc>>;
+
+    async fn updated_at(&self, ctx: &Context) -> ApiResult<Option<chrono::DateTime<chrono::Utc>>>;
+
+    async fn deleted_at(&self, ctx: &Context) -> ApiResult<Option<chrono::DateTime<chrono::Utc>>>;
+
+    async fn author(&self, ctx: &Context) -> ApiResult<User>;
+
+    /// The moderator lock currently restricting the post, if any.
+    async fn lock(&self, ctx: &Context) -> ApiResult<Option<PostLock>>;
 
-    async fn deleted_at<'c>(
+    /// The users the post's body `@`-mentions.
+    async fn mentions(&self, ctx: &Context) -> ApiResult<Vec<User>>;
+
+    /// The uploaded files the post's body links to, oldest first.
+    async fn attachments(&self, ctx: &Context) -> ApiResult<Vec<Attachment>>;
+
+    /// Every version of the post, oldest first.
+    async fn revisions(
         &self,
-        ctx: &'c Context,
-    ) -> FieldResult<Option<chrono::DateTime<chrono::Utc>>>;
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<PostRevisionConnection>;
 
-    async fn author<'c>(&self, ctx: &'c Context) -> FieldResult<User>;
+    /// Edits other users have proposed for the post, oldest first.
+    async fn suggested_edits(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<SuggestedEditConnection>;
 }
 
 impl PostValue {
-    pub async fn try_from_db_id<'c>(db_id: i32, ctx: &'c Context) -> FieldResult<Self> {
-        let mut cnx = ctx.db.get().await?;
-        let post = ctx.loader.posts.try_load(db_id).await??;
-        if let Ok(m) = models::Challenge::from_post(&mut cnx, &post).await {
-            return Ok(Self::from(Challenge::from_model(m)));
-        } else if let Ok(m) = models::Response::from_post(&mut cnx, &post).await {
-            return Ok(Self::from(Response::from_model(m)));
-        }
-        Err(format!("unknown post: {db_id}").into())
+    pub async fn try_from_db_id(db_id: i32, ctx: &Context) -> ApiResult<Self> {
+        let kind = ctx
+            .loader
+            .post_kinds
+            .try_load(db_id)
+            .await
+            .map_err(|_| ApiError::not_found(NodeId::from((SchemaTable::Posts, db_id))))??;
+        Ok(match kind {
+            PostKind::Challenge => Self::from(Challenge::new(db_id)),
+            PostKind::Response => Self::from(Response::new(db_id)),
+        })
+    }
+
+    /// Loads the post straight from the database, bypassing the loader's cache, e.g. to return
+    /// it after an edit.
+    pub async fn load_fresh(cnx: &mut AsyncPgConnection, db_id: i32) -> ApiResult<Option<Self>> {
+        Ok(match SchemaTable::Posts.find(db_id, cnx).await? {
+            Some(SchemaModel::Challenge(m)) => Some(Challenge::from(m).into()),
+            Some(SchemaModel::Response(m)) => Some(Response::from(m).into()),
+            _ => None,
+        })
     }
 }
@@ -0,0 +1,79 @@
+use juniper::{GraphQLEnum, graphql_object};
+
+use crate::{
+    api::{Context, User, errors::ApiResult},
+    models::{self, post_lock},
+};
+
+#[derive(GraphQLEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LockType {
+    /// Stops an edit war: the post can't be edited.
+    ContentDispute,
+    /// Preserves a post that no longer fits the site but is kept for its history: it can't be
+    /// edited, commented on or responded to.
+    HistoricalSignificance,
+    /// Stops a heated discussion: no new comments.
+    CommentsOnly,
+}
+
+impl From<LockType> for post_lock::LockType {
+    fn from(value: LockType) -> Self {
+        match value {
+            LockType::ContentDispute => Self::ContentDispute,
+            LockType::HistoricalSignificance => Self::HistoricalSignificance,
+            LockType::CommentsOnly => Self::CommentsOnly,
+        }
+    }
+}
+
+impl From<post_lock::LockType> for LockType {
+    fn from(value: post_lock::LockType) -> Self {
+        match value {
+            post_lock::LockType::ContentDispute => Self::ContentDispute,
+            post_lock::LockType::HistoricalSignificance => Self::HistoricalSignificance,
+            post_lock::LockType::CommentsOnly => Self::CommentsOnly,
+        }
+    }
+}
+
+/// A moderator's lock on a post, restricting what can be done to it.
+pub struct PostLock {
+    model: models::PostLock,
+}
+
+#[graphql_object]
+#[graphql(context = Context)]
+impl PostLock {
+    #[graphql(name = "type")]
+    pub fn lock_type(&self) -> LockType {
+        self.model.lock_type.into()
+    }
+
+    pub fn reason(&self) -> Option<&String> {
+        self.model.reason.as_ref()
+    }
+
+    pub async fn locked_by(&self, ctx: &Context) -> ApiResult<User> {
+        Ok(ctx
+            .loader
+            .users
+            .try_load(self.model.locked_by_id)
+            .await??
+            .into())
+    }
+
+    pub fn created_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.model.created_at.and_utc()
+    }
+
+    /// When the lock is lifted automatically. Null if it lasts until a moderator lifts it.
+    pub fn expires_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.model.expires_at.map(|t| t.and_utc())
+    }
+}
+
+impl From<models::PostLock> for PostLock {
+    fn from(model: models::PostLock) -> Self {
+        Self { model }
+    }
+}
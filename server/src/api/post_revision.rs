@@ -0,0 +1,130 @@
+use juniper::{GraphQLObject, graphql_object};
+use similar::TextDiff;
+
+use crate::{
+    api::{
+        Context, NodeId, NodeValue, User,
+        errors::{ApiError, ApiResult},
+        post::PostValue,
+    },
+    models::{self, SchemaTable},
+    relayify,
+};
+
+/// Lines of context around each change in revision diffs.
+const DIFF_CONTEXT_LINES: usize = 3;
+
+/// A snapshot of a post after one of its edits.
+pub struct PostRevision {
+    model: models::PostRevision,
+}
+
+#[graphql_object]
+#[graphql(impl = [NodeValue], context = Context)]
+impl PostRevision {
+    pub fn id(&self) -> NodeId {
+        NodeId::from((SchemaTable::PostRevisions, self.model.id))
+    }
+
+    pub async fn post(&self, ctx: &Context) -> ApiResult<PostValue> {
+        PostValue::try_from_db_id(self.model.post_id, ctx).await
+    }
+
+    pub async fn editor(&self, ctx: &Context) -> ApiResult<User> {
+        Ok(ctx
+            .loader
+            .users
+            .try_load(self.model.editor_id)
+            .await??
+            .into())
+    }
+
+    pub fn title(&self) -> &String {
+        &self.model.title
+    }
+
+    pub fn body(&self) -> &String {
+        &self.model.body
+    }
+
+    /// The body rendered as sanitized HTML, as `Post.bodyHtml` shows it.
+    pub fn body_html(&self) -> String {
+        self.model.render_body()
+    }
+
+    /// The response's code at this revision. Null for challenges.
+    pub fn code(&self) -> Option<&String> {
+        self.model.code.as_ref()
+    }
+
+    pub fn summary(&self) -> Option<&String> {
+        self.model.summary.as_ref()
+    }
+
+    pub fn created_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.model.created_at.and_utc()
+    }
+}
+
+relayify!(
+    PostRevision,
+    (Post, async |cnx, post_id, after, before, limit| {
+        models::PostRevision::filter_by_post(cnx, post_id, after, before, limit).await
+    })
+);
+
+impl From<models::PostRevision> for PostRevision {
+    fn from(model: models::PostRevision) -> Self {
+        Self { model }
+    }
+}
+
+/// Unified diffs between two revisions of a post. Parts that didn't change have empty diffs.
+#[derive(GraphQLObject, Debug, PartialEq, Eq)]
+pub struct RevisionDiff {
+    title: String,
+    body: String,
+    /// Null for challenges.
+    code: Option<String>,
+}
+
+impl RevisionDiff {
+    pub fn between(from: &models::PostRevision, to: &models::PostRevision) -> ApiResult<Self> {
+        if from.post_id != to.post_id {
+            return Err(ApiError::validation(
+                "to",
+                "revisions must belong to the same post",
+            ));
+        }
+        Ok(Self::compare(
+            (&from.title, &from.body, from.code.as_deref()),
+            (&to.title, &to.body, to.code.as_deref()),
+        ))
+    }
+
+    /// Diffs two versions of a post, each given as its title, body and code.
+    pub fn compare(
+        (old_title, old_body, old_code): (&str, &str, Option<&str>),
+        (new_title, new_body, new_code): (&str, &str, Option<&str>),
+    ) -> Self {
+        let diff = |name: &str, old: &str, new: &str| {
+            TextDiff::from_lines(old, new)
+                .unified_diff()
+                .context_radius(DIFF_CONTEXT_LINES)
+                .header(&format!("a/{name}"), &format!("b/{name}"))
+                .to_string()
+        };
+        Self {
+            title: diff("title", old_title, new_title),
+            body: diff("body", old_body, new_body),
+            code: match (old_code, new_code) {
+                (None, None) => None,
+                (old, new) => Some(diff(
+                    "code",
+                    old.unwrap_or_default(),
+                    new.unwrap_or_default(),
+                )),
+            },
+        }
+    }
+}
@@ -1,6 +1,9 @@
-use juniper::{FieldResult, GraphQLObject};
+use juniper::GraphQLObject;
 
-use crate::api::NodeId;
+use crate::api::{
+    NodeId,
+    errors::{ApiError, ApiResult},
+};
 
 // code adapted from https://github.com/Mego/juniper-relay
 
@@ -39,9 +42,12 @@ pub struct RelayConnectionPageInfo {
     end_cursor: Option<NodeId>,
 }
 
-fn check_ge_zero(val: i32) -> Result<i32, &'static str> {
+pub fn check_ge_zero(field: &str, val: i32) -> ApiResult<i32> {
     if val < 0 {
-        Err("Pagination argument must be positive")
+        Err(ApiError::validation(
+            field,
+            "Pagination argument must be positive",
+        ))
     } else {
         Ok(val)
     }
@@ -64,7 +70,7 @@ pub fn build_connection<N, E, B>(
     last: Option<i32>,
     nodes: Vec<N>,
     build_edge: B,
-) -> FieldResult<(RelayConnectionPageInfo, Vec<E>)>
+) -> ApiResult<(RelayConnectionPageInfo, Vec<E>)>
 where
     N: RelayConnectionNode,
     E: RelayConnectionEdge<N>,
@@ -72,8 +78,8 @@ where
 {
     let edges_len: i32 = nodes.len().try_into()?;
 
-    let first = first.map(check_ge_zero).transpose()?;
-    let last = last.map(check_ge_zero).transpose()?;
+    let first = first.map(|v| check_ge_zero("first", v)).transpose()?;
+    let last = last.map(|v| check_ge_zero("last", v)).transpose()?;
 
     let has_previous_page = if let Some(last) = last {
         edges_len > last
@@ -112,9 +118,9 @@ where
 
 #[macro_export]
 macro_rules! relayify {
-    ($node:ident, $(($foreign:ident, $get_nodes:expr)),+) => {
+    ($node:ident $(, ($foreign:ident, $get_nodes:expr))* $(,)?) => {
         paste::paste! {
-            impl crate::api::relay::RelayConnectionNode for $node {
+            impl $crate::api::relay::RelayConnectionNode for $node {
                 fn cursor(&self) -> NodeId {
                     self.id()
                 }
@@ -150,7 +156,7 @@ macro_rules! relayify {
                 }
             }
 
-            impl crate::api::relay::RelayConnectionEdge<$node> for [<$node ConnectionEdge>] {
+            impl $crate::api::relay::RelayConnectionEdge<$node> for [<$node ConnectionEdge>] {
                 fn node(&self) -> &$node {
                     &self.node
                 }
@@ -164,31 +170,31 @@ macro_rules! relayify {
             #[graphql(context = Context)]
             pub struct [<$node Connection>] {
                 edges: Vec<[<$node ConnectionEdge>]>,
-                page_info: crate::api::relay::RelayConnectionPageInfo,
+                page_info: $crate::api::relay::RelayConnectionPageInfo,
             }
 
             impl [<$node Connection>] {
                 pub fn new(
                     edges: Vec<[<$node ConnectionEdge>]>,
-                    page_info: crate::api::relay::RelayConnectionPageInfo
+                    page_info: $crate::api::relay::RelayConnectionPageInfo
                 ) -> Self {
                     Self { edges, page_info }
                 }
 
                 $(
-                    pub async fn [<try_from_ $foreign:lower>]<'c>(
+                    pub async fn [<try_from_ $foreign:lower>](
                         foreign_id: i32,
-                        ctx: &'c Context,
+                        ctx: &Context,
                         first: Option<i32>,
                         after: Option<NodeId>,
                         last: Option<i32>,
                         before: Option<NodeId>,
-                    ) -> FieldResult<Self> {
-                        let (after, before, limit) = crate::api::relay::relay_connection_closure_args(first, after, before);
+                    ) -> $crate::api::errors::ApiResult<Self> {
+                        let (after, before, limit) = $crate::api::relay::relay_connection_closure_args(first, after, before);
                         let mut cnx = ctx.db.get().await?;
                         let node_getter = $get_nodes;
                         let nodes = node_getter(&mut cnx, foreign_id, after, before, limit).await?;
-                        let (page_info, edges) = crate::api::relay::build_connection(
+                        let (page_info, edges) = $crate::api::relay::build_connection(
                             first,
                             last,
                             nodes.into_iter().map($node::from).collect(),
@@ -196,7 +202,7 @@ macro_rules! relayify {
                         )?;
                         Ok(Self::new(edges, page_info))
                     }
-                )+
+                )*
             }
         }
     };
@@ -1,12 +1,22 @@
 use async_lock::OnceCell;
-use juniper::{FieldResult, graphql_object};
+use juniper::graphql_object;
 
 use crate::{
     api::{
-        Context, NodeId, NodeValue, challenge::Challenge, comment::CommentConnection,
-        post::PostValue, user::User,
+        Context, NodeId, NodeValue,
+        attachment::Attachment,
+        challenge::Challenge,
+        comment::{CommentConnection, CommentOrder, TopComments},
+        errors::{ApiError, ApiResult},
+        post::PostValue,
+        post_lock::PostLock,
+        post_revision::PostRevisionConnection,
+        suggested_edit::SuggestedEditConnection,
+        user::User,
     },
-    models, relayify,
+    models::{self, SchemaTable},
+    relayify,
+    render::code,
 };
 
 pub struct Response {
@@ -18,12 +28,15 @@ pub struct Response {
 #[graphql(impl = [NodeValue, PostValue], context = Context)]
 impl Response {
     #[graphql(skip)]
-    async fn fetch_from_db<'c>(&self, ctx: &'c Context) -> FieldResult<&models::Response> {
+    async fn fetch_from_db(&self, ctx: &Context) -> ApiResult<&models::Response> {
         self.db_model
             .get_or_try_init(async || {
-                let mut cnx = ctx.db.get().await?;
-                let model = models::Response::find(&mut cnx, self.db_id).await?;
-                Ok(model)
+                Ok(ctx
+                    .loader
+                    .responses
+                    .try_load(self.db_id)
+                    .await
+                    .map_err(|_| ApiError::not_found(self.id()))??)
             })
             .await
     }
@@ -47,28 +60,30 @@ impl Response {
     }
 
     pub fn id(&self) -> NodeId {
-        NodeId::from(("responses", self.db_id))
+        NodeId::from((SchemaTable::Responses, self.db_id))
     }
 
-    pub async fn title<'c>(&self, ctx: &'c Context) -> FieldResult<&String> {
+    pub async fn title(&self, ctx: &Context) -> ApiResult<&String> {
         Ok(&self.fetch_from_db(ctx).await?.post.title)
     }
 
-    pub async fn body<'c>(&self, ctx: &'c Context) -> FieldResult<&String> {
+    pub async fn body(&self, ctx: &Context) -> ApiResult<&String> {
         Ok(&self.fetch_from_db(ctx).await?.post.body)
     }
 
-    pub async fn created_at<'c>(
-        &self,
-        ctx: &'c Context,
-    ) -> FieldResult<chrono::DateTime<chrono::Utc>> {
+    /// The body rendered as sanitized HTML.
+    pub async fn body_html(&self, ctx: &Context) -> ApiResult<String> {
+        Ok(self.fetch_from_db(ctx).await?.post.render_body())
+    }
+
+    pub async fn created_at(&self, ctx: &Context) -> ApiResult<chrono::DateTime<chrono::Utc>> {
         Ok(self.fetch_from_db(ctx).await?.post.created_at.and_utc())
     }
 
-    pub async fn updated_at<'c>(
+    pub async fn updated_at(
         &self,
-        ctx: &'c Context,
-    ) -> FieldResult<Option<chrono::DateTime<chrono::Utc>>> {
+        ctx: &Context,
+    ) -> ApiResult<Option<chrono::DateTime<chrono::Utc>>> {
         Ok(self
             .fetch_from_db(ctx)
             .await?
@@ -77,10 +92,10 @@ impl Response {
             .map(|dt| dt.and_utc()))
     }
 
-    pub async fn deleted_at<'c>(
+    pub async fn deleted_at(
         &self,
-        ctx: &'c Context,
-    ) -> FieldResult<Option<chrono::DateTime<chrono::Utc>>> {
+        ctx: &Context,
+    ) -> ApiResult<Option<chrono::DateTime<chrono::Utc>>> {
         Ok(self
             .fetch_from_db(ctx)
             .await?
@@ -89,29 +104,120 @@ impl Response {
             .map(|dt| dt.and_utc()))
     }
 
-    pub async fn author<'c>(&self, ctx: &'c Context) -> FieldResult<User> {
+    pub async fn author(&self, ctx: &Context) -> ApiResult<User> {
         let user_id = self.fetch_from_db(ctx).await?.post.user_id;
         Ok(ctx.loader.users.try_load(user_id).await??.into())
     }
 
-    pub async fn code<'c>(&self, ctx: &'c Context) -> FieldResult<&String> {
+    /// The moderator lock currently restricting the post, if any.
+    pub async fn lock(&self, ctx: &Context) -> ApiResult<Option<PostLock>> {
+        let mut cnx = ctx.db.get().await?;
+        Ok(models::PostLock::find_active(&mut cnx, self.db_id)
+            .await?
+            .map(Into::into))
+    }
+
+    /// The users the post's body `@`-mentions.
+    pub async fn mentions(&self, ctx: &Context) -> ApiResult<Vec<User>> {
+        let mut cnx = ctx.db.get().await?;
+        Ok(models::mention::filter_by_post(&mut cnx, self.db_id)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// The uploaded files the post's body links to, oldest first.
+    pub async fn attachments(&self, ctx: &Context) -> ApiResult<Vec<Attachment>> {
+        let mut cnx = ctx.db.get().await?;
+        Ok(models::Attachment::filter_by_post(&mut cnx, self.db_id)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    pub async fn code(&self, ctx: &Context) -> ApiResult<&String> {
         Ok(&self.fetch_from_db(ctx).await?.data.code)
     }
 
-    pub async fn challenge<'c>(&self, ctx: &'c Context) -> FieldResult<Challenge> {
+    /// The code highlighted for its language as HTML, wrapped in `<pre><code>`. Tokens are
+    /// `<span>`s classed by scope, each class prefixed with `hl-`, so any TextMate theme can be
+    /// turned into a stylesheet for it. Falls back to plain text for unknown languages.
+    pub async fn code_html(&self, ctx: &Context) -> ApiResult<String> {
+        let data = &self.fetch_from_db(ctx).await?.data;
+        Ok(match &data.code_html {
+            Some(html) => html.clone(),
+            None => code::highlight_blocking(data.code.clone(), data.language.clone()).await,
+        })
+    }
+
+    pub async fn language(&self, ctx: &Context) -> ApiResult<Option<&String>> {
+        Ok(self.fetch_from_db(ctx).await?.data.language.as_ref())
+    }
+
+    pub async fn challenge(&self, ctx: &Context) -> ApiResult<Challenge> {
         let challenge_id = self.fetch_from_db(ctx).await?.data.challenge_id;
         Ok(ctx.loader.challenges.try_load(challenge_id).await??.into())
     }
 
-    pub async fn comments<'c>(
+    /// Comments on the response, oldest first unless ordered otherwise. Replies are left out if
+    /// `topLevelOnly` is set, to be fetched through their parents.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn comments(
+        &self,
+        ctx: &Context,
+        #[graphql(default = false)] top_level_only: bool,
+        order_by: Option<CommentOrder>,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<CommentConnection> {
+        CommentConnection::try_from_response(
+            self.db_id,
+            ctx,
+            top_level_only,
+            order_by.unwrap_or_default(),
+            first,
+            after,
+            last,
+            before,
+        )
+        .await
+    }
+
+    /// The `count` most upvoted comments, oldest first, and how many others there are: the rest
+    /// are meant to be shown collapsed. Replies are left out if `topLevelOnly` is set.
+    pub async fn top_comments(
+        &self,
+        ctx: &Context,
+        #[graphql(default = 5)] count: i32,
+        #[graphql(default = false)] top_level_only: bool,
+    ) -> ApiResult<TopComments> {
+        TopComments::for_post(ctx, self.db_id, top_level_only, count).await
+    }
+
+    pub async fn revisions(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<PostRevisionConnection> {
+        PostRevisionConnection::try_from_post(self.db_id, ctx, first, after, last, before).await
+    }
+
+    pub async fn suggested_edits(
         &self,
-        ctx: &'c Context,
+        ctx: &Context,
         first: Option<i32>,
         after: Option<NodeId>,
         last: Option<i32>,
         before: Option<NodeId>,
-    ) -> FieldResult<CommentConnection> {
-        CommentConnection::try_from_response(self.db_id, ctx, first, after, last, before).await
+    ) -> ApiResult<SuggestedEditConnection> {
+        SuggestedEditConnection::try_from_post(self.db_id, ctx, first, after, last, before).await
     }
 }
 
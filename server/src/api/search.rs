@@ -0,0 +1,310 @@
+use juniper::{GraphQLEnum, GraphQLObject, GraphQLUnion, graphql_object};
+
+use crate::{
+    api::{
+        Challenge, Comment, Context, NodeId, Response,
+        errors::{ApiError, ApiResult},
+        relay::{
+            RelayConnectionEdge, RelayConnectionNode, RelayConnectionPageInfo, build_connection,
+            check_ge_zero, relay_connection_closure_args,
+        },
+    },
+    models::{
+        SchemaTable,
+        search::{self, SearchKind},
+    },
+};
+
+#[derive(GraphQLEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SearchResultType {
+    Challenge,
+    Response,
+    Comment,
+}
+
+impl From<SearchResultType> for SearchKind {
+    fn from(value: SearchResultType) -> Self {
+        match value {
+            SearchResultType::Challenge => Self::Challenge,
+            SearchResultType::Response => Self::Response,
+            SearchResultType::Comment => Self::Comment,
+        }
+    }
+}
+
+#[derive(GraphQLUnion)]
+#[graphql(context = Context)]
+pub enum SearchResult {
+    Challenge(Challenge),
+    Response(Response),
+    Comment(Comment),
+}
+
+pub struct SearchHit {
+    node: SearchResult,
+    hit: search::SearchHit,
+}
+
+impl From<search::SearchHit> for SearchHit {
+    fn from(hit: search::SearchHit) -> Self {
+        let node = match hit.kind {
+            SearchKind::Challenge => SearchResult::Challenge(Challenge::new(hit.id)),
+            SearchKind::Response => SearchResult::Response(Response::new(hit.id)),
+            SearchKind::Comment => SearchResult::Comment(Comment::new(hit.id)),
+        };
+        Self { node, hit }
+    }
+}
+
+impl RelayConnectionNode for SearchHit {
+    fn cursor(&self) -> NodeId {
+        match &self.node {
+            SearchResult::Challenge(n) => n.id(),
+            SearchResult::Response(n) => n.id(),
+            SearchResult::Comment(n) => n.id(),
+        }
+    }
+
+    fn connection_type_name() -> &'static str {
+        "SearchConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "SearchConnectionEdge"
+    }
+}
+
+pub struct SearchConnectionEdge {
+    hit: SearchHit,
+}
+
+#[graphql_object]
+#[graphql(context = Context)]
+impl SearchConnectionEdge {
+    #[graphql(skip)]
+    pub fn new(hit: SearchHit) -> Self {
+        Self { hit }
+    }
+
+    pub fn node(&self) -> &SearchResult {
+        &self.hit.node
+    }
+
+    pub fn cursor(&self) -> NodeId {
+        self.hit.cursor()
+    }
+
+    /// How well the result matches, as computed by `ts_rank`. Only comparable within one search.
+    pub fn rank(&self) -> f64 {
+        self.hit.hit.rank.into()
+    }
+
+    /// The post's title as escaped HTML, with every match wrapped in `<mark>` tags. Null for
+    /// comments.
+    pub fn title_highlight(&self) -> Option<&String> {
+        self.hit.hit.title.as_ref()
+    }
+
+    /// Fragments of the body around the matches as escaped HTML, with the matches wrapped in
+    /// `<mark>` tags.
+    pub fn snippet(&self) -> &String {
+        &self.hit.hit.snippet
+    }
+}
+
+impl RelayConnectionEdge<SearchHit> for SearchConnectionEdge {
+    fn node(&self) -> &SearchHit {
+        &self.hit
+    }
+
+    fn cursor(&self) -> NodeId {
+        self.cursor()
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+#[graphql(context = Context)]
+pub struct SearchConnection {
+    edges: Vec<SearchConnectionEdge>,
+    page_info: RelayConnectionPageInfo,
+}
+
+impl SearchConnection {
+    pub async fn search(
+        ctx: &Context,
+        query: &str,
+        types: Option<Vec<SearchResultType>>,
+        first: Option<i32>,
+        after: Option<NodeId>,
+    ) -> ApiResult<Self> {
+        first.map(|v| check_ge_zero("first", v)).transpose()?;
+        let query = search::parse_query(query)
+            .ok_or_else(|| ApiError::validation("query", "search has no words to match"))?;
+        let kinds: Vec<SearchKind> = match types {
+            Some(types) if !types.is_empty() => types.into_iter().map(Into::into).collect(),
+            _ => vec![
+                SearchKind::Challenge,
+                SearchKind::Response,
+                SearchKind::Comment,
+            ],
+        };
+        let mut cnx = ctx.db.get().await?;
+        let after = match after {
+            Some(NodeId(table, id)) => {
+                let kind = match table {
+                    SchemaTable::Challenges => SearchKind::Challenge,
+                    SchemaTable::Responses => SearchKind::Response,
+                    SchemaTable::Comments => SearchKind::Comment,
+                    _ => {
+                        return Err(ApiError::validation(
+                            "after",
+                            format!("unexpected {table} cursor"),
+                        ));
+                    }
+                };
+                let cursor = search::find_cursor(&mut cnx, &query, kind, id).await?;
+                Some(cursor.ok_or_else(|| {
+                    ApiError::validation("after", "cursor no longer refers to a result")
+                })?)
+            }
+            None => None,
+        };
+        let limit = first.map(|l| i64::from(l) + 1);
+        let hits = search::search(&mut cnx, &query, &kinds, after, limit).await?;
+        let (page_info, edges) = build_connection(
+            first,
+            None,
+            hits.into_iter().map(SearchHit::from).collect(),
+            SearchConnectionEdge::new,
+        )?;
+        Ok(Self { edges, page_info })
+    }
+}
+
+/// Lines of code, numbered from 1.
+#[derive(GraphQLObject, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LineRange {
+    pub start: i32,
+    /// The last line of the range, inclusive.
+    pub end: i32,
+}
+
+pub struct CodeMatch {
+    node: Response,
+    lines: Vec<LineRange>,
+}
+
+impl From<search::CodeMatch> for CodeMatch {
+    fn from(value: search::CodeMatch) -> Self {
+        Self {
+            node: Response::new(value.response_id),
+            lines: value
+                .lines
+                .into_iter()
+                .map(|range| LineRange {
+                    start: *range.start(),
+                    end: *range.end(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl RelayConnectionNode for CodeMatch {
+    fn cursor(&self) -> NodeId {
+        self.node.id()
+    }
+
+    fn connection_type_name() -> &'static str {
+        "CodeSearchConnection"
+    }
+
+    fn edge_type_name() -> &'static str {
+        "CodeSearchConnectionEdge"
+    }
+}
+
+pub struct CodeSearchConnectionEdge {
+    code_match: CodeMatch,
+}
+
+#[graphql_object]
+#[graphql(context = Context)]
+impl CodeSearchConnectionEdge {
+    #[graphql(skip)]
+    pub fn new(code_match: CodeMatch) -> Self {
+        Self { code_match }
+    }
+
+    pub fn node(&self) -> &Response {
+        &self.code_match.node
+    }
+
+    pub fn cursor(&self) -> NodeId {
+        self.code_match.cursor()
+    }
+
+    /// The lines of the response's code that match.
+    pub fn lines(&self) -> &[LineRange] {
+        &self.code_match.lines
+    }
+}
+
+impl RelayConnectionEdge<CodeMatch> for CodeSearchConnectionEdge {
+    fn node(&self) -> &CodeMatch {
+        &self.code_match
+    }
+
+    fn cursor(&self) -> NodeId {
+        self.cursor()
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+#[graphql(context = Context)]
+pub struct CodeSearchConnection {
+    edges: Vec<CodeSearchConnectionEdge>,
+    page_info: RelayConnectionPageInfo,
+}
+
+impl CodeSearchConnection {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search(
+        ctx: &Context,
+        pattern: &str,
+        language: Option<&str>,
+        regex: bool,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<Self> {
+        first.map(|v| check_ge_zero("first", v)).transpose()?;
+        last.map(|v| check_ge_zero("last", v)).transpose()?;
+        if pattern.is_empty() {
+            return Err(ApiError::validation("pattern", "pattern must not be empty"));
+        }
+        if pattern.contains('\n') {
+            return Err(ApiError::validation(
+                "pattern",
+                "patterns are matched line by line and can't contain newlines",
+            ));
+        }
+        for (field, cursor) in [("after", &after), ("before", &before)] {
+            if let Some(cursor) = cursor {
+                cursor.expect_table(field, &[SchemaTable::Responses])?;
+            }
+        }
+        let (after, before, limit) = relay_connection_closure_args(first, after, before);
+        let mut cnx = ctx.db.get().await?;
+        let matches =
+            search::search_code(&mut cnx, pattern, regex, language, after, before, limit).await?;
+        let (page_info, edges) = build_connection(
+            first,
+            last,
+            matches.into_iter().map(CodeMatch::from).collect(),
+            CodeSearchConnectionEdge::new,
+        )?;
+        Ok(Self { edges, page_info })
+    }
+}
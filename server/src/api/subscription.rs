@@ -0,0 +1,128 @@
+use std::pin::Pin;
+
+use diesel::OptionalExtension;
+use futures::{Stream, StreamExt};
+use juniper::graphql_subscription;
+
+use crate::{
+    api::{Comment, Context, NodeId, Response, errors::ApiResult, post::PostValue},
+    db::events::DbEvent,
+    models::{self, SchemaTable},
+};
+
+type NodeStream<T> = Pin<Box<dyn Stream<Item = ApiResult<T>> + Send>>;
+
+pub struct Subscription;
+
+// Each event is loaded straight from the database rather than through the dataloader, since the
+// context lives as long as the connection and its cache would only ever serve the first version.
+#[graphql_subscription]
+#[graphql(context = Context)]
+impl Subscription {
+    /// Emits every response posted to the challenge from now on.
+    async fn response_added(
+        ctx: &Context,
+        challenge_id: NodeId,
+    ) -> ApiResult<NodeStream<Response>> {
+        let challenge_id = challenge_id.expect_table("challengeId", &[SchemaTable::Challenges])?;
+        let db = ctx.db.clone();
+        Ok(ctx
+            .events
+            .subscribe()
+            .filter_map(move |event| {
+                let db = db.clone();
+                async move {
+                    let DbEvent::ResponseAdded {
+                        challenge_id: event_challenge_id,
+                        response_id,
+                    } = event
+                    else {
+                        return None;
+                    };
+                    if event_challenge_id != challenge_id {
+                        return None;
+                    }
+                    let model = async {
+                        let mut cnx = db.get().await?;
+                        Ok(models::Response::find(&mut cnx, response_id)
+                            .await
+                            .optional()?)
+                    }
+                    .await;
+                    model.transpose().map(|m| m.map(Response::from))
+                }
+            })
+            .boxed())
+    }
+
+    /// Emits every comment left on the post from now on.
+    async fn comment_added(ctx: &Context, post_id: NodeId) -> ApiResult<NodeStream<Comment>> {
+        let post_id = post_id.expect_table(
+            "postId",
+            &[
+                SchemaTable::Challenges,
+                SchemaTable::Responses,
+                SchemaTable::Posts,
+            ],
+        )?;
+        let db = ctx.db.clone();
+        Ok(ctx
+            .events
+            .subscribe()
+            .filter_map(move |event| {
+                let db = db.clone();
+                async move {
+                    let DbEvent::CommentAdded {
+                        post_id: event_post_id,
+                        comment_id,
+                    } = event
+                    else {
+                        return None;
+                    };
+                    if event_post_id != post_id {
+                        return None;
+                    }
+                    let model = async {
+                        let mut cnx = db.get().await?;
+                        Ok(models::Comment::find(&mut cnx, comment_id)
+                            .await
+                            .optional()?)
+                    }
+                    .await;
+                    model.transpose().map(|m| m.map(Comment::from))
+                }
+            })
+            .boxed())
+    }
+
+    /// Emits the post's new contents every time it is edited.
+    async fn post_edited(ctx: &Context, id: NodeId) -> ApiResult<NodeStream<PostValue>> {
+        let post_id = id.expect_table(
+            "id",
+            &[
+                SchemaTable::Challenges,
+                SchemaTable::Responses,
+                SchemaTable::Posts,
+            ],
+        )?;
+        let db = ctx.db.clone();
+        Ok(ctx
+            .events
+            .subscribe()
+            .filter_map(move |event| {
+                let db = db.clone();
+                async move {
+                    if event != (DbEvent::PostEdited { post_id }) {
+                        return None;
+                    }
+                    let post = async {
+                        let mut cnx = db.get().await?;
+                        PostValue::load_fresh(&mut cnx, post_id).await
+                    }
+                    .await;
+                    post.transpose()
+                }
+            })
+            .boxed())
+    }
+}
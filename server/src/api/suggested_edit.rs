@@ -0,0 +1,142 @@
+use juniper::{GraphQLEnum, graphql_object};
+
+use crate::{
+    api::{
+        Context, NodeId, NodeValue, User,
+        errors::ApiResult,
+        post::PostValue,
+        post_revision::{PostRevision, RevisionDiff},
+    },
+    models::{self, SchemaTable, suggested_edit},
+    relayify,
+};
+
+#[derive(GraphQLEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SuggestedEditStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl From<suggested_edit::SuggestedEditStatus> for SuggestedEditStatus {
+    fn from(value: suggested_edit::SuggestedEditStatus) -> Self {
+        match value {
+            suggested_edit::SuggestedEditStatus::Pending => Self::Pending,
+            suggested_edit::SuggestedEditStatus::Approved => Self::Approved,
+            suggested_edit::SuggestedEditStatus::Rejected => Self::Rejected,
+        }
+    }
+}
+
+/// A change to a post proposed by someone other than its author. It's applied once the author
+/// or a moderator approves it.
+pub struct SuggestedEdit {
+    model: models::SuggestedEdit,
+}
+
+#[graphql_object]
+#[graphql(impl = [NodeValue], context = Context)]
+impl SuggestedEdit {
+    pub fn id(&self) -> NodeId {
+        NodeId::from((SchemaTable::SuggestedEdits, self.model.id))
+    }
+
+    pub async fn post(&self, ctx: &Context) -> ApiResult<PostValue> {
+        PostValue::try_from_db_id(self.model.post_id, ctx).await
+    }
+
+    pub async fn suggester(&self, ctx: &Context) -> ApiResult<User> {
+        Ok(ctx
+            .loader
+            .users
+            .try_load(self.model.suggester_id)
+            .await??
+            .into())
+    }
+
+    /// The proposed title. Null if the suggestion leaves it unchanged.
+    pub fn title(&self) -> Option<&String> {
+        self.model.title.as_ref()
+    }
+
+    /// The proposed body. Null if the suggestion leaves it unchanged.
+    pub fn body(&self) -> Option<&String> {
+        self.model.body.as_ref()
+    }
+
+    /// Why the change was suggested. Becomes the revision's summary if approved.
+    pub fn comment(&self) -> &String {
+        &self.model.comment
+    }
+
+    pub fn status(&self) -> SuggestedEditStatus {
+        self.model.status.into()
+    }
+
+    /// Null until the suggestion is reviewed.
+    pub async fn reviewer(&self, ctx: &Context) -> ApiResult<Option<User>> {
+        match self.model.reviewer_id {
+            Some(id) => Ok(Some(ctx.loader.users.try_load(id).await??.into())),
+            None => Ok(None),
+        }
+    }
+
+    pub fn review_comment(&self) -> Option<&String> {
+        self.model.review_comment.as_ref()
+    }
+
+    /// The revision the suggestion was applied as. Null unless approved.
+    pub async fn revision(&self, ctx: &Context) -> ApiResult<Option<PostRevision>> {
+        let Some(id) = self.model.revision_id else {
+            return Ok(None);
+        };
+        let mut cnx = ctx.db.get().await?;
+        Ok(Some(models::PostRevision::find(&mut cnx, id).await?.into()))
+    }
+
+    /// The revision the suggestion was made against. It can only be approved while this is
+    /// still the post's latest revision.
+    pub async fn base_revision(&self, ctx: &Context) -> ApiResult<PostRevision> {
+        let mut cnx = ctx.db.get().await?;
+        Ok(
+            models::PostRevision::find(&mut cnx, self.model.base_revision_id)
+                .await?
+                .into(),
+        )
+    }
+
+    /// The changes the suggestion makes to the post's current title and body.
+    pub async fn diff(&self, ctx: &Context) -> ApiResult<RevisionDiff> {
+        let mut cnx = ctx.db.get().await?;
+        let post = models::Post::find(&mut cnx, self.model.post_id).await?;
+        Ok(RevisionDiff::compare(
+            (&post.title, &post.body, None),
+            (
+                self.model.title.as_deref().unwrap_or(&post.title),
+                self.model.body.as_deref().unwrap_or(&post.body),
+                None,
+            ),
+        ))
+    }
+
+    pub fn created_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.model.created_at.and_utc()
+    }
+
+    pub fn reviewed_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.model.reviewed_at.map(|t| t.and_utc())
+    }
+}
+
+relayify!(
+    SuggestedEdit,
+    (Post, async |cnx, post_id, after, before, limit| {
+        models::SuggestedEdit::filter_by_post(cnx, post_id, after, before, limit).await
+    })
+);
+
+impl From<models::SuggestedEdit> for SuggestedEdit {
+    fn from(model: models::SuggestedEdit) -> Self {
+        Self { model }
+    }
+}
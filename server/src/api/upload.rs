@@ -0,0 +1,48 @@
+use juniper::{
+    GraphQLScalar, InputValue, ParseScalarResult, ParseScalarValue, ScalarToken, ScalarValue, Value,
+};
+
+use crate::api::errors::{ApiError, ApiResult};
+
+/// A file sent alongside the operation as a `multipart/form-data` request, following the
+/// GraphQL multipart request spec. Only valid as an argument; the server puts the name of the
+/// form field carrying the file in its place.
+#[derive(GraphQLScalar, Clone, Debug, PartialEq, Eq)]
+#[graphql(with = Self)]
+pub struct Upload(pub String);
+
+impl Upload {
+    fn to_output<S: ScalarValue>(&self) -> Value<S> {
+        Value::scalar(self.0.clone())
+    }
+
+    fn from_input<S: ScalarValue>(input: &InputValue<S>) -> Result<Self, String> {
+        input
+            .as_string_value()
+            .map(|name| Self(name.to_owned()))
+            .ok_or_else(|| format!("Expected an uploaded file, found {input}"))
+    }
+
+    fn parse_token<S: ScalarValue>(t: ScalarToken<'_>) -> ParseScalarResult<S> {
+        <String as ParseScalarValue<S>>::from_str(t)
+    }
+}
+
+/// A file received in a multipart request.
+#[derive(Debug, Clone)]
+pub struct UploadedFile {
+    /// The name the client gave the file, if any.
+    pub filename: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl super::Context {
+    /// Takes the file `upload` refers to. Each file can only be taken once.
+    pub fn take_upload(&self, field: &str, upload: &Upload) -> ApiResult<UploadedFile> {
+        self.uploads
+            .lock()
+            .unwrap()
+            .remove(&upload.0)
+            .ok_or_else(|| ApiError::validation(field, "no file was uploaded for this argument"))
+    }
+}
@@ -1,12 +1,20 @@
 use async_lock::OnceCell;
-use juniper::{FieldResult, graphql_object};
+use juniper::graphql_object;
 
 use crate::{
     api::{
-        Context, NodeId, NodeValue, challenge::ChallengeConnection, comment::CommentConnection,
+        Context, NodeId, NodeValue,
+        challenge::ChallengeConnection,
+        comment::CommentConnection,
+        errors::{ApiError, ApiResult},
+        notification::{Notification, NotificationConnection, NotificationConnectionEdge},
+        relay::{build_connection, relay_connection_closure_args},
         response::ResponseConnection,
+        user_suspension::{UserSuspension, UserSuspensionConnection},
     },
-    models,
+    models::{self, SchemaTable},
+    render::identicon,
+    storage::{STORAGE, avatars},
 };
 
 pub struct User {
@@ -18,15 +26,15 @@ pub struct User {
 #[graphql(impl = [NodeValue], context = Context)]
 impl User {
     #[graphql(skip)]
-    async fn fetch_from_db<'c>(&self, ctx: &'c Context) -> FieldResult<&models::User> {
+    async fn fetch_from_db(&self, ctx: &Context) -> ApiResult<&models::User> {
         self.db_model
             .get_or_try_init(async || {
-                use crate::schema::users::dsl::*;
-                use diesel::QueryDsl;
-                use diesel_async::RunQueryDsl;
-                let mut cnx = ctx.db.get().await?;
-                let model = users.find(self.db_id).first(&mut cnx).await?;
-                Ok(model)
+                Ok(ctx
+                    .loader
+                    .users
+                    .try_load(self.db_id)
+                    .await
+                    .map_err(|_| ApiError::not_found(self.id()))??)
             })
             .await
     }
@@ -40,28 +48,49 @@ impl User {
     }
 
     pub fn id(&self) -> NodeId {
-        NodeId::from(("users", self.db_id))
+        NodeId::from((SchemaTable::Users, self.db_id))
     }
 
-    pub async fn name<'c>(&self, ctx: &'c Context) -> FieldResult<&String> {
+    pub async fn name(&self, ctx: &Context) -> ApiResult<&String> {
         Ok(&self.fetch_from_db(ctx).await?.name)
     }
 
-    pub async fn profile<'c>(&self, ctx: &'c Context) -> FieldResult<&String> {
+    pub async fn profile(&self, ctx: &Context) -> ApiResult<&String> {
         Ok(&self.fetch_from_db(ctx).await?.profile)
     }
 
-    pub async fn created_at<'c>(
+    /// A square image of the user, at least `size` pixels wide where possible. Avatars come in a
+    /// few standard sizes and the closest one is picked, so scale it to fit. Users who haven't
+    /// uploaded one get an identicon.
+    pub async fn avatar_url(
         &self,
-        ctx: &'c Context,
-    ) -> FieldResult<chrono::DateTime<chrono::Utc>> {
+        ctx: &Context,
+        #[graphql(default = 64)] size: i32,
+    ) -> ApiResult<String> {
+        let size = u32::try_from(size)
+            .ok()
+            .filter(|&size| size > 0)
+            .ok_or_else(|| ApiError::validation("size", "size must be positive"))?;
+        let size = avatars::standard_size(size);
+        Ok(match &self.fetch_from_db(ctx).await?.avatar_key {
+            Some(avatar_key) => STORAGE.url(&avatars::key(avatar_key, size)),
+            None => identicon::url(self.db_id, size),
+        })
+    }
+
+    /// Whether the user uploaded an avatar, rather than showing an identicon.
+    pub async fn has_avatar(&self, ctx: &Context) -> ApiResult<bool> {
+        Ok(self.fetch_from_db(ctx).await?.avatar_key.is_some())
+    }
+
+    pub async fn created_at(&self, ctx: &Context) -> ApiResult<chrono::DateTime<chrono::Utc>> {
         Ok(self.fetch_from_db(ctx).await?.created_at.and_utc())
     }
 
-    pub async fn updated_at<'c>(
+    pub async fn updated_at(
         &self,
-        ctx: &'c Context,
-    ) -> FieldResult<Option<chrono::DateTime<chrono::Utc>>> {
+        ctx: &Context,
+    ) -> ApiResult<Option<chrono::DateTime<chrono::Utc>>> {
         Ok(self
             .fetch_from_db(ctx)
             .await?
@@ -69,10 +98,10 @@ impl User {
             .map(|dt| dt.and_utc()))
     }
 
-    pub async fn deleted_at<'c>(
+    pub async fn deleted_at(
         &self,
-        ctx: &'c Context,
-    ) -> FieldResult<Option<chrono::DateTime<chrono::Utc>>> {
+        ctx: &Context,
+    ) -> ApiResult<Option<chrono::DateTime<chrono::Utc>>> {
         Ok(self
             .fetch_from_db(ctx)
             .await?
@@ -80,6 +109,76 @@ impl User {
             .map(|dt| dt.and_utc()))
     }
 
+    /// When the user's current suspension ends. Null unless they're suspended.
+    pub async fn suspended_until(
+        &self,
+        ctx: &Context,
+    ) -> ApiResult<Option<chrono::DateTime<chrono::Utc>>> {
+        let user = self.fetch_from_db(ctx).await?;
+        Ok(user
+            .suspended_until
+            .filter(|_| user.is_suspended())
+            .map(|dt| dt.and_utc()))
+    }
+
+    /// The user's suspensions, oldest first. Only visible to the user and to moderators.
+    pub async fn suspensions(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<UserSuspensionConnection> {
+        if !UserSuspension::is_visible_to(self.db_id, ctx.viewer().await?) {
+            return Err(ApiError::forbidden(
+                "only moderators may see other users' suspensions",
+            ));
+        }
+        UserSuspensionConnection::try_from_user(self.db_id, ctx, first, after, last, before).await
+    }
+
+    /// The user's notifications, most recently updated first, optionally only the unread ones.
+    /// Only visible to the user.
+    pub async fn notifications(
+        &self,
+        ctx: &Context,
+        #[graphql(default = false)] unread_only: bool,
+        first: Option<i32>,
+        after: Option<NodeId>,
+        last: Option<i32>,
+        before: Option<NodeId>,
+    ) -> ApiResult<NotificationConnection> {
+        self.require_self(ctx, "notifications").await?;
+        let (after, before, limit) = relay_connection_closure_args(first, after, before);
+        let mut cnx = ctx.db.get().await?;
+        let nodes = models::Notification::filter_by_user(
+            &mut cnx,
+            self.db_id,
+            unread_only,
+            after,
+            before,
+            limit,
+        )
+        .await?;
+        let (page_info, edges) = build_connection(
+            first,
+            last,
+            nodes.into_iter().map(Notification::from).collect(),
+            NotificationConnectionEdge::new,
+        )?;
+        Ok(NotificationConnection::new(edges, page_info))
+    }
+
+    /// Only visible to the user.
+    pub async fn unread_notification_count(&self, ctx: &Context) -> ApiResult<i32> {
+        self.require_self(ctx, "notifications").await?;
+        let mut cnx = ctx.db.get().await?;
+        Ok(models::Notification::count_unread(&mut cnx, self.db_id)
+            .await?
+            .try_into()?)
+    }
+
     #[graphql(skip)]
     pub(super) fn from_model(model: models::User) -> Self {
         let res = Self {
@@ -90,40 +189,53 @@ impl User {
         res
     }
 
-    pub async fn challenges<'c>(
+    pub async fn challenges(
         &self,
-        ctx: &'c Context,
+        ctx: &Context,
         first: Option<i32>,
         after: Option<NodeId>,
         last: Option<i32>,
         before: Option<NodeId>,
-    ) -> FieldResult<ChallengeConnection> {
+    ) -> ApiResult<ChallengeConnection> {
         ChallengeConnection::try_from_user(self.db_id, ctx, first, after, last, before).await
     }
 
-    pub async fn responses<'c>(
+    pub async fn responses(
         &self,
-        ctx: &'c Context,
+        ctx: &Context,
         first: Option<i32>,
         after: Option<NodeId>,
         last: Option<i32>,
         before: Option<NodeId>,
-    ) -> FieldResult<ResponseConnection> {
+    ) -> ApiResult<ResponseConnection> {
         ResponseConnection::try_from_user(self.db_id, ctx, first, after, last, before).await
     }
 
-    pub async fn comments<'c>(
+    pub async fn comments(
         &self,
-        ctx: &'c Context,
+        ctx: &Context,
         first: Option<i32>,
         after: Option<NodeId>,
         last: Option<i32>,
         before: Option<NodeId>,
-    ) -> FieldResult<CommentConnection> {
+    ) -> ApiResult<CommentConnection> {
         CommentConnection::try_from_user(self.db_id, ctx, first, after, last, before).await
     }
 }
 
+impl User {
+    /// Fails unless the request is signed in as this user. `what` names the private data, for
+    /// the error message.
+    async fn require_self(&self, ctx: &Context, what: &str) -> ApiResult<()> {
+        if ctx.require_viewer().await?.id != self.db_id {
+            return Err(ApiError::forbidden(format!(
+                "only the user may see their {what}"
+            )));
+        }
+        Ok(())
+    }
+}
+
 impl From<models::User> for User {
     fn from(value: models::User) -> Self {
         Self::from_model(value)
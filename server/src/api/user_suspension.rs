@@ -0,0 +1,89 @@
+use juniper::graphql_object;
+
+use crate::{
+    api::{Context, NodeId, NodeValue, User, errors::ApiResult},
+    models::{self, SchemaTable},
+    relayify,
+};
+
+/// A period during which a moderator stopped a user from doing anything but reading. Only
+/// visible to the suspended user and to moderators.
+pub struct UserSuspension {
+    model: models::UserSuspension,
+}
+
+#[graphql_object]
+#[graphql(impl = [NodeValue], context = Context)]
+impl UserSuspension {
+    pub fn id(&self) -> NodeId {
+        NodeId::from((SchemaTable::UserSuspensions, self.model.id))
+    }
+
+    pub async fn user(&self, ctx: &Context) -> ApiResult<User> {
+        Ok(ctx.loader.users.try_load(self.model.user_id).await??.into())
+    }
+
+    pub async fn moderator(&self, ctx: &Context) -> ApiResult<User> {
+        Ok(ctx
+            .loader
+            .users
+            .try_load(self.model.moderator_id)
+            .await??
+            .into())
+    }
+
+    pub fn reason(&self) -> &String {
+        &self.model.reason
+    }
+
+    /// What the moderator told the suspended user.
+    pub fn message(&self) -> Option<&String> {
+        self.model.message.as_ref()
+    }
+
+    pub fn created_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.model.created_at.and_utc()
+    }
+
+    /// When the suspension ends, unless it's lifted before then.
+    pub fn ends_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.model.ends_at.and_utc()
+    }
+
+    /// When a moderator ended the suspension early. Null if it ran its course.
+    pub fn lifted_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.model.lifted_at.map(|t| t.and_utc())
+    }
+
+    pub async fn lifted_by(&self, ctx: &Context) -> ApiResult<Option<User>> {
+        match self.model.lifted_by_id {
+            Some(id) => Ok(Some(ctx.loader.users.try_load(id).await??.into())),
+            None => Ok(None),
+        }
+    }
+
+    pub fn lift_note(&self) -> Option<&String> {
+        self.model.lift_note.as_ref()
+    }
+}
+
+impl UserSuspension {
+    /// Whether `viewer` may see the suspensions of the user `user_id`: moderators see everyone's,
+    /// other users only their own.
+    pub fn is_visible_to(user_id: i32, viewer: Option<&models::User>) -> bool {
+        viewer.is_some_and(|v| v.role.is_moderator() || v.id == user_id)
+    }
+}
+
+relayify!(
+    UserSuspension,
+    (User, async |cnx, user_id, after, before, limit| {
+        models::UserSuspension::filter_by_user(cnx, user_id, after, before, limit).await
+    })
+);
+
+impl From<models::UserSuspension> for UserSuspension {
+    fn from(model: models::UserSuspension) -> Self {
+        Self { model }
+    }
+}
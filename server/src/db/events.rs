@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use futures::{Stream, StreamExt, stream};
+use serde::Deserialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_postgres::{AsyncMessage, NoTls};
+
+/// The channel the database triggers `pg_notify` on.
+pub const CHANNEL: &str = "axtell_events";
+
+/// How many events a slow subscriber may fall behind before it starts missing them.
+const CAPACITY: usize = 256;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// A change to the database, as reported by the triggers in the `activity_notifications`
+/// migration.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DbEvent {
+    ResponseAdded { challenge_id: i32, response_id: i32 },
+    CommentAdded { post_id: i32, comment_id: i32 },
+    PostEdited { post_id: i32 },
+}
+
+/// Fans out database notifications to every subscriber in this process. Since the events are
+/// raised by the database itself, every server instance sees every write.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<DbEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+
+    pub fn subscribe(&self) -> impl Stream<Item = DbEvent> + Send + 'static {
+        stream::unfold(self.sender.subscribe(), async |mut receiver| {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Listens for notifications on a dedicated connection, reconnecting whenever it drops.
+    /// Runs forever; spawn it once per process.
+    pub async fn listen(self, database_url: String) {
+        loop {
+            if let Err(e) = self.listen_once(&database_url).await {
+                log::error!("database event listener failed: {e}");
+            }
+            actix_web::rt::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    async fn listen_once(&self, database_url: &str) -> anyhow::Result<()> {
+        let (client, mut connection) = tokio_postgres::connect(database_url, NoTls).await?;
+        let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+        let forward = async {
+            while let Some(message) = messages.next().await {
+                if let AsyncMessage::Notification(n) = message? {
+                    match serde_json::from_str::<DbEvent>(n.payload()) {
+                        // an error only means nobody is subscribed right now
+                        Ok(event) => _ = self.sender.send(event),
+                        Err(e) => log::error!("malformed database event {:?}: {e}", n.payload()),
+                    }
+                }
+            }
+            anyhow::Ok(())
+        };
+        let listen = async {
+            client.batch_execute(&format!("LISTEN {CHANNEL}")).await?;
+            anyhow::Ok(())
+        };
+        futures::future::try_join(listen, forward).await?;
+        Ok(())
+    }
+}
@@ -6,10 +6,13 @@ use diesel_async::pooled_connection::AsyncDieselConnectionManager;
 use diesel_async::pooled_connection::deadpool::Pool;
 use dotenvy::dotenv;
 
+pub mod events;
 pub mod repo;
+pub use events::EventBus;
 pub use repo::{Loader, Repository};
 
 pub type DbPool = deadpool::managed::Pool<AsyncDieselConnectionManager<AsyncPgConnection>>;
+pub type PoolError = deadpool::managed::PoolError<diesel_async::pooled_connection::PoolError>;
 
 fn build_pool() -> Result<DbPool> {
     let config = AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new({
@@ -20,3 +23,5 @@ fn build_pool() -> Result<DbPool> {
 }
 
 pub static DB_POOL: LazyLock<DbPool> = LazyLock::new(|| build_pool().unwrap());
+
+pub static DB_EVENTS: LazyLock<EventBus> = LazyLock::new(EventBus::new);
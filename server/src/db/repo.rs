@@ -6,7 +6,7 @@ use futures::TryStreamExt;
 
 use crate::{
     db::DbPool,
-    models::{Challenge, ChallengeType, Comment, Post, Response, User},
+    models::{Challenge, ChallengeType, Comment, Post, Response, User, post::PostKind},
 };
 
 #[derive(Clone)]
@@ -46,6 +46,7 @@ impl Repository {
         let mut cnx = self.db_pool.get().await?;
         Ok(posts
             .filter(id.eq_any(ids))
+            .select(Post::as_select())
             .load_stream::<Post>(&mut cnx)
             .await?
             .try_fold(HashMap::new(), |mut acc, item| {
@@ -55,6 +56,17 @@ impl Repository {
             .await?)
     }
 
+    pub async fn load_post_kinds_by_ids(
+        &self,
+        ids: &[i32],
+    ) -> anyhow::Result<HashMap<i32, PostKind>> {
+        let mut cnx = self.db_pool.get().await?;
+        Ok(PostKind::find_by_ids(&mut cnx, ids)
+            .await?
+            .into_iter()
+            .collect())
+    }
+
     pub async fn load_challenges_by_ids(
         &self,
         ids: &[i32],
@@ -102,6 +114,7 @@ impl Repository {
         let mut cnx = self.db_pool.get().await?;
         Ok(comments
             .filter(id.eq_any(ids))
+            .select(Comment::as_select())
             .load_stream::<Comment>(&mut cnx)
             .await?
             .try_fold(HashMap::new(), |mut acc, item| {
@@ -201,6 +214,7 @@ macro_rules! make_loader {
 
 make_loader!(User);
 make_loader!(Post);
+make_loader!(PostKind);
 make_loader!(Challenge);
 make_loader!(Response);
 make_loader!(Comment);
@@ -209,6 +223,7 @@ make_loader!(ChallengeType);
 pub struct Loader {
     pub users: UserLoader,
     pub posts: PostLoader,
+    pub post_kinds: PostKindLoader,
     pub challenges: ChallengeLoader,
     pub responses: ResponseLoader,
     pub comments: CommentLoader,
@@ -220,6 +235,7 @@ impl Loader {
         Self {
             users: UserLoader::new(UserBatcher::new(db_pool)),
             posts: PostLoader::new(PostBatcher::new(db_pool)),
+            post_kinds: PostKindLoader::new(PostKindBatcher::new(db_pool)),
             challenges: ChallengeLoader::new(ChallengeBatcher::new(db_pool)),
             challenge_type: ChallengeTypeLoader::new(ChallengeTypeBatcher::new(db_pool)),
             responses: ResponseLoader::new(ResponseBatcher::new(db_pool)),
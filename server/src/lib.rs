@@ -1,5 +1,7 @@
 pub mod api;
 pub mod db;
 pub mod models;
+pub mod render;
 pub mod schema;
 pub mod server;
+pub mod storage;
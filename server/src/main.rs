@@ -5,5 +5,6 @@ use axtell_server::server;
 #[actix_web::main]
 async fn main() -> io::Result<()> {
     dotenvy::dotenv().ok();
+    env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
     server::serve().await
 }
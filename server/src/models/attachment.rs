@@ -0,0 +1,108 @@
+use crate::models::post::Post;
+use crate::models::user::User;
+use crate::schema::attachments;
+use crate::storage::files;
+use diesel::{Identifiable, Insertable, QueryResult, Queryable, Selectable, prelude::Associations};
+use diesel_async::AsyncPgConnection;
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq, Clone)]
+#[diesel(belongs_to(User))]
+#[diesel(belongs_to(Post))]
+#[diesel(table_name = attachments)]
+pub struct Attachment {
+    pub id: i32,
+    pub user_id: i32,
+    pub post_id: Option<i32>,
+    pub storage_key: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i32,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = attachments)]
+pub struct NewAttachment<'a> {
+    pub user_id: i32,
+    pub storage_key: &'a str,
+    pub filename: &'a str,
+    pub content_type: &'a str,
+    pub size: i32,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+}
+
+impl Attachment {
+    pub async fn find(cnx: &mut AsyncPgConnection, id: i32) -> QueryResult<Self> {
+        use diesel::{QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        attachments::table
+            .find(id)
+            .select(Self::as_select())
+            .first(cnx)
+            .await
+    }
+
+    pub async fn find_by_key(cnx: &mut AsyncPgConnection, key: &str) -> QueryResult<Self> {
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        attachments::table
+            .filter(attachments::dsl::storage_key.eq(key))
+            .select(Self::as_select())
+            .first(cnx)
+            .await
+    }
+
+    /// The files a post refers to, oldest first.
+    pub async fn filter_by_post(
+        cnx: &mut AsyncPgConnection,
+        post_id: i32,
+    ) -> QueryResult<Vec<Self>> {
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        attachments::table
+            .filter(attachments::dsl::post_id.eq(post_id))
+            .select(Self::as_select())
+            .order_by(attachments::dsl::id)
+            .load(cnx)
+            .await
+    }
+
+    pub async fn create(cnx: &mut AsyncPgConnection, new: NewAttachment<'_>) -> QueryResult<Self> {
+        use diesel::SelectableHelper;
+        use diesel_async::RunQueryDsl;
+
+        diesel::insert_into(attachments::table)
+            .values(new)
+            .returning(Self::as_returning())
+            .get_result(cnx)
+            .await
+    }
+
+    /// Links the files `body` refers to that no post has claimed yet to the post.
+    pub async fn link_to_post(
+        cnx: &mut AsyncPgConnection,
+        post_id: i32,
+        body: &str,
+    ) -> QueryResult<()> {
+        use diesel::ExpressionMethods;
+        use diesel_async::RunQueryDsl;
+
+        let keys = files::find_keys(body);
+        if keys.is_empty() {
+            return Ok(());
+        }
+        diesel::update(attachments::table)
+            .filter(attachments::dsl::storage_key.eq_any(keys))
+            .filter(attachments::dsl::post_id.is_null())
+            .set(attachments::dsl::post_id.eq(post_id))
+            .execute(cnx)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use std::str::FromStr;
+
+use crate::models::{InvalidTableNameError, SchemaTable};
+use crate::schema::audit_log;
+use diesel::{Identifiable, Insertable, QueryResult, Queryable, Selectable};
+use diesel_async::AsyncPgConnection;
+use diesel_derive_enum::DbEnum;
+
+#[derive(DbEnum, Debug, PartialEq, Eq, Clone, Copy)]
+#[db_enum(existing_type_path = "crate::schema::sql_types::AuditAction")]
+pub enum AuditAction {
+    LockPost,
+    UnlockPost,
+    /// A moderator's binding vote closed a challenge.
+    CloseChallenge,
+    /// A moderator's binding vote reopened a challenge.
+    ReopenChallenge,
+    /// A moderator reviewed a suggested edit on someone else's post.
+    ReviewSuggestedEdit,
+    ResolveFlag,
+    SuspendUser,
+    UnsuspendUser,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, PartialEq, Clone)]
+#[diesel(table_name = audit_log)]
+pub struct AuditLogEntry {
+    pub id: i32,
+    pub actor_id: i32,
+    pub action: AuditAction,
+    pub target_table: String,
+    pub target_id: i32,
+    pub before_state: Option<serde_json::Value>,
+    pub after_state: Option<serde_json::Value>,
+    pub ip: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// An action to record. `before_state` and `after_state` are snapshots of what the action
+/// changed, left as `None` when it didn't exist before or after the action.
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditLogEntry<'a> {
+    pub actor_id: i32,
+    pub action: AuditAction,
+    pub target_table: &'a str,
+    pub target_id: i32,
+    pub before_state: Option<serde_json::Value>,
+    pub after_state: Option<serde_json::Value>,
+    pub ip: Option<&'a str>,
+}
+
+/// Narrows down the audit log. Unset fields match every entry.
+#[derive(Debug, Default, Clone)]
+pub struct AuditLogFilter {
+    pub actor_id: Option<i32>,
+    pub target: Option<(SchemaTable, i32)>,
+    pub actions: Option<Vec<AuditAction>>,
+}
+
+impl AuditLogEntry {
+    pub fn target(&self) -> Result<(SchemaTable, i32), InvalidTableNameError> {
+        Ok((SchemaTable::from_str(&self.target_table)?, self.target_id))
+    }
+
+    pub async fn find(cnx: &mut AsyncPgConnection, id: i32) -> QueryResult<Self> {
+        use diesel::{QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        audit_log::table
+            .find(id)
+            .select(Self::as_select())
+            .first(cnx)
+            .await
+    }
+
+    /// Entries matching `filter`, oldest first.
+    pub async fn filter(
+        cnx: &mut AsyncPgConnection,
+        filter: &AuditLogFilter,
+        after: Option<i32>,
+        before: Option<i32>,
+        limit: Option<i64>,
+    ) -> QueryResult<Vec<Self>> {
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        let mut query = audit_log::table
+            .filter(audit_log::dsl::id.gt(after.unwrap_or_default()))
+            .filter(audit_log::dsl::id.lt(before.unwrap_or(i32::MAX)))
+            .select(Self::as_select())
+            .order_by(audit_log::dsl::id)
+            .limit(limit.unwrap_or(25))
+            .into_boxed();
+        if let Some(actor_id) = filter.actor_id {
+            query = query.filter(audit_log::dsl::actor_id.eq(actor_id));
+        }
+        if let Some((table, id)) = filter.target {
+            query = query
+                .filter(audit_log::dsl::target_table.eq(table.name()))
+                .filter(audit_log::dsl::target_id.eq(id));
+        }
+        if let Some(actions) = &filter.actions {
+            query = query.filter(audit_log::dsl::action.eq_any(actions.clone()));
+        }
+        query.load(cnx).await
+    }
+
+    /// Appends an entry. Run it in the same transaction as the action it records, so that one
+    /// can't happen without the other.
+    pub async fn record(
+        cnx: &mut AsyncPgConnection,
+        entry: NewAuditLogEntry<'_>,
+    ) -> QueryResult<Self> {
+        use diesel::SelectableHelper;
+        use diesel_async::RunQueryDsl;
+
+        diesel::insert_into(audit_log::table)
+            .values(entry)
+            .returning(Self::as_returning())
+            .get_result(cnx)
+            .await
+    }
+}
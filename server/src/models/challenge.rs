@@ -2,9 +2,24 @@ use crate::models::post::Post;
 use crate::models::{challenge_type::ChallengeType, post::FromPost};
 use crate::schema::challenges;
 use diesel::{
-    AsChangeset, Associations, Identifiable, Insertable, QueryResult, Queryable, Selectable,
+    AsChangeset, Associations, Identifiable, Insertable, QueryResult, Queryable, QueryableByName,
+    Selectable,
 };
 use diesel_async::AsyncPgConnection;
+use diesel_derive_enum::DbEnum;
+use serde::Serialize;
+
+/// Why a challenge was closed.
+#[derive(DbEnum, Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize)]
+#[db_enum(existing_type_path = "crate::schema::sql_types::CloseReason")]
+#[serde(rename_all = "snake_case")]
+pub enum CloseReason {
+    Duplicate,
+    Unclear,
+    NeedsDetails,
+    NoWinningCriterion,
+    OffTopic,
+}
 
 #[derive(
     Queryable,
@@ -16,6 +31,7 @@ use diesel_async::AsyncPgConnection;
     Debug,
     PartialEq,
     Clone,
+    Serialize,
 )]
 #[diesel(belongs_to(Post))]
 #[diesel(belongs_to(ChallengeType))]
@@ -24,6 +40,15 @@ use diesel_async::AsyncPgConnection;
 pub struct ChallengeData {
     pub post_id: i32,
     pub challenge_type_id: i16,
+    pub closed_at: Option<chrono::NaiveDateTime>,
+    pub close_reason: Option<CloseReason>,
+    pub duplicate_of_id: Option<i32>,
+}
+
+impl ChallengeData {
+    pub fn is_closed(&self) -> bool {
+        self.closed_at.is_some()
+    }
 }
 
 #[derive(Queryable, Selectable, Debug, PartialEq, Clone)]
@@ -97,6 +122,56 @@ impl Challenge {
     }
 }
 
+/// An existing challenge resembling some title and body.
+#[derive(QueryableByName, Debug, PartialEq, Clone, Copy)]
+pub struct SimilarChallenge {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub id: i32,
+    /// Trigram similarity of the titles plus the rank of the body's words in the challenge's
+    /// body. Only meaningful relative to other results of the same search.
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub score: f32,
+}
+
+impl SimilarChallenge {
+    /// Finds the challenges most similar to `title` and `body`, best first. Candidates either
+    /// have a similar title (per `pg_trgm.similarity_threshold`) or share words with `body`.
+    pub async fn find(
+        cnx: &mut AsyncPgConnection,
+        title: &str,
+        body: &str,
+        exclude_id: Option<i32>,
+        limit: i64,
+    ) -> QueryResult<Vec<Self>> {
+        use diesel::sql_types::{BigInt, Integer, Nullable, Text};
+        use diesel_async::RunQueryDsl;
+
+        // the weights only count matches in the body, since the title is scored by trigrams
+        diesel::sql_query(
+            "SELECT posts.id,
+                similarity(posts.title, $1)
+                    + ts_rank('{0, 0, 1, 0}', posts.search_vector, words.query) AS score
+            FROM posts
+            INNER JOIN challenges ON challenges.post_id = posts.id
+            CROSS JOIN (
+                SELECT coalesce(string_agg(quote_literal(lexeme), ' | '), '')::tsquery AS query
+                FROM unnest(tsvector_to_array(to_tsvector('english', $2))) AS lexeme
+            ) AS words
+            WHERE posts.deleted_at IS NULL
+                AND ($3::int4 IS NULL OR posts.id <> $3)
+                AND (posts.title % $1 OR posts.search_vector @@ words.query)
+            ORDER BY score DESC, posts.id
+            LIMIT $4",
+        )
+        .bind::<Text, _>(title)
+        .bind::<Text, _>(body)
+        .bind::<Nullable<Integer>, _>(exclude_id)
+        .bind::<BigInt, _>(limit)
+        .load(cnx)
+        .await
+    }
+}
+
 impl FromPost for Challenge {
     async fn from_post(cnx: &mut AsyncPgConnection, post: &Post) -> QueryResult<Self> {
         Self::find(cnx, post.id).await
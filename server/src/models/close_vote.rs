@@ -0,0 +1,249 @@
+use std::{collections::HashMap, fmt::Display, sync::LazyLock};
+
+use crate::models::challenge::{ChallengeData, CloseReason};
+use crate::schema::close_votes;
+use diesel::{Identifiable, Insertable, QueryResult, Queryable, Selectable};
+use diesel_async::AsyncPgConnection;
+use diesel_derive_enum::DbEnum;
+
+/// Pending votes needed to close or reopen a challenge without a moderator. Read from
+/// `CLOSE_VOTE_THRESHOLD`.
+pub static CLOSE_VOTE_THRESHOLD: LazyLock<i64> = LazyLock::new(|| {
+    dotenvy::var("CLOSE_VOTE_THRESHOLD")
+        .map(|v| v.parse().expect("CLOSE_VOTE_THRESHOLD must be an integer"))
+        .unwrap_or(5)
+});
+
+#[derive(DbEnum, Debug, PartialEq, Eq, Clone, Copy)]
+#[db_enum(existing_type_path = "crate::schema::sql_types::CloseVoteKind")]
+pub enum CloseVoteKind {
+    Close,
+    Reopen,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, PartialEq, Clone)]
+#[diesel(table_name = close_votes)]
+pub struct CloseVote {
+    pub id: i32,
+    pub challenge_id: i32,
+    pub voter_id: i32,
+    pub kind: CloseVoteKind,
+    pub reason: Option<CloseReason>,
+    pub duplicate_of_id: Option<i32>,
+    pub binding: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub spent_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = close_votes)]
+struct NewCloseVote {
+    challenge_id: i32,
+    voter_id: i32,
+    kind: CloseVoteKind,
+    reason: Option<CloseReason>,
+    duplicate_of_id: Option<i32>,
+    binding: bool,
+}
+
+/// What a vote asks for.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Ballot {
+    /// `duplicate_of_id` must be set if, and only if, `reason` is [`CloseReason::Duplicate`].
+    Close {
+        reason: CloseReason,
+        duplicate_of_id: Option<i32>,
+    },
+    Reopen,
+}
+
+impl Ballot {
+    pub fn kind(&self) -> CloseVoteKind {
+        match self {
+            Self::Close { .. } => CloseVoteKind::Close,
+            Self::Reopen => CloseVoteKind::Reopen,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CloseVoteError {
+    /// Close votes can only be cast on open challenges, and reopen votes on closed ones.
+    WrongState(CloseVoteKind),
+    AlreadyVoted,
+    /// The challenge named as the original doesn't exist, or is the challenge itself.
+    InvalidDuplicate,
+    Query(diesel::result::Error),
+}
+
+impl Display for CloseVoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongState(CloseVoteKind::Close) => f.write_str("challenge is already closed"),
+            Self::WrongState(CloseVoteKind::Reopen) => f.write_str("challenge is not closed"),
+            Self::AlreadyVoted => f.write_str("you have already voted"),
+            Self::InvalidDuplicate => {
+                f.write_str("a challenge can only be a duplicate of another existing challenge")
+            }
+            Self::Query(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CloseVoteError {}
+
+impl From<diesel::result::Error> for CloseVoteError {
+    fn from(value: diesel::result::Error) -> Self {
+        Self::Query(value)
+    }
+}
+
+impl CloseVote {
+    /// Counts the votes of `kind` that haven't taken effect yet.
+    pub async fn count_pending(
+        cnx: &mut AsyncPgConnection,
+        challenge_id: i32,
+        kind: CloseVoteKind,
+    ) -> QueryResult<i64> {
+        use diesel::{ExpressionMethods, QueryDsl};
+        use diesel_async::RunQueryDsl;
+
+        close_votes::table
+            .filter(close_votes::dsl::challenge_id.eq(challenge_id))
+            .filter(close_votes::dsl::kind.eq(kind))
+            .filter(close_votes::dsl::spent_at.is_null())
+            .count()
+            .get_result(cnx)
+            .await
+    }
+
+    /// Casts a vote. If the vote is `binding`, or brings the pending votes of its kind up to
+    /// `threshold`, the challenge is closed or reopened and all its pending votes are spent. A
+    /// community closure takes the reason most voters chose, ties going to the earliest vote.
+    /// Returns the challenge as it is after the vote.
+    pub async fn cast(
+        cnx: &mut AsyncPgConnection,
+        challenge_id: i32,
+        voter_id: i32,
+        ballot: Ballot,
+        binding: bool,
+        threshold: i64,
+    ) -> Result<ChallengeData, CloseVoteError> {
+        use crate::schema::challenges;
+        use diesel::{
+            ExpressionMethods, NullableExpressionMethods, QueryDsl, SelectableHelper,
+            result::{DatabaseErrorKind, Error as DieselError},
+        };
+        use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+
+        cnx.transaction(|cnx| {
+            async move {
+                let challenge = challenges::table
+                    .find(challenge_id)
+                    .select(ChallengeData::as_select())
+                    .for_update()
+                    .first(cnx)
+                    .await?;
+                let kind = ballot.kind();
+                if challenge.is_closed() != (kind == CloseVoteKind::Reopen) {
+                    return Err(CloseVoteError::WrongState(kind));
+                }
+                let (reason, duplicate_of_id) = match ballot {
+                    Ballot::Close {
+                        reason,
+                        duplicate_of_id,
+                    } => (Some(reason), duplicate_of_id),
+                    Ballot::Reopen => (None, None),
+                };
+                if let Some(original_id) = duplicate_of_id {
+                    let exists = challenges::table
+                        .find(original_id)
+                        .count()
+                        .get_result::<i64>(cnx)
+                        .await?
+                        > 0;
+                    if !exists || original_id == challenge_id {
+                        return Err(CloseVoteError::InvalidDuplicate);
+                    }
+                }
+
+                diesel::insert_into(close_votes::table)
+                    .values(NewCloseVote {
+                        challenge_id,
+                        voter_id,
+                        kind,
+                        reason,
+                        duplicate_of_id,
+                        binding,
+                    })
+                    .execute(cnx)
+                    .await
+                    .map_err(|e| match e {
+                        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                            CloseVoteError::AlreadyVoted
+                        }
+                        e => e.into(),
+                    })?;
+                let pending = close_votes::table
+                    .filter(close_votes::dsl::challenge_id.eq(challenge_id))
+                    .filter(close_votes::dsl::kind.eq(kind))
+                    .filter(close_votes::dsl::spent_at.is_null())
+                    .select(Self::as_select())
+                    .order_by(close_votes::dsl::id)
+                    .load(cnx)
+                    .await?;
+                if !binding && (pending.len() as i64) < threshold {
+                    return Ok(challenge);
+                }
+
+                let (reason, duplicate_of_id) = match kind {
+                    CloseVoteKind::Close if binding => (reason, duplicate_of_id),
+                    CloseVoteKind::Close => {
+                        let reason = plurality(pending.iter().filter_map(|v| v.reason));
+                        let duplicate_of_id = plurality(
+                            pending
+                                .iter()
+                                .filter(|v| v.reason == reason)
+                                .filter_map(|v| v.duplicate_of_id),
+                        );
+                        (reason, duplicate_of_id)
+                    }
+                    CloseVoteKind::Reopen => (None, None),
+                };
+                diesel::update(close_votes::table)
+                    .filter(close_votes::dsl::challenge_id.eq(challenge_id))
+                    .filter(close_votes::dsl::spent_at.is_null())
+                    .set(close_votes::dsl::spent_at.eq(diesel::dsl::now.nullable()))
+                    .execute(cnx)
+                    .await?;
+                let closed_at = match kind {
+                    CloseVoteKind::Close => Some(chrono::Utc::now().naive_utc()),
+                    CloseVoteKind::Reopen => None,
+                };
+                Ok(diesel::update(challenges::table.find(challenge_id))
+                    .set((
+                        challenges::dsl::closed_at.eq(closed_at),
+                        challenges::dsl::close_reason.eq(reason),
+                        challenges::dsl::duplicate_of_id.eq(duplicate_of_id),
+                    ))
+                    .returning(ChallengeData::as_returning())
+                    .get_result(cnx)
+                    .await?)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+}
+
+/// The most common value, ties going to the one seen first.
+fn plurality<T: Eq + std::hash::Hash + Copy>(values: impl Iterator<Item = T>) -> Option<T> {
+    let mut counts: HashMap<T, (usize, usize)> = HashMap::new();
+    for (i, value) in values.enumerate() {
+        counts.entry(value).or_insert((0, i)).0 += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(_, (count, first))| (count, std::cmp::Reverse(first)))
+        .map(|(value, _)| value)
+}
@@ -1,12 +1,25 @@
+use std::sync::LazyLock;
+
+use crate::models::mention;
 use crate::models::post::Post;
 use crate::models::user::User;
-use crate::schema::comments;
+use crate::render::markdown;
+use crate::schema::{comment_votes, comments};
+use diesel::pg::Pg;
 use diesel::{
     AsChangeset, Identifiable, Insertable, QueryResult, Queryable, Selectable,
     prelude::Associations,
 };
 use diesel_async::AsyncPgConnection;
 
+/// How deeply replies can nest: replies to comments at this depth aren't allowed, so 0 disables
+/// replies. Read from `MAX_COMMENT_DEPTH`.
+pub static MAX_COMMENT_DEPTH: LazyLock<i32> = LazyLock::new(|| {
+    dotenvy::var("MAX_COMMENT_DEPTH")
+        .map(|v| v.parse().expect("MAX_COMMENT_DEPTH must be an integer"))
+        .unwrap_or(3)
+});
+
 #[derive(
     Queryable,
     Selectable,
@@ -29,14 +42,36 @@ pub struct Comment {
     pub user_id: i32,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: Option<chrono::NaiveDateTime>,
+    pub parent_comment_id: Option<i32>,
+    /// 0 for top-level comments, one more than the parent's for replies.
+    pub depth: i32,
+    /// How many users have upvoted the comment.
+    pub score: i32,
+    /// `body` rendered as HTML. Missing for comments written before rendered bodies started
+    /// being stored.
+    pub body_html: Option<String>,
+}
+
+/// Orders for listing a post's comments.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum CommentOrder {
+    /// Oldest first.
+    #[default]
+    Created,
+    /// Highest score first, then oldest first.
+    Score,
 }
 
 impl Comment {
     pub async fn find(cnx: &mut AsyncPgConnection, db_id: i32) -> QueryResult<Self> {
-        use diesel::QueryDsl;
+        use diesel::{QueryDsl, SelectableHelper};
         use diesel_async::RunQueryDsl;
 
-        comments::table.find(db_id).first(cnx).await
+        comments::table
+            .find(db_id)
+            .select(Self::as_select())
+            .first(cnx)
+            .await
     }
 
     pub async fn filter_by_user(
@@ -59,47 +94,259 @@ impl Comment {
             .await
     }
 
+    /// Comments on the challenge in the given order. Replies are left out if `top_level_only`
+    /// is set.
+    #[allow(clippy::too_many_arguments)]
     pub async fn filter_by_challenge(
         cnx: &mut AsyncPgConnection,
         challenge_id: i32,
+        top_level_only: bool,
+        order: CommentOrder,
         after: Option<i32>,
         before: Option<i32>,
         limit: Option<i64>,
     ) -> QueryResult<Vec<Self>> {
-        use crate::schema::{challenges, posts};
-        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use crate::schema::challenges;
+        use diesel::{ExpressionMethods, QueryDsl};
+
+        let query = comments::table
+            .filter(
+                comments::dsl::post_id.eq_any(
+                    challenges::table
+                        .filter(challenges::dsl::post_id.eq(challenge_id))
+                        .select(challenges::dsl::post_id),
+                ),
+            )
+            .into_boxed();
+        Self::filter_by_post(cnx, query, top_level_only, order, after, before, limit).await
+    }
+
+    /// Comments on the response in the given order. Replies are left out if `top_level_only` is
+    /// set.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn filter_by_response(
+        cnx: &mut AsyncPgConnection,
+        response_id: i32,
+        top_level_only: bool,
+        order: CommentOrder,
+        after: Option<i32>,
+        before: Option<i32>,
+        limit: Option<i64>,
+    ) -> QueryResult<Vec<Self>> {
+        use crate::schema::responses;
+        use diesel::{ExpressionMethods, QueryDsl};
+
+        let query = comments::table
+            .filter(
+                comments::dsl::post_id.eq_any(
+                    responses::table
+                        .filter(responses::dsl::post_id.eq(response_id))
+                        .select(responses::dsl::post_id),
+                ),
+            )
+            .into_boxed();
+        Self::filter_by_post(cnx, query, top_level_only, order, after, before, limit).await
+    }
+
+    /// Orders the comments `query` matches and pages through them. The `after` and `before`
+    /// cursors are comment ids; when ordering by score, a comment's place is given by its current
+    /// score, ties going to the older comment.
+    #[allow(clippy::too_many_arguments)]
+    async fn filter_by_post(
+        cnx: &mut AsyncPgConnection,
+        mut query: comments::BoxedQuery<'_, Pg>,
+        top_level_only: bool,
+        order: CommentOrder,
+        after: Option<i32>,
+        before: Option<i32>,
+        limit: Option<i64>,
+    ) -> QueryResult<Vec<Self>> {
+        use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, SelectableHelper};
         use diesel_async::RunQueryDsl;
 
-        comments::table
-            .inner_join(posts::table.inner_join(challenges::table))
-            .filter(comments::dsl::post_id.eq(challenge_id))
-            .filter(comments::dsl::id.gt(after.unwrap_or_default()))
-            .filter(comments::dsl::id.lt(before.unwrap_or(i32::MAX)))
+        if top_level_only {
+            query = query.filter(comments::dsl::parent_comment_id.is_null());
+        }
+        query = match order {
+            CommentOrder::Created => query
+                .filter(comments::dsl::id.gt(after.unwrap_or_default()))
+                .filter(comments::dsl::id.lt(before.unwrap_or(i32::MAX)))
+                .order_by(comments::dsl::id),
+            CommentOrder::Score => {
+                if let Some(after) = after {
+                    let score = Self::find(cnx, after).await?.score;
+                    query = query.filter(
+                        comments::dsl::score.lt(score).or(comments::dsl::score
+                            .eq(score)
+                            .and(comments::dsl::id.gt(after))),
+                    );
+                }
+                if let Some(before) = before {
+                    let score = Self::find(cnx, before).await?.score;
+                    query = query.filter(
+                        comments::dsl::score.gt(score).or(comments::dsl::score
+                            .eq(score)
+                            .and(comments::dsl::id.lt(before))),
+                    );
+                }
+                query.order_by((comments::dsl::score.desc(), comments::dsl::id))
+            }
+        };
+        query
             .select(Self::as_select())
             .limit(limit.unwrap_or(25))
             .load(cnx)
             .await
     }
 
-    pub async fn filter_by_response(
+    /// The `count` highest-scoring comments on a post, oldest first, and how many others there
+    /// are. Ties go to the older comment. Replies are left out if `top_level_only` is set.
+    pub async fn top_by_post(
         cnx: &mut AsyncPgConnection,
-        response_id: i32,
+        post_id: i32,
+        top_level_only: bool,
+        count: i64,
+    ) -> QueryResult<(Vec<Self>, i64)> {
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        let filtered = || {
+            let mut query = comments::table
+                .filter(comments::dsl::post_id.eq(post_id))
+                .into_boxed();
+            if top_level_only {
+                query = query.filter(comments::dsl::parent_comment_id.is_null());
+            }
+            query
+        };
+        let mut top = filtered()
+            .select(Self::as_select())
+            .order_by((comments::dsl::score.desc(), comments::dsl::id))
+            .limit(count)
+            .load::<Self>(cnx)
+            .await?;
+        top.sort_by_key(|c| c.id);
+        let total: i64 = filtered().count().get_result(cnx).await?;
+        Ok((top, total))
+    }
+
+    /// Direct replies to a comment, oldest first.
+    pub async fn filter_by_parent(
+        cnx: &mut AsyncPgConnection,
+        parent_id: i32,
         after: Option<i32>,
         before: Option<i32>,
         limit: Option<i64>,
     ) -> QueryResult<Vec<Self>> {
-        use crate::schema::{posts, responses};
         use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
         use diesel_async::RunQueryDsl;
 
         comments::table
-            .inner_join(posts::table.inner_join(responses::table))
-            .filter(comments::dsl::post_id.eq(response_id))
+            .filter(comments::dsl::parent_comment_id.eq(parent_id))
             .filter(comments::dsl::id.gt(after.unwrap_or_default()))
             .filter(comments::dsl::id.lt(before.unwrap_or(i32::MAX)))
             .select(Self::as_select())
+            .order_by(comments::dsl::id)
             .limit(limit.unwrap_or(25))
             .load(cnx)
             .await
     }
+
+    /// Upvotes the comment as the user. Returns whether the user hadn't already.
+    pub async fn upvote(
+        cnx: &mut AsyncPgConnection,
+        comment_id: i32,
+        user_id: i32,
+    ) -> QueryResult<bool> {
+        use diesel::ExpressionMethods;
+        use diesel_async::RunQueryDsl;
+
+        let inserted = diesel::insert_into(comment_votes::table)
+            .values((
+                comment_votes::dsl::user_id.eq(user_id),
+                comment_votes::dsl::comment_id.eq(comment_id),
+            ))
+            .on_conflict_do_nothing()
+            .execute(cnx)
+            .await?;
+        Ok(inserted > 0)
+    }
+
+    /// Takes back the user's upvote on the comment. Returns whether there was one.
+    pub async fn remove_upvote(
+        cnx: &mut AsyncPgConnection,
+        comment_id: i32,
+        user_id: i32,
+    ) -> QueryResult<bool> {
+        use diesel::QueryDsl;
+        use diesel_async::RunQueryDsl;
+
+        let deleted = diesel::delete(comment_votes::table.find((user_id, comment_id)))
+            .execute(cnx)
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    /// Whether the user has upvoted the comment.
+    pub async fn has_upvoted(
+        cnx: &mut AsyncPgConnection,
+        comment_id: i32,
+        user_id: i32,
+    ) -> QueryResult<bool> {
+        use diesel::QueryDsl;
+        use diesel_async::RunQueryDsl;
+
+        diesel::select(diesel::dsl::exists(
+            comment_votes::table.find((user_id, comment_id)),
+        ))
+        .get_result(cnx)
+        .await
+    }
+
+    /// The body rendered as HTML.
+    pub fn render_body(&self) -> String {
+        self.body_html
+            .clone()
+            .unwrap_or_else(|| markdown::render_comment(&self.body))
+    }
+
+    /// Whether a reply to this comment would stay within `MAX_COMMENT_DEPTH`.
+    pub fn accepts_replies(&self) -> bool {
+        self.depth < *MAX_COMMENT_DEPTH
+    }
+
+    /// Creates a comment, as a reply to `parent` if given, and records who it mentions. The
+    /// parent is expected to be on the same post.
+    pub async fn create(
+        cnx: &mut AsyncPgConnection,
+        post_id: i32,
+        user_id: i32,
+        parent: Option<&Comment>,
+        body: &str,
+    ) -> QueryResult<Self> {
+        use diesel::{ExpressionMethods, SelectableHelper};
+        use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+
+        cnx.transaction(|cnx| {
+            async move {
+                let comment = diesel::insert_into(comments::table)
+                    .values((
+                        comments::dsl::post_id.eq(post_id),
+                        comments::dsl::user_id.eq(user_id),
+                        comments::dsl::body.eq(body),
+                        comments::dsl::body_html.eq(markdown::render_comment(body)),
+                        comments::dsl::created_at.eq(diesel::dsl::now),
+                        comments::dsl::parent_comment_id.eq(parent.map(|p| p.id)),
+                        comments::dsl::depth.eq(parent.map_or(0, |p| p.depth + 1)),
+                    ))
+                    .returning(Self::as_returning())
+                    .get_result(cnx)
+                    .await?;
+                mention::record_comment_mentions(cnx, &comment).await?;
+                Ok(comment)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
 }
@@ -0,0 +1,197 @@
+use crate::schema::flags;
+use diesel::{Identifiable, Insertable, QueryResult, Queryable, Selectable};
+use diesel_async::AsyncPgConnection;
+use diesel_derive_enum::DbEnum;
+use serde::Serialize;
+
+#[derive(DbEnum, Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[db_enum(existing_type_path = "crate::schema::sql_types::FlagReason")]
+#[serde(rename_all = "snake_case")]
+pub enum FlagReason {
+    Spam,
+    Offensive,
+    LowQuality,
+    NeedsModerator,
+    Other,
+}
+
+#[derive(DbEnum, Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[db_enum(existing_type_path = "crate::schema::sql_types::FlagOutcome")]
+#[serde(rename_all = "snake_case")]
+pub enum FlagOutcome {
+    /// The flag pointed out a real problem, which was dealt with.
+    Helpful,
+    /// Nothing needed to be done.
+    Declined,
+    /// Moderators couldn't agree whether the flag was valid.
+    Disputed,
+}
+
+/// What a flag was raised against.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FlagTarget {
+    Post(i32),
+    Comment(i32),
+    User(i32),
+}
+
+/// The kinds of [`FlagTarget`], for filtering.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FlagTargetKind {
+    Post,
+    Comment,
+    User,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, PartialEq, Clone, Serialize)]
+#[diesel(table_name = flags)]
+pub struct Flag {
+    pub id: i32,
+    pub flagger_id: i32,
+    pub post_id: Option<i32>,
+    pub comment_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub reason: FlagReason,
+    pub details: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub handler_id: Option<i32>,
+    pub outcome: Option<FlagOutcome>,
+    pub resolution_note: Option<String>,
+    pub resolved_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = flags)]
+struct NewFlag<'a> {
+    flagger_id: i32,
+    post_id: Option<i32>,
+    comment_id: Option<i32>,
+    user_id: Option<i32>,
+    reason: FlagReason,
+    details: Option<&'a str>,
+}
+
+/// Narrows down the flag queue. Unset fields match every flag.
+#[derive(Debug, Default, Clone)]
+pub struct FlagFilter {
+    pub reasons: Option<Vec<FlagReason>>,
+    pub target_kind: Option<FlagTargetKind>,
+    pub resolved: Option<bool>,
+}
+
+impl Flag {
+    pub fn target(&self) -> FlagTarget {
+        match (self.post_id, self.comment_id, self.user_id) {
+            (Some(id), _, _) => FlagTarget::Post(id),
+            (_, Some(id), _) => FlagTarget::Comment(id),
+            (_, _, Some(id)) => FlagTarget::User(id),
+            // ruled out by a check constraint
+            (None, None, None) => unreachable!("flag {} has no target", self.id),
+        }
+    }
+
+    pub async fn find(cnx: &mut AsyncPgConnection, id: i32) -> QueryResult<Self> {
+        use diesel::{QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        flags::table
+            .find(id)
+            .select(Self::as_select())
+            .first(cnx)
+            .await
+    }
+
+    /// Flags matching `filter`, oldest first.
+    pub async fn queue(
+        cnx: &mut AsyncPgConnection,
+        filter: &FlagFilter,
+        after: Option<i32>,
+        before: Option<i32>,
+        limit: Option<i64>,
+    ) -> QueryResult<Vec<Self>> {
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        let mut query = flags::table
+            .filter(flags::dsl::id.gt(after.unwrap_or_default()))
+            .filter(flags::dsl::id.lt(before.unwrap_or(i32::MAX)))
+            .select(Self::as_select())
+            .order_by(flags::dsl::id)
+            .limit(limit.unwrap_or(25))
+            .into_boxed();
+        if let Some(reasons) = &filter.reasons {
+            query = query.filter(flags::dsl::reason.eq_any(reasons.clone()));
+        }
+        query = match filter.target_kind {
+            Some(FlagTargetKind::Post) => query.filter(flags::dsl::post_id.is_not_null()),
+            Some(FlagTargetKind::Comment) => query.filter(flags::dsl::comment_id.is_not_null()),
+            Some(FlagTargetKind::User) => query.filter(flags::dsl::user_id.is_not_null()),
+            None => query,
+        };
+        query = match filter.resolved {
+            Some(true) => query.filter(flags::dsl::outcome.is_not_null()),
+            Some(false) => query.filter(flags::dsl::outcome.is_null()),
+            None => query,
+        };
+        query.load(cnx).await
+    }
+
+    /// Raises a flag. Fails with a unique violation if the flagger already has an open flag on
+    /// the target.
+    pub async fn create(
+        cnx: &mut AsyncPgConnection,
+        flagger_id: i32,
+        target: FlagTarget,
+        reason: FlagReason,
+        details: Option<&str>,
+    ) -> QueryResult<Self> {
+        use diesel::SelectableHelper;
+        use diesel_async::RunQueryDsl;
+
+        let (post_id, comment_id, user_id) = match target {
+            FlagTarget::Post(id) => (Some(id), None, None),
+            FlagTarget::Comment(id) => (None, Some(id), None),
+            FlagTarget::User(id) => (None, None, Some(id)),
+        };
+        diesel::insert_into(flags::table)
+            .values(NewFlag {
+                flagger_id,
+                post_id,
+                comment_id,
+                user_id,
+                reason,
+                details,
+            })
+            .returning(Self::as_returning())
+            .get_result(cnx)
+            .await
+    }
+
+    /// Records how a moderator handled an open flag. Returns `None` if the flag had already
+    /// been resolved.
+    pub async fn resolve(
+        cnx: &mut AsyncPgConnection,
+        id: i32,
+        handler_id: i32,
+        outcome: FlagOutcome,
+        note: Option<&str>,
+    ) -> QueryResult<Option<Self>> {
+        use diesel::{
+            ExpressionMethods, NullableExpressionMethods, OptionalExtension, QueryDsl,
+            SelectableHelper,
+        };
+        use diesel_async::RunQueryDsl;
+
+        diesel::update(flags::table.find(id).filter(flags::dsl::outcome.is_null()))
+            .set((
+                flags::dsl::handler_id.eq(handler_id),
+                flags::dsl::outcome.eq(outcome),
+                flags::dsl::resolution_note.eq(note),
+                flags::dsl::resolved_at.eq(diesel::dsl::now.nullable()),
+            ))
+            .returning(Self::as_returning())
+            .get_result(cnx)
+            .await
+            .optional()
+    }
+}
@@ -0,0 +1,205 @@
+use crate::models::comment::Comment;
+use crate::models::user::User;
+use crate::schema::{mentions, users};
+use diesel::{QueryResult, define_sql_function, sql_types::Text};
+use diesel_async::AsyncPgConnection;
+
+define_sql_function! {
+    fn lower(x: Text) -> Text;
+}
+
+/// The names `@`-mentioned in `text`, without duplicates, in order of first appearance. A mention
+/// is an `@` that doesn't follow a word character, so email addresses don't count, followed by
+/// letters, digits, `_`, `-` or `.`. A trailing `.` is taken as punctuation.
+pub fn parse_mentions(text: &str) -> Vec<&str> {
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+    let mut names: Vec<&str> = Vec::new();
+    let mut prev = None;
+    for (i, c) in text.char_indices() {
+        if c == '@' && !prev.is_some_and(|p: char| p.is_alphanumeric() || p == '_') {
+            let rest = &text[i + 1..];
+            let end = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+            let name = rest[..end].trim_end_matches('.');
+            if !name.is_empty() && !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                names.push(name);
+            }
+        }
+        prev = Some(c);
+    }
+    names
+}
+
+/// The users `@`-mentioned in `text`, matching names case-insensitively. Deleted users and names
+/// that don't belong to anyone are left out.
+pub async fn find_mentioned_users(
+    cnx: &mut AsyncPgConnection,
+    text: &str,
+) -> QueryResult<Vec<User>> {
+    use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+    use diesel_async::RunQueryDsl;
+
+    let names: Vec<String> = parse_mentions(text)
+        .into_iter()
+        .map(str::to_lowercase)
+        .collect();
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+    users::table
+        .filter(lower(users::dsl::name).eq_any(names))
+        .filter(users::dsl::deleted_at.is_null())
+        .select(User::as_select())
+        .order_by(users::dsl::id)
+        .load(cnx)
+        .await
+}
+
+/// The users taking part in a post: its author, anyone who has edited it and anyone who has
+/// commented on it. Deleted users are left out.
+pub async fn find_participants(
+    cnx: &mut AsyncPgConnection,
+    post_id: i32,
+) -> QueryResult<Vec<User>> {
+    use crate::schema::{comments, post_revisions, posts};
+    use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, SelectableHelper};
+    use diesel_async::RunQueryDsl;
+
+    users::table
+        .filter(
+            users::dsl::id
+                .eq_any(
+                    posts::table
+                        .filter(posts::dsl::id.eq(post_id))
+                        .select(posts::dsl::user_id),
+                )
+                .or(users::dsl::id.eq_any(
+                    post_revisions::table
+                        .filter(post_revisions::dsl::post_id.eq(post_id))
+                        .select(post_revisions::dsl::editor_id),
+                ))
+                .or(users::dsl::id.eq_any(
+                    comments::table
+                        .filter(comments::dsl::post_id.eq(post_id))
+                        .select(comments::dsl::user_id),
+                )),
+        )
+        .filter(users::dsl::deleted_at.is_null())
+        .select(User::as_select())
+        .order_by(users::dsl::id)
+        .load(cnx)
+        .await
+}
+
+/// Participants in the post whose names start with `prefix`, ignoring case, for completing a
+/// mention in a comment.
+pub async fn suggest(
+    cnx: &mut AsyncPgConnection,
+    post_id: i32,
+    prefix: &str,
+    limit: usize,
+) -> QueryResult<Vec<User>> {
+    let prefix = prefix.to_lowercase();
+    let mut users: Vec<User> = find_participants(cnx, post_id)
+        .await?
+        .into_iter()
+        .filter(|u| u.name.to_lowercase().starts_with(&prefix))
+        .collect();
+    users.sort_by_cached_key(|u| u.name.to_lowercase());
+    users.truncate(limit);
+    Ok(users)
+}
+
+/// Replaces the mentions recorded for a post with the users its `body` mentions. Returns the ids
+/// of the users who weren't mentioned before.
+pub async fn record_post_mentions(
+    cnx: &mut AsyncPgConnection,
+    post_id: i32,
+    body: &str,
+) -> QueryResult<Vec<i32>> {
+    use diesel::ExpressionMethods;
+    use diesel_async::RunQueryDsl;
+
+    let user_ids: Vec<i32> = find_mentioned_users(cnx, body)
+        .await?
+        .into_iter()
+        .map(|u| u.id)
+        .collect();
+    diesel::delete(mentions::table)
+        .filter(mentions::dsl::post_id.eq(post_id))
+        .filter(mentions::dsl::user_id.ne_all(&user_ids))
+        .execute(cnx)
+        .await?;
+    let rows: Vec<_> = user_ids
+        .into_iter()
+        .map(|user_id| {
+            (
+                mentions::dsl::user_id.eq(user_id),
+                mentions::dsl::post_id.eq(post_id),
+            )
+        })
+        .collect();
+    diesel::insert_into(mentions::table)
+        .values(rows)
+        .on_conflict_do_nothing()
+        .returning(mentions::dsl::user_id)
+        .get_results(cnx)
+        .await
+}
+
+/// Records the users a new comment mentions. Any user can be mentioned; only autocompletion
+/// (see [`suggest`]) is limited to the post's participants.
+pub async fn record_comment_mentions(
+    cnx: &mut AsyncPgConnection,
+    comment: &Comment,
+) -> QueryResult<()> {
+    use diesel::ExpressionMethods;
+    use diesel_async::RunQueryDsl;
+
+    let rows: Vec<_> = find_mentioned_users(cnx, &comment.body)
+        .await?
+        .into_iter()
+        .map(|u| {
+            (
+                mentions::dsl::user_id.eq(u.id),
+                mentions::dsl::comment_id.eq(comment.id),
+            )
+        })
+        .collect();
+    diesel::insert_into(mentions::table)
+        .values(rows)
+        .on_conflict_do_nothing()
+        .execute(cnx)
+        .await?;
+    Ok(())
+}
+
+/// The users mentioned in a post's body.
+pub async fn filter_by_post(cnx: &mut AsyncPgConnection, post_id: i32) -> QueryResult<Vec<User>> {
+    use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+    use diesel_async::RunQueryDsl;
+
+    mentions::table
+        .inner_join(users::table)
+        .filter(mentions::dsl::post_id.eq(post_id))
+        .select(User::as_select())
+        .order_by(mentions::dsl::id)
+        .load(cnx)
+        .await
+}
+
+/// The users mentioned in a comment.
+pub async fn filter_by_comment(
+    cnx: &mut AsyncPgConnection,
+    comment_id: i32,
+) -> QueryResult<Vec<User>> {
+    use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+    use diesel_async::RunQueryDsl;
+
+    mentions::table
+        .inner_join(users::table)
+        .filter(mentions::dsl::comment_id.eq(comment_id))
+        .select(User::as_select())
+        .order_by(mentions::dsl::id)
+        .load(cnx)
+        .await
+}
@@ -1,29 +1,67 @@
+pub mod attachment;
+pub mod audit_log;
 pub mod challenge;
 pub mod challenge_type;
+pub mod close_vote;
 pub mod comment;
+pub mod flag;
+pub mod mention;
+pub mod notification;
 pub mod post;
+pub mod post_lock;
+pub mod post_revision;
 pub mod response;
+pub mod search;
+pub mod suggested_edit;
 pub mod user;
+pub mod user_suspension;
 
 use std::{fmt::Display, str::FromStr};
 
+pub use attachment::Attachment;
+pub use audit_log::AuditLogEntry;
 pub use challenge::Challenge;
 pub use challenge_type::ChallengeType;
 pub use comment::Comment;
-use diesel::QueryResult;
 use diesel_async::AsyncPgConnection;
+pub use flag::Flag;
+pub use notification::Notification;
 pub use post::Post;
+use post::PostKind;
+pub use post_lock::PostLock;
+pub use post_revision::PostRevision;
 pub use response::Response;
+pub use suggested_edit::SuggestedEdit;
 pub use user::User;
+pub use user_suspension::UserSuspension;
 
 use crate::schema;
 
 pub enum SchemaModel {
+    Attachment(Attachment),
+    AuditLogEntry(AuditLogEntry),
     Challenge(Challenge),
     Comment(Comment),
     ChallengeType(ChallengeType),
+    Flag(Flag),
+    Notification(Notification),
+    PostRevision(PostRevision),
     Response(Response),
+    SuggestedEdit(SuggestedEdit),
     User(User),
+    UserSuspension(UserSuspension),
+}
+
+impl From<Attachment> for SchemaModel {
+    fn from(value: Attachment) -> Self {
+        Self::Attachment(value)
+    }
+}
+
+impl From<AuditLogEntry> for SchemaModel {
+    fn from(value: AuditLogEntry) -> Self {
+        Self::AuditLogEntry(value)
+    }
 }
 
 impl From<Challenge> for SchemaModel {
@@ -44,49 +82,199 @@ impl From<ChallengeType> for SchemaModel {
     }
 }
 
+impl From<Flag> for SchemaModel {
+    fn from(value: Flag) -> Self {
+        Self::Flag(value)
+    }
+}
+
+impl From<Notification> for SchemaModel {
+    fn from(value: Notification) -> Self {
+        Self::Notification(value)
+    }
+}
+
+impl From<PostRevision> for SchemaModel {
+    fn from(value: PostRevision) -> Self {
+        Self::PostRevision(value)
+    }
+}
+
 impl From<Response> for SchemaModel {
     fn from(value: Response) -> Self {
         Self::Response(value)
     }
 }
 
+impl From<SuggestedEdit> for SchemaModel {
+    fn from(value: SuggestedEdit) -> Self {
+        Self::SuggestedEdit(value)
+    }
+}
+
 impl From<User> for SchemaModel {
     fn from(value: User) -> Self {
         Self::User(value)
     }
 }
 
+impl From<UserSuspension> for SchemaModel {
+    fn from(value: UserSuspension) -> Self {
+        Self::UserSuspension(value)
+    }
+}
+
+/// Every table whose rows are exposed as `Node`s. Each variant's name is the
+/// table prefix encoded into `NodeId`s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SchemaTable {
+    Attachments,
+    AuditLog,
     Challenges,
-    Comments(schema::comments::table),
-    ChallengeTypes(schema::challenge_types::table),
+    Comments,
+    ChallengeTypes,
+    Flags,
+    Notifications,
+    Posts,
+    PostRevisions,
     Responses,
-    Users(schema::users::table),
+    SuggestedEdits,
+    Users,
+    UserSuspensions,
 }
 
 impl SchemaTable {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Attachments => "attachments",
+            Self::AuditLog => "audit_log",
+            Self::Challenges => "challenges",
+            Self::Comments => "comments",
+            Self::ChallengeTypes => "challenge_types",
+            Self::Flags => "flags",
+            Self::Notifications => "notifications",
+            Self::Posts => "posts",
+            Self::PostRevisions => "post_revisions",
+            Self::Responses => "responses",
+            Self::SuggestedEdits => "suggested_edits",
+            Self::Users => "users",
+            Self::UserSuspensions => "user_suspensions",
+        }
+    }
+
+    /// Looks up the row with the given primary key. Returns `Ok(None)` if no
+    /// such row exists; any other database error is passed through.
     pub async fn find(
         &self,
         primary_key: i32,
         cnx: &mut AsyncPgConnection,
-    ) -> QueryResult<SchemaModel> {
-        use diesel::QueryDsl;
+    ) -> anyhow::Result<Option<SchemaModel>> {
+        use diesel::{OptionalExtension, QueryDsl};
         use diesel_async::RunQueryDsl;
 
         Ok(match self {
-            Self::Challenges => Challenge::find(cnx, primary_key).await?.into(),
-            Self::Responses => Response::find(cnx, primary_key).await?.into(),
-            Self::Comments(t) => t.find(primary_key).first::<Comment>(cnx).await?.into(),
-            Self::ChallengeTypes(t) => t
-                .find(primary_key as i16)
-                .first::<ChallengeType>(cnx)
-                .await?
-                .into(),
-            Self::Users(t) => t.find(primary_key).first::<User>(cnx).await?.into(),
+            Self::Attachments => Attachment::find(cnx, primary_key)
+                .await
+                .optional()?
+                .map(Into::into),
+            Self::AuditLog => AuditLogEntry::find(cnx, primary_key)
+                .await
+                .optional()?
+                .map(Into::into),
+            Self::Challenges => Challenge::find(cnx, primary_key)
+                .await
+                .optional()?
+                .map(Into::into),
+            Self::Responses => Response::find(cnx, primary_key)
+                .await
+                .optional()?
+                .map(Into::into),
+            Self::Posts => match PostKind::find_by_ids(cnx, &[primary_key]).await?.pop() {
+                Some((_, PostKind::Challenge)) => Challenge::find(cnx, primary_key)
+                    .await
+                    .optional()?
+                    .map(Into::into),
+                Some((_, PostKind::Response)) => Response::find(cnx, primary_key)
+                    .await
+                    .optional()?
+                    .map(Into::into),
+                None => match Post::find(cnx, primary_key).await.optional()? {
+                    Some(post) => return Err(UnknownPostKindError(post.id).into()),
+                    None => None,
+                },
+            },
+            Self::PostRevisions => PostRevision::find(cnx, primary_key)
+                .await
+                .optional()?
+                .map(Into::into),
+            Self::SuggestedEdits => SuggestedEdit::find(cnx, primary_key)
+                .await
+                .optional()?
+                .map(Into::into),
+            Self::Flags => Flag::find(cnx, primary_key)
+                .await
+                .optional()?
+                .map(Into::into),
+            Self::Notifications => Notification::find(cnx, primary_key)
+                .await
+                .optional()?
+                .map(Into::into),
+            Self::Comments => Comment::find(cnx, primary_key)
+                .await
+                .optional()?
+                .map(Into::into),
+            Self::ChallengeTypes => {
+                let primary_key = i16::try_from(primary_key)
+                    .map_err(|_| InvalidPrimaryKeyError(*self, primary_key))?;
+                schema::challenge_types::table
+                    .find(primary_key)
+                    .first::<ChallengeType>(cnx)
+                    .await
+                    .optional()?
+                    .map(Into::into)
+            }
+            Self::Users => schema::users::table
+                .find(primary_key)
+                .first::<User>(cnx)
+                .await
+                .optional()?
+                .map(Into::into),
+            Self::UserSuspensions => UserSuspension::find(cnx, primary_key)
+                .await
+                .optional()?
+                .map(Into::into),
         })
     }
 }
 
+impl Display for SchemaTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidPrimaryKeyError(SchemaTable, i32);
+
+impl Display for InvalidPrimaryKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "primary key out of range for {}: {}", self.0, self.1)
+    }
+}
+
+impl std::error::Error for InvalidPrimaryKeyError {}
+
+#[derive(Debug)]
+pub struct UnknownPostKindError(i32);
+
+impl Display for UnknownPostKindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "post is neither a challenge nor a response: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownPostKindError {}
+
 #[derive(Debug)]
 pub struct InvalidTableNameError(String);
 
@@ -103,11 +291,19 @@ impl FromStr for SchemaTable {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
+            "attachments" => Ok(Self::Attachments),
+            "audit_log" => Ok(Self::AuditLog),
             "challenges" => Ok(Self::Challenges),
             "responses" => Ok(Self::Responses),
-            "challenge_types" => Ok(Self::ChallengeTypes(schema::challenge_types::table)),
-            "comments" => Ok(Self::Comments(schema::comments::table)),
-            "users" => Ok(Self::Users(schema::users::table)),
+            "challenge_types" => Ok(Self::ChallengeTypes),
+            "comments" => Ok(Self::Comments),
+            "flags" => Ok(Self::Flags),
+            "notifications" => Ok(Self::Notifications),
+            "posts" => Ok(Self::Posts),
+            "post_revisions" => Ok(Self::PostRevisions),
+            "suggested_edits" => Ok(Self::SuggestedEdits),
+            "users" => Ok(Self::Users),
+            "user_suspensions" => Ok(Self::UserSuspensions),
             _ => Err(InvalidTableNameError(s.to_owned())),
         }
     }
@@ -0,0 +1,284 @@
+use crate::models::{comment::Comment, mention, response::Response, user::User};
+use crate::schema::notifications;
+use diesel::{Identifiable, Insertable, QueryResult, Queryable, Selectable, prelude::Associations};
+use diesel_async::AsyncPgConnection;
+use diesel_derive_enum::DbEnum;
+
+#[derive(DbEnum, Debug, PartialEq, Eq, Clone, Copy)]
+#[db_enum(existing_type_path = "crate::schema::sql_types::NotificationKind")]
+pub enum NotificationKind {
+    /// Someone responded to the user's challenge.
+    Response,
+    /// Someone commented on the user's post.
+    Comment,
+    /// Someone `@`-mentioned the user.
+    Mention,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq, Clone)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = notifications)]
+pub struct Notification {
+    pub id: i32,
+    pub user_id: i32,
+    pub kind: NotificationKind,
+    pub post_id: i32,
+    pub actor_id: i32,
+    pub response_id: Option<i32>,
+    pub comment_id: Option<i32>,
+    pub event_count: i32,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+    pub read_at: Option<chrono::NaiveDateTime>,
+}
+
+/// An event to tell a user about.
+#[derive(Insertable, Debug, Clone, Copy)]
+#[diesel(table_name = notifications)]
+pub struct NewNotification {
+    pub user_id: i32,
+    pub kind: NotificationKind,
+    pub post_id: i32,
+    pub actor_id: i32,
+    pub response_id: Option<i32>,
+    pub comment_id: Option<i32>,
+}
+
+impl Notification {
+    pub async fn find(cnx: &mut AsyncPgConnection, id: i32) -> QueryResult<Self> {
+        use diesel::{QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        notifications::table
+            .find(id)
+            .select(Self::as_select())
+            .first(cnx)
+            .await
+    }
+
+    /// The user's notifications, most recently updated first, so `after` is an older
+    /// notification's id and `before` a newer one's. A notification moves back to the top when
+    /// another event is batched into it.
+    pub async fn filter_by_user(
+        cnx: &mut AsyncPgConnection,
+        user_id: i32,
+        unread_only: bool,
+        after: Option<i32>,
+        before: Option<i32>,
+        limit: Option<i64>,
+    ) -> QueryResult<Vec<Self>> {
+        use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        let mut query = notifications::table
+            .filter(notifications::dsl::user_id.eq(user_id))
+            .select(Self::as_select())
+            .order_by((
+                notifications::dsl::updated_at.desc(),
+                notifications::dsl::id.desc(),
+            ))
+            .limit(limit.unwrap_or(25))
+            .into_boxed();
+        if unread_only {
+            query = query.filter(notifications::dsl::read_at.is_null());
+        }
+        if let Some(after) = after {
+            let Some(updated_at) = Self::updated_at(cnx, user_id, after).await? else {
+                return Ok(Vec::new());
+            };
+            query = query.filter(
+                notifications::dsl::updated_at
+                    .lt(updated_at)
+                    .or(notifications::dsl::updated_at
+                        .eq(updated_at)
+                        .and(notifications::dsl::id.lt(after))),
+            );
+        }
+        if let Some(before) = before {
+            let Some(updated_at) = Self::updated_at(cnx, user_id, before).await? else {
+                return Ok(Vec::new());
+            };
+            query = query.filter(
+                notifications::dsl::updated_at
+                    .gt(updated_at)
+                    .or(notifications::dsl::updated_at
+                        .eq(updated_at)
+                        .and(notifications::dsl::id.gt(before))),
+            );
+        }
+        query.load(cnx).await
+    }
+
+    /// When one of the user's notifications last had an event, to page past it. `None` if the
+    /// notification isn't the user's.
+    async fn updated_at(
+        cnx: &mut AsyncPgConnection,
+        user_id: i32,
+        id: i32,
+    ) -> QueryResult<Option<chrono::NaiveDateTime>> {
+        use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+        use diesel_async::RunQueryDsl;
+
+        notifications::table
+            .find(id)
+            .filter(notifications::dsl::user_id.eq(user_id))
+            .select(notifications::dsl::updated_at)
+            .first(cnx)
+            .await
+            .optional()
+    }
+
+    pub async fn count_unread(cnx: &mut AsyncPgConnection, user_id: i32) -> QueryResult<i64> {
+        use diesel::{ExpressionMethods, QueryDsl};
+        use diesel_async::RunQueryDsl;
+
+        notifications::table
+            .filter(notifications::dsl::user_id.eq(user_id))
+            .filter(notifications::dsl::read_at.is_null())
+            .count()
+            .get_result(cnx)
+            .await
+    }
+
+    /// Tells users about events. Each event is batched into the recipient's unread notification
+    /// of the same kind for the same post if there is one, which then points at the latest event.
+    pub async fn notify(
+        cnx: &mut AsyncPgConnection,
+        events: &[NewNotification],
+    ) -> QueryResult<()> {
+        use diesel::{ExpressionMethods, upsert::DecoratableTarget, upsert::excluded};
+        use diesel_async::RunQueryDsl;
+
+        for event in events {
+            diesel::insert_into(notifications::table)
+                .values(event)
+                .on_conflict((
+                    notifications::dsl::user_id,
+                    notifications::dsl::kind,
+                    notifications::dsl::post_id,
+                ))
+                .filter_target(notifications::dsl::read_at.is_null())
+                .do_update()
+                .set((
+                    notifications::dsl::actor_id.eq(excluded(notifications::dsl::actor_id)),
+                    notifications::dsl::response_id.eq(excluded(notifications::dsl::response_id)),
+                    notifications::dsl::comment_id.eq(excluded(notifications::dsl::comment_id)),
+                    notifications::dsl::event_count.eq(notifications::dsl::event_count + 1),
+                    notifications::dsl::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(cnx)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Tells the post's author about a new comment, and anyone the comment mentions.
+    pub async fn notify_comment(
+        cnx: &mut AsyncPgConnection,
+        comment: &Comment,
+        post_author_id: i32,
+    ) -> QueryResult<()> {
+        let event = NewNotification {
+            user_id: post_author_id,
+            kind: NotificationKind::Comment,
+            post_id: comment.post_id,
+            actor_id: comment.user_id,
+            response_id: None,
+            comment_id: Some(comment.id),
+        };
+        let mentioned = mention::filter_by_comment(cnx, comment.id).await?;
+        Self::notify_with_mentions(cnx, event, mentioned).await
+    }
+
+    /// Tells the challenge's author about a new response, and anyone the response's body
+    /// mentions.
+    pub async fn notify_response(
+        cnx: &mut AsyncPgConnection,
+        response: &Response,
+        challenge_author_id: i32,
+    ) -> QueryResult<()> {
+        let event = NewNotification {
+            user_id: challenge_author_id,
+            kind: NotificationKind::Response,
+            post_id: response.data.challenge_id,
+            actor_id: response.post.user_id,
+            response_id: Some(response.post.id),
+            comment_id: None,
+        };
+        let mentioned = mention::filter_by_post(cnx, response.post.id).await?;
+        Self::notify_with_mentions(cnx, event, mentioned).await
+    }
+
+    /// Tells users that an edit by `editor_id` newly mentions them in a post. `response_id` is
+    /// the post's id if it's a response.
+    pub async fn notify_edit_mentions(
+        cnx: &mut AsyncPgConnection,
+        post_id: i32,
+        response_id: Option<i32>,
+        editor_id: i32,
+        mentioned_ids: &[i32],
+    ) -> QueryResult<()> {
+        let events: Vec<_> = mentioned_ids
+            .iter()
+            .filter(|&&user_id| user_id != editor_id)
+            .map(|&user_id| NewNotification {
+                user_id,
+                kind: NotificationKind::Mention,
+                post_id,
+                actor_id: editor_id,
+                response_id,
+                comment_id: None,
+            })
+            .collect();
+        Self::notify(cnx, &events).await
+    }
+
+    /// Sends `event`, plus a mention of the same activity to each of the `mentioned` users.
+    /// Nobody is told about their own activity, or twice about the same event.
+    async fn notify_with_mentions(
+        cnx: &mut AsyncPgConnection,
+        event: NewNotification,
+        mentioned: Vec<User>,
+    ) -> QueryResult<()> {
+        let mut events = Vec::new();
+        if event.user_id != event.actor_id {
+            events.push(event);
+        }
+        // mentions in a response are about the response, not the challenge it's on
+        let post_id = event.response_id.unwrap_or(event.post_id);
+        for user in mentioned {
+            if user.id != event.actor_id && events.iter().all(|e| e.user_id != user.id) {
+                events.push(NewNotification {
+                    user_id: user.id,
+                    kind: NotificationKind::Mention,
+                    post_id,
+                    ..event
+                });
+            }
+        }
+        Self::notify(cnx, &events).await
+    }
+
+    /// Marks the user's notifications as read: those in `ids`, or all of them if it's `None`.
+    /// Returns how many were unread.
+    pub async fn mark_read(
+        cnx: &mut AsyncPgConnection,
+        user_id: i32,
+        ids: Option<&[i32]>,
+    ) -> QueryResult<usize> {
+        use diesel::{ExpressionMethods, NullableExpressionMethods};
+        use diesel_async::RunQueryDsl;
+
+        let mut query = diesel::update(notifications::table)
+            .filter(notifications::dsl::user_id.eq(user_id))
+            .filter(notifications::dsl::read_at.is_null())
+            .into_boxed();
+        if let Some(ids) = ids {
+            query = query.filter(notifications::dsl::id.eq_any(ids));
+        }
+        query
+            .set(notifications::dsl::read_at.eq(diesel::dsl::now.nullable()))
+            .execute(cnx)
+            .await
+    }
+}
@@ -1,4 +1,5 @@
 use crate::models::user::User;
+use crate::render::markdown;
 use crate::schema::posts;
 use diesel::{
     AsChangeset, Identifiable, Insertable, QueryResult, Queryable, Selectable,
@@ -29,14 +30,82 @@ pub struct Post {
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: Option<chrono::NaiveDateTime>,
     pub deleted_at: Option<chrono::NaiveDateTime>,
+    /// `body` rendered as HTML. Missing for posts that haven't been written since rendered
+    /// bodies started being stored.
+    pub body_html: Option<String>,
 }
 
 impl Post {
+    /// The body rendered as HTML.
+    pub fn render_body(&self) -> String {
+        self.body_html
+            .clone()
+            .unwrap_or_else(|| markdown::render_post(&self.body))
+    }
+
     pub async fn find(cnx: &mut AsyncPgConnection, db_id: i32) -> QueryResult<Self> {
-        use diesel::QueryDsl;
+        use diesel::{QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        posts::table
+            .find(db_id)
+            .select(Self::as_select())
+            .first(cnx)
+            .await
+    }
+
+    /// Like [`Self::find`], but locks the row until the transaction ends, so that whatever was
+    /// checked against it still holds when the transaction writes.
+    pub async fn find_for_update(cnx: &mut AsyncPgConnection, db_id: i32) -> QueryResult<Self> {
+        use diesel::{QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        posts::table
+            .find(db_id)
+            .select(Self::as_select())
+            .for_update()
+            .first(cnx)
+            .await
+    }
+}
+
+/// Which concrete table extends a `posts` row.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PostKind {
+    Challenge,
+    Response,
+}
+
+impl PostKind {
+    /// Determines the kind of every given post in a single query. Posts that
+    /// don't exist, or that are extended by neither table, are left out.
+    pub async fn find_by_ids(
+        cnx: &mut AsyncPgConnection,
+        ids: &[i32],
+    ) -> QueryResult<Vec<(i32, Self)>> {
+        use crate::schema::{challenges, responses};
+        use diesel::{ExpressionMethods, JoinOnDsl, NullableExpressionMethods, QueryDsl};
         use diesel_async::RunQueryDsl;
 
-        posts::table.find(db_id).first(cnx).await
+        let rows = posts::table
+            .left_join(challenges::table.on(challenges::dsl::post_id.eq(posts::dsl::id)))
+            .left_join(responses::table.on(responses::dsl::post_id.eq(posts::dsl::id)))
+            .filter(posts::dsl::id.eq_any(ids))
+            .select((
+                posts::dsl::id,
+                challenges::dsl::post_id.nullable(),
+                responses::dsl::post_id.nullable(),
+            ))
+            .load::<(i32, Option<i32>, Option<i32>)>(cnx)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(id, challenge, response)| match (challenge, response) {
+                (Some(_), _) => Some((id, Self::Challenge)),
+                (None, Some(_)) => Some((id, Self::Response)),
+                (None, None) => None,
+            })
+            .collect())
     }
 }
 
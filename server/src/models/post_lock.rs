@@ -0,0 +1,173 @@
+use crate::models::post::Post;
+use crate::schema::post_locks;
+use diesel::{Identifiable, Insertable, QueryResult, Queryable, Selectable, prelude::Associations};
+use diesel_async::AsyncPgConnection;
+use diesel_derive_enum::DbEnum;
+use serde::Serialize;
+
+#[derive(DbEnum, Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[db_enum(existing_type_path = "crate::schema::sql_types::LockType")]
+#[serde(rename_all = "snake_case")]
+pub enum LockType {
+    /// Stops an edit war: the post can't be edited.
+    ContentDispute,
+    /// Preserves a post that no longer fits the site but is kept for its history: nothing can
+    /// be added or changed.
+    HistoricalSignificance,
+    /// Stops a heated discussion: no new comments.
+    CommentsOnly,
+}
+
+impl LockType {
+    pub fn blocks_edits(self) -> bool {
+        matches!(self, Self::ContentDispute | Self::HistoricalSignificance)
+    }
+
+    pub fn blocks_comments(self) -> bool {
+        matches!(self, Self::HistoricalSignificance | Self::CommentsOnly)
+    }
+
+    pub fn blocks_responses(self) -> bool {
+        matches!(self, Self::HistoricalSignificance)
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq, Clone, Serialize)]
+#[diesel(belongs_to(Post))]
+#[diesel(table_name = post_locks)]
+pub struct PostLock {
+    pub id: i32,
+    pub post_id: i32,
+    pub lock_type: LockType,
+    pub reason: Option<String>,
+    pub locked_by_id: i32,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub lifted_at: Option<chrono::NaiveDateTime>,
+    pub lifted_by_id: Option<i32>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = post_locks)]
+struct NewPostLock<'a> {
+    post_id: i32,
+    lock_type: LockType,
+    reason: Option<&'a str>,
+    locked_by_id: i32,
+    expires_at: Option<chrono::NaiveDateTime>,
+}
+
+impl PostLock {
+    /// The lock currently applying to a post, if any. Locks past their expiry don't apply even
+    /// if they haven't been marked lifted yet.
+    pub async fn find_active(
+        cnx: &mut AsyncPgConnection,
+        post_id: i32,
+    ) -> QueryResult<Option<Self>> {
+        use diesel::{
+            BoolExpressionMethods, ExpressionMethods, NullableExpressionMethods, OptionalExtension,
+            QueryDsl, SelectableHelper,
+        };
+        use diesel_async::RunQueryDsl;
+
+        post_locks::table
+            .filter(post_locks::dsl::post_id.eq(post_id))
+            .filter(post_locks::dsl::lifted_at.is_null())
+            .filter(
+                post_locks::dsl::expires_at
+                    .is_null()
+                    .or(post_locks::dsl::expires_at.gt(diesel::dsl::now.nullable())),
+            )
+            .select(Self::as_select())
+            .first(cnx)
+            .await
+            .optional()
+    }
+
+    /// Locks a post. Fails with a unique violation if the post already has a lock that hasn't
+    /// expired.
+    pub async fn create(
+        cnx: &mut AsyncPgConnection,
+        post_id: i32,
+        locked_by_id: i32,
+        lock_type: LockType,
+        reason: Option<&str>,
+        expires_at: Option<chrono::NaiveDateTime>,
+    ) -> QueryResult<Self> {
+        use diesel::SelectableHelper;
+        use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+
+        cnx.transaction(|cnx| {
+            async move {
+                // an expired lock that the background task hasn't lifted yet would conflict
+                Self::lift_expired(cnx, Some(post_id)).await?;
+                diesel::insert_into(post_locks::table)
+                    .values(NewPostLock {
+                        post_id,
+                        lock_type,
+                        reason,
+                        locked_by_id,
+                        expires_at,
+                    })
+                    .returning(Self::as_returning())
+                    .get_result(cnx)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    /// Lifts a post's lock early. Returns `None` if the post has no active lock.
+    pub async fn lift(
+        cnx: &mut AsyncPgConnection,
+        post_id: i32,
+        lifted_by_id: i32,
+    ) -> QueryResult<Option<Self>> {
+        use diesel::{
+            BoolExpressionMethods, ExpressionMethods, NullableExpressionMethods, OptionalExtension,
+            SelectableHelper,
+        };
+        use diesel_async::RunQueryDsl;
+
+        diesel::update(post_locks::table)
+            .filter(post_locks::dsl::post_id.eq(post_id))
+            .filter(post_locks::dsl::lifted_at.is_null())
+            .filter(
+                post_locks::dsl::expires_at
+                    .is_null()
+                    .or(post_locks::dsl::expires_at.gt(diesel::dsl::now.nullable())),
+            )
+            .set((
+                post_locks::dsl::lifted_at.eq(diesel::dsl::now.nullable()),
+                post_locks::dsl::lifted_by_id.eq(lifted_by_id),
+            ))
+            .returning(Self::as_returning())
+            .get_result(cnx)
+            .await
+            .optional()
+    }
+
+    /// Marks locks past their expiry as lifted, either on one post or on all of them. Returns
+    /// the locks that were lifted.
+    pub async fn lift_expired(
+        cnx: &mut AsyncPgConnection,
+        post_id: Option<i32>,
+    ) -> QueryResult<Vec<Self>> {
+        use diesel::{ExpressionMethods, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        let mut query = diesel::update(post_locks::table)
+            .filter(post_locks::dsl::lifted_at.is_null())
+            .filter(post_locks::dsl::expires_at.le(diesel::dsl::now))
+            .into_boxed();
+        if let Some(post_id) = post_id {
+            query = query.filter(post_locks::dsl::post_id.eq(post_id));
+        }
+        query
+            .set(post_locks::dsl::lifted_at.eq(post_locks::dsl::expires_at))
+            .returning(Self::as_returning())
+            .get_results(cnx)
+            .await
+    }
+}
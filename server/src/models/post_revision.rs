@@ -0,0 +1,227 @@
+use crate::models::attachment::Attachment;
+use crate::models::mention;
+use crate::models::notification::Notification;
+use crate::models::post::Post;
+use crate::models::user::User;
+use crate::render::{code, markdown};
+use crate::schema::post_revisions;
+use diesel::{Identifiable, Insertable, QueryResult, Queryable, Selectable, prelude::Associations};
+use diesel_async::AsyncPgConnection;
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq, Clone)]
+#[diesel(belongs_to(Post))]
+#[diesel(belongs_to(User, foreign_key = editor_id))]
+#[diesel(table_name = post_revisions)]
+pub struct PostRevision {
+    pub id: i32,
+    pub post_id: i32,
+    pub editor_id: i32,
+    pub title: String,
+    pub body: String,
+    pub code: Option<String>,
+    pub summary: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    /// `body` rendered as HTML. Missing for revisions recorded before rendered bodies started
+    /// being stored.
+    pub body_html: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = post_revisions)]
+struct NewPostRevision<'a> {
+    post_id: i32,
+    editor_id: i32,
+    title: &'a str,
+    body: &'a str,
+    code: Option<&'a str>,
+    summary: Option<&'a str>,
+    body_html: &'a str,
+}
+
+/// Changes to a post. Fields left as `None` keep their current value; `code` is ignored for
+/// challenges.
+#[derive(Debug, Default, Clone)]
+pub struct PostEdit {
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub code: Option<String>,
+}
+
+impl PostRevision {
+    pub async fn find(cnx: &mut AsyncPgConnection, id: i32) -> QueryResult<Self> {
+        use diesel::{QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        post_revisions::table
+            .find(id)
+            .select(Self::as_select())
+            .first(cnx)
+            .await
+    }
+
+    pub async fn filter_by_post(
+        cnx: &mut AsyncPgConnection,
+        post_id: i32,
+        after: Option<i32>,
+        before: Option<i32>,
+        limit: Option<i64>,
+    ) -> QueryResult<Vec<Self>> {
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        post_revisions::table
+            .filter(post_revisions::dsl::post_id.eq(post_id))
+            .filter(post_revisions::dsl::id.gt(after.unwrap_or_default()))
+            .filter(post_revisions::dsl::id.lt(before.unwrap_or(i32::MAX)))
+            .select(Self::as_select())
+            .order_by(post_revisions::dsl::id)
+            .limit(limit.unwrap_or(25))
+            .load(cnx)
+            .await
+    }
+
+    /// The post's current revision.
+    pub async fn latest_for_post(cnx: &mut AsyncPgConnection, post_id: i32) -> QueryResult<Self> {
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        post_revisions::table
+            .filter(post_revisions::dsl::post_id.eq(post_id))
+            .select(Self::as_select())
+            .order_by(post_revisions::dsl::id.desc())
+            .first(cnx)
+            .await
+    }
+
+    /// The body rendered as HTML.
+    pub fn render_body(&self) -> String {
+        self.body_html
+            .clone()
+            .unwrap_or_else(|| markdown::render_post(&self.body))
+    }
+
+    /// Records a newly created post's content as its first revision, who its body mentions and
+    /// which attachments it uses, and stores its rendered body on `post` as well.
+    pub async fn record_original(
+        cnx: &mut AsyncPgConnection,
+        post: &mut Post,
+        code: Option<&str>,
+    ) -> QueryResult<Self> {
+        use crate::schema::posts;
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        let body_html = markdown::render_post(&post.body);
+        diesel::update(posts::table.find(post.id))
+            .set(posts::dsl::body_html.eq(&body_html))
+            .execute(cnx)
+            .await?;
+        mention::record_post_mentions(cnx, post.id, &post.body).await?;
+        Attachment::link_to_post(cnx, post.id, &post.body).await?;
+        let revision = diesel::insert_into(post_revisions::table)
+            .values(NewPostRevision {
+                post_id: post.id,
+                editor_id: post.user_id,
+                title: &post.title,
+                body: &post.body,
+                code,
+                summary: None,
+                body_html: &body_html,
+            })
+            .returning(Self::as_returning())
+            .get_result(cnx)
+            .await?;
+        post.body_html = Some(body_html);
+        Ok(revision)
+    }
+
+    /// Applies `edit` to the post and records the result as a new revision, in one transaction.
+    /// The post's mentions and attachments are updated to match its new body, and users it newly
+    /// mentions are notified.
+    pub async fn record_edit(
+        cnx: &mut AsyncPgConnection,
+        post_id: i32,
+        editor_id: i32,
+        edit: PostEdit,
+        summary: Option<&str>,
+    ) -> QueryResult<Self> {
+        use crate::schema::{posts, responses};
+        use diesel::{
+            ExpressionMethods, NullableExpressionMethods, OptionalExtension, QueryDsl,
+            SelectableHelper,
+        };
+        use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+
+        cnx.transaction(|cnx| {
+            async move {
+                let post = posts::table
+                    .find(post_id)
+                    .select(Post::as_select())
+                    .for_update()
+                    .first(cnx)
+                    .await?;
+                let old_code = responses::table
+                    .find(post_id)
+                    .select((responses::dsl::code, responses::dsl::language))
+                    .for_update()
+                    .first::<(String, Option<String>)>(cnx)
+                    .await
+                    .optional()?;
+
+                let title = edit.title.unwrap_or(post.title);
+                let body = edit.body.unwrap_or(post.body);
+                let body_html = markdown::render_post(&body);
+                diesel::update(posts::table.find(post_id))
+                    .set((
+                        posts::dsl::title.eq(&title),
+                        posts::dsl::body.eq(&body),
+                        posts::dsl::body_html.eq(&body_html),
+                        posts::dsl::updated_at.eq(diesel::dsl::now.nullable()),
+                    ))
+                    .execute(cnx)
+                    .await?;
+                let code = match (old_code, edit.code) {
+                    (Some((_, language)), Some(code)) => {
+                        let code_html = code::highlight_blocking(code.clone(), language).await;
+                        diesel::update(responses::table.find(post_id))
+                            .set((
+                                responses::dsl::code.eq(&code),
+                                responses::dsl::code_html.eq(code_html),
+                            ))
+                            .execute(cnx)
+                            .await?;
+                        Some(code)
+                    }
+                    (old_code, _) => old_code.map(|(code, _)| code),
+                };
+                let mentioned_ids = mention::record_post_mentions(cnx, post_id, &body).await?;
+                let response_id = code.is_some().then_some(post_id);
+                Notification::notify_edit_mentions(
+                    cnx,
+                    post_id,
+                    response_id,
+                    editor_id,
+                    &mentioned_ids,
+                )
+                .await?;
+                Attachment::link_to_post(cnx, post_id, &body).await?;
+
+                diesel::insert_into(post_revisions::table)
+                    .values(NewPostRevision {
+                        post_id,
+                        editor_id,
+                        title: &title,
+                        body: &body,
+                        code: code.as_deref(),
+                        summary,
+                        body_html: &body_html,
+                    })
+                    .returning(Self::as_returning())
+                    .get_result(cnx)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+}
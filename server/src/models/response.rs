@@ -1,5 +1,12 @@
+use std::fmt::Display;
+
 use crate::models::post::Post;
-use crate::models::{challenge::Challenge, post::FromPost};
+use crate::models::post_revision::PostRevision;
+use crate::models::{
+    challenge::{Challenge, ChallengeData},
+    post::FromPost,
+};
+use crate::render::code;
 use crate::schema::responses;
 use diesel::{
     AsChangeset, Associations, Identifiable, Insertable, QueryResult, Queryable, Selectable,
@@ -25,6 +32,10 @@ pub struct ResponseData {
     pub post_id: i32,
     pub challenge_id: i32,
     pub code: String,
+    pub language: Option<String>,
+    /// `code` highlighted as HTML. Missing for responses whose code hasn't been written since
+    /// highlighted code started being stored.
+    pub code_html: Option<String>,
 }
 
 #[derive(Queryable, Selectable, Debug, PartialEq, Clone)]
@@ -94,6 +105,95 @@ impl Response {
     }
 }
 
+/// The contents of a response to be created.
+#[derive(Debug, Clone, Copy)]
+pub struct NewResponse<'a> {
+    pub challenge_id: i32,
+    pub user_id: i32,
+    pub title: &'a str,
+    pub body: &'a str,
+    pub code: &'a str,
+    pub language: Option<&'a str>,
+}
+
+#[derive(Debug)]
+pub enum ResponseCreateError {
+    ChallengeClosed,
+    Query(diesel::result::Error),
+}
+
+impl Display for ResponseCreateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ChallengeClosed => f.write_str("challenge is closed to new responses"),
+            Self::Query(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ResponseCreateError {}
+
+impl From<diesel::result::Error> for ResponseCreateError {
+    fn from(value: diesel::result::Error) -> Self {
+        Self::Query(value)
+    }
+}
+
+impl Response {
+    /// Creates a response along with its first revision. The challenge is locked while doing so,
+    /// so that it can't be closed halfway through.
+    pub async fn create(
+        cnx: &mut AsyncPgConnection,
+        new: NewResponse<'_>,
+    ) -> Result<Self, ResponseCreateError> {
+        use crate::schema::{challenges, posts};
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+
+        let code_html =
+            code::highlight_blocking(new.code.to_owned(), new.language.map(str::to_owned)).await;
+        cnx.transaction(|cnx| {
+            async move {
+                let challenge = challenges::table
+                    .find(new.challenge_id)
+                    .select(ChallengeData::as_select())
+                    .for_share()
+                    .first(cnx)
+                    .await?;
+                if challenge.is_closed() {
+                    return Err(ResponseCreateError::ChallengeClosed);
+                }
+
+                let mut post = diesel::insert_into(posts::table)
+                    .values((
+                        posts::dsl::title.eq(new.title),
+                        posts::dsl::body.eq(new.body),
+                        posts::dsl::user_id.eq(new.user_id),
+                        posts::dsl::created_at.eq(diesel::dsl::now),
+                    ))
+                    .returning(Post::as_returning())
+                    .get_result(cnx)
+                    .await?;
+                let data = diesel::insert_into(responses::table)
+                    .values(ResponseData {
+                        post_id: post.id,
+                        challenge_id: new.challenge_id,
+                        code: new.code.to_owned(),
+                        language: new.language.map(str::to_owned),
+                        code_html: Some(code_html),
+                    })
+                    .returning(ResponseData::as_returning())
+                    .get_result(cnx)
+                    .await?;
+                PostRevision::record_original(cnx, &mut post, Some(new.code)).await?;
+                Ok(Self { post, data })
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+}
+
 impl FromPost for Response {
     async fn from_post(cnx: &mut AsyncPgConnection, post: &Post) -> QueryResult<Self> {
         Self::find(cnx, post.id).await
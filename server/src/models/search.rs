@@ -0,0 +1,523 @@
+use std::{fmt::Display, ops::RangeInclusive};
+
+use diesel::{QueryResult, QueryableByName, define_sql_function, sql_types::Text};
+use diesel_async::AsyncPgConnection;
+use diesel_full_text_search::{RegConfig, TsQuery, configuration::TsConfigurationByName};
+
+/// Must match the configuration the `full_text_search` migration builds the stored vectors with.
+const CONFIG: TsConfigurationByName = TsConfigurationByName("english");
+
+/// `ts_headline` marks matches with these control characters rather than HTML tags, so that the
+/// text around them can be escaped before the marks become `<mark>` tags (see [`highlight`]).
+const START_SEL: char = '\u{2}';
+const STOP_SEL: char = '\u{3}';
+const HEADLINE_OPTIONS: &str =
+    "StartSel=\u{2}, StopSel=\u{3}, MaxFragments=2, MaxWords=30, MinWords=10";
+const TITLE_HEADLINE_OPTIONS: &str = "StartSel=\u{2}, StopSel=\u{3}, HighlightAll=true";
+
+/// Longest a code search may run before it is cancelled, in milliseconds. Regexes especially can
+/// be written to take practically forever.
+const CODE_SEARCH_TIMEOUT_MS: u32 = 2000;
+
+define_sql_function! {
+    #[sql_name = "ts_headline"]
+    fn ts_headline_with_options(config: RegConfig, document: Text, query: TsQuery, options: Text) -> Text;
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SearchKind {
+    Challenge,
+    Response,
+    Comment,
+}
+
+/// Search results are ordered by rank, then posts before comments, then by id.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SearchCursor {
+    rank: f32,
+    is_comment: bool,
+    id: i32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SearchHit {
+    pub kind: SearchKind,
+    pub id: i32,
+    pub rank: f32,
+    /// The highlighted title, for posts. Like the snippet, it is escaped HTML.
+    pub title: Option<String>,
+    pub snippet: String,
+}
+
+impl SearchHit {
+    fn cursor(&self) -> SearchCursor {
+        SearchCursor {
+            rank: self.rank,
+            is_comment: self.kind == SearchKind::Comment,
+            id: self.id,
+        }
+    }
+}
+
+/// Escapes a `ts_headline` result for HTML and wraps its matches in `<mark>` tags. Stray marks
+/// (the characters can also appear in the stored text) are dropped, so the tags always balance.
+fn highlight(headline: &str) -> String {
+    let mut html = String::with_capacity(headline.len());
+    let mut marked = false;
+    for c in headline.chars() {
+        match c {
+            START_SEL if !marked => {
+                html.push_str("<mark>");
+                marked = true;
+            }
+            STOP_SEL if marked => {
+                html.push_str("</mark>");
+                marked = false;
+            }
+            START_SEL | STOP_SEL => {}
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    if marked {
+        html.push_str("</mark>");
+    }
+    html
+}
+
+/// Translates a user's search into `to_tsquery` syntax. Terms must all match unless separated
+/// by `or`; `"quoted words"` match as a phrase, `word*` matches as a prefix and `-word`
+/// excludes. Only letters and digits are kept from each word, so the result is always valid.
+/// Returns `None` if the search has no words at all.
+pub fn parse_query(input: &str) -> Option<String> {
+    fn words(s: &str) -> Vec<&str> {
+        s.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect()
+    }
+
+    let mut query = String::new();
+    let mut or = false;
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        let negate = rest.starts_with('-');
+        if negate {
+            rest = &rest[1..];
+        }
+        let term = if let Some(quoted) = rest.strip_prefix('"') {
+            let (phrase, after) = quoted.split_once('"').unwrap_or((quoted, ""));
+            rest = after;
+            words(phrase).join(" <-> ")
+        } else {
+            let (token, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            rest = after;
+            if token.eq_ignore_ascii_case("or") && !negate {
+                or = true;
+                rest = rest.trim_start();
+                continue;
+            }
+            let words = words(token);
+            match words.len() {
+                0 => String::new(),
+                _ if token.ends_with('*') => format!("{}:*", words.join(" <-> ")),
+                _ => words.join(" <-> "),
+            }
+        };
+        rest = rest.trim_start();
+        if term.is_empty() {
+            continue;
+        }
+        if !query.is_empty() {
+            query.push_str(if or { " | " } else { " & " });
+        }
+        or = false;
+        if negate {
+            query.push('!');
+        }
+        query.push('(');
+        query.push_str(&term);
+        query.push(')');
+    }
+    (!query.is_empty()).then_some(query)
+}
+
+/// Finds the cursor of a previous result, so that results can be paged through by rank.
+/// Returns `None` if the row no longer exists.
+pub async fn find_cursor(
+    cnx: &mut AsyncPgConnection,
+    query: &str,
+    kind: SearchKind,
+    id: i32,
+) -> QueryResult<Option<SearchCursor>> {
+    use crate::schema::{comments, posts};
+    use diesel::{OptionalExtension, QueryDsl};
+    use diesel_async::RunQueryDsl;
+    use diesel_full_text_search::{to_tsquery_with_search_config, ts_rank};
+
+    let tsquery = to_tsquery_with_search_config(CONFIG, query.to_owned());
+    let rank = match kind {
+        SearchKind::Challenge | SearchKind::Response => {
+            posts::table
+                .find(id)
+                .select(ts_rank(posts::dsl::search_vector, tsquery))
+                .first::<f32>(cnx)
+                .await
+        }
+        SearchKind::Comment => {
+            comments::table
+                .find(id)
+                .select(ts_rank(comments::dsl::search_vector, tsquery))
+                .first::<f32>(cnx)
+                .await
+        }
+    }
+    .optional()?;
+    Ok(rank.map(|rank| SearchCursor {
+        rank,
+        is_comment: kind == SearchKind::Comment,
+        id,
+    }))
+}
+
+/// Searches posts and comments of the given kinds for `query`, which must be in `to_tsquery`
+/// syntax (see [`parse_query`]). Results of deleted posts are left out.
+pub async fn search(
+    cnx: &mut AsyncPgConnection,
+    query: &str,
+    kinds: &[SearchKind],
+    after: Option<SearchCursor>,
+    limit: Option<i64>,
+) -> QueryResult<Vec<SearchHit>> {
+    use crate::schema::{challenges, comments, posts, responses};
+    use diesel::{
+        BoolExpressionMethods, ExpressionMethods, JoinOnDsl, NullableExpressionMethods, QueryDsl,
+    };
+    use diesel_async::RunQueryDsl;
+    use diesel_full_text_search::{TsVectorExtensions, to_tsquery_with_search_config, ts_rank};
+
+    let limit = limit.unwrap_or(25);
+    let tsquery = to_tsquery_with_search_config(CONFIG, query.to_owned());
+    let mut hits = Vec::new();
+
+    let challenges = kinds.contains(&SearchKind::Challenge);
+    let responses = kinds.contains(&SearchKind::Response);
+    if challenges || responses {
+        let rank = ts_rank(posts::dsl::search_vector, tsquery.clone());
+        let is_challenge = challenges::dsl::post_id.nullable().is_not_null();
+        let is_response = responses::dsl::post_id.nullable().is_not_null();
+        let mut posts_query = posts::table
+            .left_join(challenges::table.on(challenges::dsl::post_id.eq(posts::dsl::id)))
+            .left_join(responses::table.on(responses::dsl::post_id.eq(posts::dsl::id)))
+            .filter(posts::dsl::deleted_at.is_null())
+            .filter(posts::dsl::search_vector.matches(tsquery.clone()))
+            .select((
+                posts::dsl::id,
+                is_response,
+                rank.clone(),
+                ts_headline_with_options(
+                    CONFIG,
+                    posts::dsl::title,
+                    tsquery.clone(),
+                    TITLE_HEADLINE_OPTIONS,
+                ),
+                ts_headline_with_options(
+                    CONFIG,
+                    posts::dsl::body,
+                    tsquery.clone(),
+                    HEADLINE_OPTIONS,
+                ),
+            ))
+            .order((rank.clone().desc(), posts::dsl::id))
+            .limit(limit)
+            .into_boxed();
+        posts_query = match (challenges, responses) {
+            (true, true) => posts_query.filter(is_challenge.or(is_response)),
+            (true, false) => posts_query.filter(is_challenge),
+            (false, _) => posts_query.filter(is_response),
+        };
+        posts_query = match after {
+            Some(SearchCursor {
+                rank: after_rank,
+                is_comment: false,
+                id,
+            }) => posts_query.filter(
+                rank.clone()
+                    .lt(after_rank)
+                    .or(rank.clone().eq(after_rank).and(posts::dsl::id.gt(id))),
+            ),
+            Some(SearchCursor {
+                rank: after_rank,
+                is_comment: true,
+                ..
+            }) => posts_query.filter(rank.lt(after_rank)),
+            None => posts_query,
+        };
+        hits.extend(
+            posts_query
+                .load::<(i32, bool, f32, String, String)>(cnx)
+                .await?
+                .into_iter()
+                .map(|(id, is_response, rank, title, snippet)| SearchHit {
+                    kind: if is_response {
+                        SearchKind::Response
+                    } else {
+                        SearchKind::Challenge
+                    },
+                    id,
+                    rank,
+                    title: Some(highlight(&title)),
+                    snippet: highlight(&snippet),
+                }),
+        );
+    }
+
+    if kinds.contains(&SearchKind::Comment) {
+        let rank = ts_rank(comments::dsl::search_vector, tsquery.clone());
+        let mut comments_query = comments::table
+            .inner_join(posts::table)
+            .filter(posts::dsl::deleted_at.is_null())
+            .filter(comments::dsl::search_vector.matches(tsquery.clone()))
+            .select((
+                comments::dsl::id,
+                rank.clone(),
+                ts_headline_with_options(
+                    CONFIG,
+                    comments::dsl::body,
+                    tsquery.clone(),
+                    HEADLINE_OPTIONS,
+                ),
+            ))
+            .order((rank.clone().desc(), comments::dsl::id))
+            .limit(limit)
+            .into_boxed();
+        comments_query = match after {
+            Some(SearchCursor {
+                rank: after_rank,
+                is_comment: true,
+                id,
+            }) => comments_query.filter(
+                rank.clone()
+                    .lt(after_rank)
+                    .or(rank.clone().eq(after_rank).and(comments::dsl::id.gt(id))),
+            ),
+            Some(SearchCursor {
+                rank: after_rank,
+                is_comment: false,
+                ..
+            }) => comments_query.filter(rank.le(after_rank)),
+            None => comments_query,
+        };
+        hits.extend(
+            comments_query
+                .load::<(i32, f32, String)>(cnx)
+                .await?
+                .into_iter()
+                .map(|(id, rank, snippet)| SearchHit {
+                    kind: SearchKind::Comment,
+                    id,
+                    rank,
+                    title: None,
+                    snippet: highlight(&snippet),
+                }),
+        );
+    }
+
+    hits.sort_by(|a, b| {
+        let (a, b) = (a.cursor(), b.cursor());
+        b.rank
+            .total_cmp(&a.rank)
+            .then(a.is_comment.cmp(&b.is_comment))
+            .then(a.id.cmp(&b.id))
+    });
+    hits.truncate(limit.try_into().unwrap_or(usize::MAX));
+    Ok(hits)
+}
+
+/// A response whose code matches a code search.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CodeMatch {
+    pub response_id: i32,
+    /// The matching lines, numbered from 1, with consecutive lines merged into one range.
+    pub lines: Vec<RangeInclusive<i32>>,
+}
+
+#[derive(QueryableByName)]
+struct CodeMatchRow {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    post_id: i32,
+    #[diesel(sql_type = diesel::sql_types::Array<diesel::sql_types::BigInt>)]
+    lines: Vec<i64>,
+}
+
+impl From<CodeMatchRow> for CodeMatch {
+    fn from(row: CodeMatchRow) -> Self {
+        let mut lines: Vec<RangeInclusive<i32>> = Vec::new();
+        for line in row.lines {
+            let line = i32::try_from(line).unwrap_or(i32::MAX);
+            match lines.last_mut() {
+                Some(range) if *range.end() + 1 == line => *range = *range.start()..=line,
+                _ => lines.push(line..=line),
+            }
+        }
+        Self {
+            response_id: row.post_id,
+            lines,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CodeSearchError {
+    InvalidPattern(String),
+    TimedOut,
+    Query(diesel::result::Error),
+}
+
+impl Display for CodeSearchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidPattern(message) => f.write_str(message),
+            Self::TimedOut => write!(
+                f,
+                "search took longer than {CODE_SEARCH_TIMEOUT_MS}ms and was cancelled"
+            ),
+            Self::Query(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for CodeSearchError {}
+
+impl From<diesel::result::Error> for CodeSearchError {
+    fn from(value: diesel::result::Error) -> Self {
+        // diesel doesn't expose the SQLSTATE, so go by postgres' messages
+        match &value {
+            diesel::result::Error::DatabaseError(_, info)
+                if info.message().starts_with("invalid regular expression") =>
+            {
+                Self::InvalidPattern(info.message().to_owned())
+            }
+            diesel::result::Error::DatabaseError(_, info)
+                if info.message().contains("statement timeout") =>
+            {
+                Self::TimedOut
+            }
+            _ => Self::Query(value),
+        }
+    }
+}
+
+/// Finds the responses whose code contains `pattern` on a single line, either literally or as a
+/// POSIX regex, ordered by id. Each search runs under a statement timeout.
+pub async fn search_code(
+    cnx: &mut AsyncPgConnection,
+    pattern: &str,
+    regex: bool,
+    language: Option<&str>,
+    after: Option<i32>,
+    before: Option<i32>,
+    limit: Option<i64>,
+) -> Result<Vec<CodeMatch>, CodeSearchError> {
+    use diesel::sql_types::{BigInt, Integer, Nullable};
+    use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+
+    let (operator, pattern) = if regex {
+        // `n` makes `^`, `$` and `.` work line by line, as they do when matching each line
+        ("~", format!("(?n){pattern}"))
+    } else {
+        let escaped = pattern
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        ("LIKE", format!("%{escaped}%"))
+    };
+    // both conditions on the code let the trigram index narrow down the rows to split
+    let query = diesel::sql_query(format!(
+        "SELECT responses.post_id, array_agg(lines.n ORDER BY lines.n) AS lines
+        FROM responses
+        INNER JOIN posts ON posts.id = responses.post_id
+        CROSS JOIN LATERAL string_to_table(responses.code, E'\\n') WITH ORDINALITY AS lines(line, n)
+        WHERE posts.deleted_at IS NULL
+            AND responses.code {operator} $1
+            AND lines.line {operator} $1
+            AND ($2::text IS NULL OR lower(responses.language) = lower($2))
+            AND responses.post_id > $3
+            AND responses.post_id < $4
+        GROUP BY responses.post_id
+        ORDER BY responses.post_id
+        LIMIT $5"
+    ))
+    .bind::<Text, _>(pattern)
+    .bind::<Nullable<Text>, _>(language)
+    .bind::<Integer, _>(after.unwrap_or_default())
+    .bind::<Integer, _>(before.unwrap_or(i32::MAX))
+    .bind::<BigInt, _>(limit.unwrap_or(25));
+
+    let rows = cnx
+        .transaction::<_, diesel::result::Error, _>(|cnx| {
+            async move {
+                diesel::sql_query(format!(
+                    "SET LOCAL statement_timeout = {CODE_SEARCH_TIMEOUT_MS}"
+                ))
+                .execute(cnx)
+                .await?;
+                query.load::<CodeMatchRow>(cnx).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    Ok(rows.into_iter().map(CodeMatch::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_words_phrases_and_prefixes() {
+        assert_eq!(parse_query("foo bar").as_deref(), Some("(foo) & (bar)"));
+        assert_eq!(
+            parse_query(r#""hello world" code"#).as_deref(),
+            Some("(hello <-> world) & (code)")
+        );
+        assert_eq!(parse_query("pre*").as_deref(), Some("(pre:*)"));
+        assert_eq!(parse_query("foo-bar").as_deref(), Some("(foo <-> bar)"));
+    }
+
+    #[test]
+    fn parses_or_and_negation() {
+        assert_eq!(parse_query("foo OR bar").as_deref(), Some("(foo) | (bar)"));
+        assert_eq!(parse_query("foo -bar").as_deref(), Some("(foo) & !(bar)"));
+        assert_eq!(
+            parse_query("-foo or bar").as_deref(),
+            Some("!(foo) | (bar)")
+        );
+        assert_eq!(parse_query("foo or").as_deref(), Some("(foo)"));
+    }
+
+    #[test]
+    fn drops_tsquery_syntax() {
+        assert_eq!(parse_query("a:*&b").as_deref(), Some("(a <-> b)"));
+        assert_eq!(parse_query("!(x) | y").as_deref(), Some("(x) & (y)"));
+        assert_eq!(parse_query(r#"& | ! "" - *"#), None);
+        assert_eq!(parse_query("   "), None);
+    }
+
+    #[test]
+    fn escapes_and_marks_headlines() {
+        assert_eq!(
+            highlight("a \u{2}<b>\u{3} & 'c'"),
+            "a <mark>&lt;b&gt;</mark> &amp; &#39;c&#39;"
+        );
+    }
+
+    #[test]
+    fn drops_stray_marks() {
+        assert_eq!(highlight("\u{3}x \u{2}y"), "x <mark>y</mark>");
+        assert_eq!(highlight("\u{2}\u{2}a\u{3}\u{3}b"), "<mark>a</mark>b");
+    }
+}
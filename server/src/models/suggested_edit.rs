@@ -0,0 +1,176 @@
+use crate::models::post::Post;
+use crate::models::post_revision::{PostEdit, PostRevision};
+use crate::schema::suggested_edits;
+use diesel::{Identifiable, Insertable, QueryResult, Queryable, Selectable, prelude::Associations};
+use diesel_async::AsyncPgConnection;
+use diesel_derive_enum::DbEnum;
+use serde::Serialize;
+
+#[derive(DbEnum, Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[db_enum(existing_type_path = "crate::schema::sql_types::SuggestedEditStatus")]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestedEditStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq, Clone, Serialize)]
+#[diesel(belongs_to(Post))]
+#[diesel(table_name = suggested_edits)]
+pub struct SuggestedEdit {
+    pub id: i32,
+    pub post_id: i32,
+    pub suggester_id: i32,
+    /// The post's latest revision when the suggestion was made.
+    pub base_revision_id: i32,
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub comment: String,
+    pub status: SuggestedEditStatus,
+    pub reviewer_id: Option<i32>,
+    pub review_comment: Option<String>,
+    pub revision_id: Option<i32>,
+    pub created_at: chrono::NaiveDateTime,
+    pub reviewed_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = suggested_edits)]
+struct NewSuggestedEdit<'a> {
+    post_id: i32,
+    suggester_id: i32,
+    base_revision_id: i32,
+    title: Option<&'a str>,
+    body: Option<&'a str>,
+    comment: &'a str,
+}
+
+impl SuggestedEdit {
+    pub async fn find(cnx: &mut AsyncPgConnection, id: i32) -> QueryResult<Self> {
+        use diesel::{QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        suggested_edits::table
+            .find(id)
+            .select(Self::as_select())
+            .first(cnx)
+            .await
+    }
+
+    pub async fn filter_by_post(
+        cnx: &mut AsyncPgConnection,
+        post_id: i32,
+        after: Option<i32>,
+        before: Option<i32>,
+        limit: Option<i64>,
+    ) -> QueryResult<Vec<Self>> {
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        suggested_edits::table
+            .filter(suggested_edits::dsl::post_id.eq(post_id))
+            .filter(suggested_edits::dsl::id.gt(after.unwrap_or_default()))
+            .filter(suggested_edits::dsl::id.lt(before.unwrap_or(i32::MAX)))
+            .select(Self::as_select())
+            .order_by(suggested_edits::dsl::id)
+            .limit(limit.unwrap_or(25))
+            .load(cnx)
+            .await
+    }
+
+    /// Records a pending suggestion against the post's latest revision. Fails with a unique
+    /// violation if the suggester already has one pending for the post.
+    pub async fn create(
+        cnx: &mut AsyncPgConnection,
+        post_id: i32,
+        suggester_id: i32,
+        title: Option<&str>,
+        body: Option<&str>,
+        comment: &str,
+    ) -> QueryResult<Self> {
+        use diesel::SelectableHelper;
+        use diesel_async::RunQueryDsl;
+
+        let base_revision_id = PostRevision::latest_for_post(cnx, post_id).await?.id;
+        diesel::insert_into(suggested_edits::table)
+            .values(NewSuggestedEdit {
+                post_id,
+                suggester_id,
+                base_revision_id,
+                title,
+                body,
+                comment,
+            })
+            .returning(Self::as_returning())
+            .get_result(cnx)
+            .await
+    }
+
+    /// Approves or rejects a pending suggestion. Approving applies it to the post as a new
+    /// revision credited to the suggester, with the suggestion's comment as its summary. Returns
+    /// `None` if the suggestion has already been reviewed.
+    pub async fn review(
+        cnx: &mut AsyncPgConnection,
+        id: i32,
+        reviewer_id: i32,
+        approve: bool,
+        comment: Option<&str>,
+    ) -> QueryResult<Option<Self>> {
+        use diesel::{
+            ExpressionMethods, NullableExpressionMethods, OptionalExtension, QueryDsl,
+            SelectableHelper,
+        };
+        use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+
+        cnx.transaction(|cnx| {
+            async move {
+                let Some(suggestion) = suggested_edits::table
+                    .find(id)
+                    .filter(suggested_edits::dsl::status.eq(SuggestedEditStatus::Pending))
+                    .select(Self::as_select())
+                    .for_update()
+                    .first(cnx)
+                    .await
+                    .optional()?
+                else {
+                    return Ok(None);
+                };
+
+                let (status, revision_id) = if approve {
+                    let edit = PostEdit {
+                        title: suggestion.title,
+                        body: suggestion.body,
+                        code: None,
+                    };
+                    let revision = PostRevision::record_edit(
+                        cnx,
+                        suggestion.post_id,
+                        suggestion.suggester_id,
+                        edit,
+                        Some(&suggestion.comment),
+                    )
+                    .await?;
+                    (SuggestedEditStatus::Approved, Some(revision.id))
+                } else {
+                    (SuggestedEditStatus::Rejected, None)
+                };
+
+                diesel::update(suggested_edits::table.find(id))
+                    .set((
+                        suggested_edits::dsl::status.eq(status),
+                        suggested_edits::dsl::reviewer_id.eq(reviewer_id),
+                        suggested_edits::dsl::review_comment.eq(comment),
+                        suggested_edits::dsl::revision_id.eq(revision_id),
+                        suggested_edits::dsl::reviewed_at.eq(diesel::dsl::now.nullable()),
+                    ))
+                    .returning(Self::as_returning())
+                    .get_result(cnx)
+                    .await
+                    .map(Some)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+}
@@ -1,5 +1,23 @@
 use crate::schema::users;
-use diesel::prelude::*;
+use diesel::{AsChangeset, Identifiable, Insertable, QueryResult, Queryable, Selectable};
+use diesel_async::AsyncPgConnection;
+use diesel_derive_enum::DbEnum;
+
+/// What a user may do beyond managing their own content.
+#[derive(DbEnum, Debug, PartialEq, Eq, Clone, Copy)]
+#[db_enum(existing_type_path = "crate::schema::sql_types::UserRole")]
+pub enum UserRole {
+    User,
+    Moderator,
+    Admin,
+}
+
+impl UserRole {
+    /// Whether the role may act on other users' content. Admins can do anything moderators can.
+    pub fn is_moderator(self) -> bool {
+        matches!(self, Self::Moderator | Self::Admin)
+    }
+}
 
 #[derive(Queryable, Selectable, Identifiable, AsChangeset, Insertable, Debug, PartialEq, Clone)]
 #[diesel(table_name = users)]
@@ -12,4 +30,61 @@ pub struct User {
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: Option<chrono::NaiveDateTime>,
     pub deleted_at: Option<chrono::NaiveDateTime>,
+
+    pub auth_subject: Option<String>,
+    pub role: UserRole,
+    pub suspended_until: Option<chrono::NaiveDateTime>,
+    /// Set if the user uploaded an avatar. See [`crate::storage::avatars`].
+    pub avatar_key: Option<String>,
+}
+
+impl User {
+    /// Whether the user is serving a suspension, during which they may only read.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_until
+            .is_some_and(|until| until > chrono::Utc::now().naive_utc())
+    }
+
+    pub async fn find(cnx: &mut AsyncPgConnection, id: i32) -> QueryResult<Self> {
+        use diesel::{QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        users::table
+            .find(id)
+            .select(Self::as_select())
+            .first(cnx)
+            .await
+    }
+
+    /// Finds the user a token's `sub` claim belongs to. Deleted users are left out.
+    pub async fn find_by_auth_subject(
+        cnx: &mut AsyncPgConnection,
+        subject: &str,
+    ) -> QueryResult<Self> {
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        users::table
+            .filter(users::dsl::auth_subject.eq(subject))
+            .filter(users::dsl::deleted_at.is_null())
+            .select(Self::as_select())
+            .first(cnx)
+            .await
+    }
+
+    /// Replaces the user's avatar, or removes it if `avatar_key` is `None`.
+    pub async fn set_avatar(
+        cnx: &mut AsyncPgConnection,
+        id: i32,
+        avatar_key: Option<&str>,
+    ) -> QueryResult<Self> {
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        diesel::update(users::table.find(id))
+            .set(users::dsl::avatar_key.eq(avatar_key))
+            .returning(Self::as_returning())
+            .get_result(cnx)
+            .await
+    }
 }
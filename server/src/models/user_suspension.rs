@@ -0,0 +1,152 @@
+use crate::models::user::User;
+use crate::schema::{user_suspensions, users};
+use diesel::{Identifiable, Insertable, QueryResult, Queryable, Selectable, prelude::Associations};
+use diesel_async::AsyncPgConnection;
+use serde::Serialize;
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq, Clone, Serialize)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = user_suspensions)]
+pub struct UserSuspension {
+    pub id: i32,
+    pub user_id: i32,
+    pub moderator_id: i32,
+    pub reason: String,
+    pub message: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub ends_at: chrono::NaiveDateTime,
+    pub lifted_at: Option<chrono::NaiveDateTime>,
+    pub lifted_by_id: Option<i32>,
+    pub lift_note: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = user_suspensions)]
+struct NewUserSuspension<'a> {
+    user_id: i32,
+    moderator_id: i32,
+    reason: &'a str,
+    message: Option<&'a str>,
+    ends_at: chrono::NaiveDateTime,
+}
+
+impl UserSuspension {
+    pub async fn find(cnx: &mut AsyncPgConnection, id: i32) -> QueryResult<Self> {
+        use diesel::{QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        user_suspensions::table
+            .find(id)
+            .select(Self::as_select())
+            .first(cnx)
+            .await
+    }
+
+    /// The user's suspensions, oldest first.
+    pub async fn filter_by_user(
+        cnx: &mut AsyncPgConnection,
+        user_id: i32,
+        after: Option<i32>,
+        before: Option<i32>,
+        limit: Option<i64>,
+    ) -> QueryResult<Vec<Self>> {
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::RunQueryDsl;
+
+        user_suspensions::table
+            .filter(user_suspensions::dsl::user_id.eq(user_id))
+            .filter(user_suspensions::dsl::id.gt(after.unwrap_or_default()))
+            .filter(user_suspensions::dsl::id.lt(before.unwrap_or(i32::MAX)))
+            .select(Self::as_select())
+            .order_by(user_suspensions::dsl::id)
+            .limit(limit.unwrap_or(25))
+            .load(cnx)
+            .await
+    }
+
+    /// Suspends a user until `ends_at`. Returns `None` if they're already suspended.
+    pub async fn create(
+        cnx: &mut AsyncPgConnection,
+        user_id: i32,
+        moderator_id: i32,
+        reason: &str,
+        message: Option<&str>,
+        ends_at: chrono::NaiveDateTime,
+    ) -> QueryResult<Option<Self>> {
+        use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+        use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+
+        cnx.transaction(|cnx| {
+            async move {
+                let user = users::table
+                    .find(user_id)
+                    .select(User::as_select())
+                    .for_update()
+                    .first(cnx)
+                    .await?;
+                if user.is_suspended() {
+                    return Ok(None);
+                }
+                diesel::update(users::table.find(user_id))
+                    .set(users::dsl::suspended_until.eq(ends_at))
+                    .execute(cnx)
+                    .await?;
+                diesel::insert_into(user_suspensions::table)
+                    .values(NewUserSuspension {
+                        user_id,
+                        moderator_id,
+                        reason,
+                        message,
+                        ends_at,
+                    })
+                    .returning(Self::as_returning())
+                    .get_result(cnx)
+                    .await
+                    .map(Some)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    /// Ends a user's current suspension early. Returns `None` if they aren't suspended.
+    pub async fn lift(
+        cnx: &mut AsyncPgConnection,
+        user_id: i32,
+        lifted_by_id: i32,
+        note: Option<&str>,
+    ) -> QueryResult<Option<Self>> {
+        use diesel::{
+            ExpressionMethods, NullableExpressionMethods, OptionalExtension, QueryDsl,
+            SelectableHelper,
+        };
+        use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+
+        cnx.transaction(|cnx| {
+            async move {
+                let lifted = diesel::update(user_suspensions::table)
+                    .filter(user_suspensions::dsl::user_id.eq(user_id))
+                    .filter(user_suspensions::dsl::lifted_at.is_null())
+                    .filter(user_suspensions::dsl::ends_at.gt(diesel::dsl::now))
+                    .set((
+                        user_suspensions::dsl::lifted_at.eq(diesel::dsl::now.nullable()),
+                        user_suspensions::dsl::lifted_by_id.eq(lifted_by_id),
+                        user_suspensions::dsl::lift_note.eq(note),
+                    ))
+                    .returning(Self::as_returning())
+                    .get_result(cnx)
+                    .await
+                    .optional()?;
+                if lifted.is_some() {
+                    diesel::update(users::table.find(user_id))
+                        .set(users::dsl::suspended_until.eq(None::<chrono::NaiveDateTime>))
+                        .execute(cnx)
+                        .await?;
+                }
+                Ok(lifted)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::LazyLock;
+
+use actix_web::web;
+use syntect::{
+    html::{ClassStyle, ClassedHTMLGenerator},
+    parsing::{SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+/// Prefix of the CSS classes in highlighted code, one per scope name (`hl-keyword` etc.).
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// Grammars for highlighting: syntect's defaults plus the `.sublime-syntax` files in the
+/// directory named by `SYNTAX_DIR`, if set, which is how golfing languages get highlighted.
+pub static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(|| {
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    if let Ok(dir) = dotenvy::var("SYNTAX_DIR") {
+        builder
+            .add_from_folder(&dir, true)
+            .expect("could not load grammars from SYNTAX_DIR");
+    }
+    builder.build()
+});
+
+/// The grammar for a response's language, e.g. `Python 3` or `jelly`. Tries the whole name, then
+/// its first word, each as a grammar name or file extension, ignoring case.
+fn find_syntax(language: &str) -> Option<&'static SyntaxReference> {
+    let language = language.trim();
+    SYNTAXES.find_syntax_by_token(language).or_else(|| {
+        let first_word = language.split_whitespace().next()?;
+        SYNTAXES.find_syntax_by_token(first_word)
+    })
+}
+
+fn highlight_with(code: &str, syntax: &SyntaxReference) -> Result<String, syntect::Error> {
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAXES, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        generator.parse_html_for_line_which_includes_newline(line)?;
+    }
+    Ok(format!("<pre><code>{}</code></pre>", generator.finalize()))
+}
+
+/// Highlights code as HTML, with spans classed by scope. Code in a language without a grammar,
+/// or that the grammar chokes on, comes back as escaped plain text.
+pub fn highlight(code: &str, language: Option<&str>) -> String {
+    let plain_text = SYNTAXES.find_syntax_plain_text();
+    let syntax = language.and_then(find_syntax).unwrap_or(plain_text);
+    highlight_with(code, syntax)
+        .or_else(|_| highlight_with(code, plain_text))
+        .unwrap_or_else(|_| escape(code))
+}
+
+/// [`highlight`] on the blocking thread pool: long code can keep syntect busy for a while. Code
+/// that highlighting panics on comes back as escaped plain text.
+pub async fn highlight_blocking(code: String, language: Option<String>) -> String {
+    let unhighlighted = code.clone();
+    web::block(move || highlight(&code, language.as_deref()))
+        .await
+        .unwrap_or_else(|_| escape(&unhighlighted))
+}
+
+fn escape(code: &str) -> String {
+    format!("<pre><code>{}</code></pre>", ammonia::clean_text(code))
+}
@@ -0,0 +1,107 @@
+use std::{fmt::Write, io::Cursor};
+
+use image::{ImageFormat, Rgb, RgbImage};
+use sha2::{Digest, Sha256};
+
+/// Cells per side of the pattern. Only the left half and the middle column are random; the rest
+/// mirrors them, which makes the pattern read as a shape rather than noise.
+const GRID: u32 = 5;
+
+/// Width of the empty border around the pattern, in cells.
+const MARGIN: f32 = 0.5;
+
+const BACKGROUND: Rgb<u8> = Rgb([240, 240, 240]);
+
+/// Where the server serves the `size`-pixel PNG identicon of a user.
+pub fn url(user_id: i32, size: u32) -> String {
+    format!("/identicons/{size}/{user_id}.png")
+}
+
+/// A symmetric pattern of colored cells standing in for the avatar of a user who hasn't uploaded
+/// one. The same user always gets the same identicon.
+pub struct Identicon {
+    cells: [[bool; GRID as usize]; GRID as usize],
+    color: Rgb<u8>,
+}
+
+impl Identicon {
+    pub fn for_user(user_id: i32) -> Self {
+        let hash = Sha256::digest(format!("user:{user_id}"));
+        let mut cells = [[false; GRID as usize]; GRID as usize];
+        for (row, cells) in cells.iter_mut().enumerate() {
+            for col in 0..GRID.div_ceil(2) as usize {
+                let filled = hash[row * 3 + col] % 2 == 0;
+                cells[col] = filled;
+                cells[GRID as usize - 1 - col] = filled;
+            }
+        }
+        let hue = f32::from(u16::from_be_bytes([hash[28], hash[29]]) % 360);
+        let saturation = 0.45 + f32::from(hash[30] % 20) / 100.0;
+        let lightness = 0.5 + f32::from(hash[31] % 15) / 100.0;
+        Self {
+            cells,
+            color: hsl_to_rgb(hue, saturation, lightness),
+        }
+    }
+
+    /// The identicon as a scalable SVG image.
+    pub fn to_svg(&self) -> String {
+        let side = GRID as f32 + 2.0 * MARGIN;
+        let Rgb([r, g, b]) = self.color;
+        let Rgb([br, bg, bb]) = BACKGROUND;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {side} {side}\" \
+             shape-rendering=\"crispEdges\">\
+             <rect width=\"{side}\" height=\"{side}\" fill=\"#{br:02x}{bg:02x}{bb:02x}\"/>\
+             <g fill=\"#{r:02x}{g:02x}{b:02x}\">"
+        );
+        for (row, cells) in self.cells.iter().enumerate() {
+            for (col, &filled) in cells.iter().enumerate() {
+                if filled {
+                    let x = col as f32 + MARGIN;
+                    let y = row as f32 + MARGIN;
+                    let _ = write!(svg, "<rect x=\"{x}\" y=\"{y}\" width=\"1\" height=\"1\"/>");
+                }
+            }
+        }
+        svg.push_str("</g></svg>");
+        svg
+    }
+
+    /// The identicon as a `size`-pixel square PNG image.
+    pub fn to_png(&self, size: u32) -> Vec<u8> {
+        let cell = size as f32 / (GRID as f32 + 2.0 * MARGIN);
+        let image = RgbImage::from_fn(size, size, |x, y| {
+            let col = (x as f32 + 0.5) / cell - MARGIN;
+            let row = (y as f32 + 0.5) / cell - MARGIN;
+            let inside = (0.0..GRID as f32).contains(&col) && (0.0..GRID as f32).contains(&row);
+            if inside && self.cells[row as usize][col as usize] {
+                self.color
+            } else {
+                BACKGROUND
+            }
+        });
+        let mut out = Cursor::new(Vec::new());
+        image
+            .write_to(&mut out, ImageFormat::Png)
+            .expect("encoding a PNG in memory can't fail");
+        out.into_inner()
+    }
+}
+
+/// Converts a color given as hue (degrees), saturation and lightness (both 0 to 1).
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> Rgb<u8> {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |v: f32| ((v + m) * 255.0).round() as u8;
+    Rgb([channel(r), channel(g), channel(b)])
+}
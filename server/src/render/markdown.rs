@@ -0,0 +1,176 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
+
+use ammonia::Builder;
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+
+/// Tags allowed in rendered comments.
+const INLINE_TAGS: &[&str] = &["a", "code", "del", "em", "strong"];
+
+/// Tags allowed in rendered post bodies, on top of `INLINE_TAGS`.
+const BLOCK_TAGS: &[&str] = &[
+    "blockquote",
+    "br",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "img",
+    "input",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "table",
+    "tbody",
+    "td",
+    "th",
+    "thead",
+    "tr",
+    "ul",
+];
+
+static POST_SANITIZER: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let mut builder = sanitizer(INLINE_TAGS.iter().chain(BLOCK_TAGS));
+    builder
+        .add_tag_attributes("code", &["class"])
+        .add_tag_attributes("img", &["src", "alt", "title"])
+        .add_tag_attributes("input", &["checked"])
+        .set_tag_attribute_value("input", "type", "checkbox")
+        .set_tag_attribute_value("input", "disabled", "")
+        .add_tag_attributes("ol", &["start"])
+        // fenced code blocks get a `language-*` class; nothing else may set classes
+        .attribute_filter(|element, attribute, value| match (element, attribute) {
+            ("code", "class") if !value.starts_with("language-") => None,
+            _ => Some(value.into()),
+        });
+    builder
+});
+
+static COMMENT_SANITIZER: LazyLock<Builder<'static>> =
+    LazyLock::new(|| sanitizer(INLINE_TAGS.iter()));
+
+/// A sanitizer allowing only `tags`, links with safe schemes, and no other attributes.
+fn sanitizer(tags: impl Iterator<Item = &'static &'static str>) -> Builder<'static> {
+    let mut builder = Builder::empty();
+    builder
+        .tags(tags.copied().collect())
+        .generic_attributes(HashSet::new())
+        .tag_attributes(HashMap::from([("a", HashSet::from(["href", "title"]))]))
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .link_rel(Some("nofollow noopener noreferrer"));
+    builder
+}
+
+/// Markdown extensions enabled in post bodies.
+const POST_OPTIONS: Options = Options::ENABLE_TABLES
+    .union(Options::ENABLE_STRIKETHROUGH)
+    .union(Options::ENABLE_TASKLISTS);
+
+/// A fenced or indented code block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// The first word of a fenced block's info string, if any.
+    pub language: Option<String>,
+    pub code: String,
+}
+
+/// Renders a post body: CommonMark with GitHub's tables, strikethrough and task lists. Raw HTML
+/// is allowed but filtered down to the tags Markdown itself produces.
+pub fn render_post(text: &str) -> String {
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, Parser::new_ext(text, POST_OPTIONS));
+    POST_SANITIZER.clean(&html).to_string()
+}
+
+/// Renders a comment. Comments only get inline formatting: emphasis, strikethrough, code and
+/// links. Block structure is flattened into a single line of text.
+pub fn render_comment(text: &str) -> String {
+    let events =
+        Parser::new_ext(text, Options::ENABLE_STRIKETHROUGH).filter_map(|event| match event {
+            Event::Start(Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link { .. })
+            | Event::End(
+                TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough | TagEnd::Link,
+            ) => Some(event),
+            Event::Start(_) | Event::Rule | Event::TaskListMarker(_) => None,
+            // keep the text of neighbouring blocks apart
+            Event::End(_) | Event::HardBreak => Some(Event::SoftBreak),
+            event => Some(event),
+        });
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+    COMMENT_SANITIZER.clean(html.trim()).to_string()
+}
+
+/// The code blocks in `text`, in order.
+pub fn code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<CodeBlock> = None;
+    for event in Parser::new_ext(text, POST_OPTIONS) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split_whitespace().next().map(str::to_owned)
+                    }
+                    CodeBlockKind::Indented => None,
+                };
+                current = Some(CodeBlock {
+                    language,
+                    code: String::new(),
+                });
+            }
+            Event::Text(text) => {
+                if let Some(block) = &mut current {
+                    block.code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => blocks.extend(current.take()),
+            _ => {}
+        }
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ATTACKS: &[&str] = &[
+        "<script>alert(1)</script>",
+        "[link](javascript:alert(1))",
+        "<a href=\"javascript:alert(1)\">link</a>",
+        "<img src=\"x.png\" onerror=\"alert(1)\">",
+        "<em onmouseover=\"alert(1)\">text</em>",
+    ];
+
+    fn assert_safe(html: &str) {
+        for needle in ["<script", "javascript:", "onerror", "onmouseover", "alert"] {
+            assert!(!html.contains(needle), "{needle} kept in {html:?}");
+        }
+    }
+
+    #[test]
+    fn sanitizes_posts() {
+        for attack in ATTACKS {
+            assert_safe(&render_post(attack));
+        }
+        assert_eq!(
+            render_post("![a](x.png)"),
+            "<p><img src=\"x.png\" alt=\"a\"></p>\n"
+        );
+    }
+
+    #[test]
+    fn sanitizes_comments() {
+        for attack in ATTACKS {
+            assert_safe(&render_comment(attack));
+        }
+        assert_eq!(render_comment("*hi*"), "<em>hi</em>");
+    }
+}
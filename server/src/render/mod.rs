@@ -0,0 +1,6 @@
+//! Turning user-written text into HTML that's safe to show, and drawing the images shown in
+//! place of missing user content.
+
+pub mod code;
+pub mod identicon;
+pub mod markdown;
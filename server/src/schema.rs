@@ -1,6 +1,88 @@
 // @generated automatically by Diesel CLI.
 
+pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "audit_action"))]
+    pub struct AuditAction;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "close_reason"))]
+    pub struct CloseReason;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "close_vote_kind"))]
+    pub struct CloseVoteKind;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "flag_outcome"))]
+    pub struct FlagOutcome;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "flag_reason"))]
+    pub struct FlagReason;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "lock_type"))]
+    pub struct LockType;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "notification_kind"))]
+    pub struct NotificationKind;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "suggested_edit_status"))]
+    pub struct SuggestedEditStatus;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "user_role"))]
+    pub struct UserRole;
+}
+
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    attachments (id) {
+        id -> Int4,
+        user_id -> Int4,
+        post_id -> Nullable<Int4>,
+        #[max_length = 64]
+        storage_key -> Varchar,
+        #[max_length = 256]
+        filename -> Varchar,
+        #[max_length = 64]
+        content_type -> Varchar,
+        size -> Int4,
+        width -> Nullable<Int4>,
+        height -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+    use super::sql_types::AuditAction;
+
+    audit_log (id) {
+        id -> Int4,
+        actor_id -> Int4,
+        action -> AuditAction,
+        #[max_length = 32]
+        target_table -> Varchar,
+        target_id -> Int4,
+        before_state -> Nullable<Jsonb>,
+        after_state -> Nullable<Jsonb>,
+        #[max_length = 45]
+        ip -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     challenge_types (id) {
         id -> Int2,
         #[max_length = 32]
@@ -10,13 +92,53 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+    use super::sql_types::CloseReason;
+
     challenges (post_id) {
         post_id -> Int4,
         challenge_type_id -> Int2,
+        closed_at -> Nullable<Timestamp>,
+        close_reason -> Nullable<CloseReason>,
+        duplicate_of_id -> Nullable<Int4>,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+    use super::sql_types::CloseVoteKind;
+    use super::sql_types::CloseReason;
+
+    close_votes (id) {
+        id -> Int4,
+        challenge_id -> Int4,
+        voter_id -> Int4,
+        kind -> CloseVoteKind,
+        reason -> Nullable<CloseReason>,
+        duplicate_of_id -> Nullable<Int4>,
+        binding -> Bool,
+        created_at -> Timestamp,
+        spent_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    comment_votes (user_id, comment_id) {
+        user_id -> Int4,
+        comment_id -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     comments (id) {
         id -> Int4,
         post_id -> Int4,
@@ -25,10 +147,112 @@ diesel::table! {
         user_id -> Int4,
         created_at -> Timestamp,
         updated_at -> Nullable<Timestamp>,
+        search_vector -> Tsvector,
+        parent_comment_id -> Nullable<Int4>,
+        depth -> Int4,
+        score -> Int4,
+        body_html -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+    use super::sql_types::FlagReason;
+    use super::sql_types::FlagOutcome;
+
+    flags (id) {
+        id -> Int4,
+        flagger_id -> Int4,
+        post_id -> Nullable<Int4>,
+        comment_id -> Nullable<Int4>,
+        user_id -> Nullable<Int4>,
+        reason -> FlagReason,
+        #[max_length = 1000]
+        details -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        handler_id -> Nullable<Int4>,
+        outcome -> Nullable<FlagOutcome>,
+        #[max_length = 256]
+        resolution_note -> Nullable<Varchar>,
+        resolved_at -> Nullable<Timestamp>,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    mentions (id) {
+        id -> Int4,
+        user_id -> Int4,
+        post_id -> Nullable<Int4>,
+        comment_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+    use super::sql_types::NotificationKind;
+
+    notifications (id) {
+        id -> Int4,
+        user_id -> Int4,
+        kind -> NotificationKind,
+        post_id -> Int4,
+        actor_id -> Int4,
+        response_id -> Nullable<Int4>,
+        comment_id -> Nullable<Int4>,
+        event_count -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        read_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+    use super::sql_types::LockType;
+
+    post_locks (id) {
+        id -> Int4,
+        post_id -> Int4,
+        lock_type -> LockType,
+        #[max_length = 256]
+        reason -> Nullable<Varchar>,
+        locked_by_id -> Int4,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+        lifted_at -> Nullable<Timestamp>,
+        lifted_by_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    post_revisions (id) {
+        id -> Int4,
+        post_id -> Int4,
+        editor_id -> Int4,
+        title -> Text,
+        body -> Text,
+        code -> Nullable<Text>,
+        #[max_length = 256]
+        summary -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        body_html -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     posts (id) {
         id -> Int4,
         title -> Text,
@@ -37,18 +261,74 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Nullable<Timestamp>,
         deleted_at -> Nullable<Timestamp>,
+        search_vector -> Tsvector,
+        body_html -> Nullable<Text>,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     responses (post_id) {
         post_id -> Int4,
         challenge_id -> Int4,
         code -> Text,
+        #[max_length = 64]
+        language -> Nullable<Varchar>,
+        code_html -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+    use super::sql_types::SuggestedEditStatus;
+
+    suggested_edits (id) {
+        id -> Int4,
+        post_id -> Int4,
+        suggester_id -> Int4,
+        base_revision_id -> Int4,
+        title -> Nullable<Text>,
+        body -> Nullable<Text>,
+        #[max_length = 256]
+        comment -> Varchar,
+        status -> SuggestedEditStatus,
+        reviewer_id -> Nullable<Int4>,
+        #[max_length = 256]
+        review_comment -> Nullable<Varchar>,
+        revision_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+        reviewed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    user_suspensions (id) {
+        id -> Int4,
+        user_id -> Int4,
+        moderator_id -> Int4,
+        #[max_length = 256]
+        reason -> Varchar,
+        message -> Nullable<Text>,
+        created_at -> Timestamp,
+        ends_at -> Timestamp,
+        lifted_at -> Nullable<Timestamp>,
+        lifted_by_id -> Nullable<Int4>,
+        #[max_length = 256]
+        lift_note -> Nullable<Varchar>,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+    use super::sql_types::UserRole;
+
     users (id) {
         id -> Int4,
         name -> Varchar,
@@ -56,22 +336,58 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Nullable<Timestamp>,
         deleted_at -> Nullable<Timestamp>,
+        #[max_length = 255]
+        auth_subject -> Nullable<Varchar>,
+        role -> UserRole,
+        suspended_until -> Nullable<Timestamp>,
+        #[max_length = 64]
+        avatar_key -> Nullable<Varchar>,
     }
 }
 
+diesel::joinable!(attachments -> posts (post_id));
+diesel::joinable!(attachments -> users (user_id));
+diesel::joinable!(audit_log -> users (actor_id));
 diesel::joinable!(challenges -> challenge_types (challenge_type_id));
 diesel::joinable!(challenges -> posts (post_id));
+diesel::joinable!(close_votes -> users (voter_id));
+diesel::joinable!(comment_votes -> comments (comment_id));
+diesel::joinable!(comment_votes -> users (user_id));
 diesel::joinable!(comments -> posts (post_id));
 diesel::joinable!(comments -> users (user_id));
+diesel::joinable!(flags -> comments (comment_id));
+diesel::joinable!(flags -> posts (post_id));
+diesel::joinable!(mentions -> comments (comment_id));
+diesel::joinable!(mentions -> posts (post_id));
+diesel::joinable!(mentions -> users (user_id));
+diesel::joinable!(notifications -> comments (comment_id));
+diesel::joinable!(notifications -> posts (post_id));
+diesel::joinable!(notifications -> responses (response_id));
+diesel::joinable!(post_locks -> posts (post_id));
+diesel::joinable!(post_revisions -> posts (post_id));
+diesel::joinable!(post_revisions -> users (editor_id));
 diesel::joinable!(posts -> users (user_id));
 diesel::joinable!(responses -> challenges (challenge_id));
 diesel::joinable!(responses -> posts (post_id));
+diesel::joinable!(suggested_edits -> post_revisions (revision_id));
+diesel::joinable!(suggested_edits -> posts (post_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    attachments,
+    audit_log,
     challenge_types,
     challenges,
+    close_votes,
+    comment_votes,
     comments,
+    flags,
+    mentions,
+    notifications,
+    post_locks,
+    post_revisions,
     posts,
     responses,
+    suggested_edits,
+    user_suspensions,
     users,
 );
@@ -5,11 +5,14 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Serialize, Deserialize)]
 struct Claims {
     sub: String,
-    company: String,
-    exp: usize,
 }
 
-pub async fn validate_token(token: &str) -> Result<bool, ServiceError> {
+/// The `sub` claim of a validated token, stored in the request's extensions.
+#[derive(Debug, Clone)]
+pub struct Subject(pub String);
+
+/// Returns the token's subject, or `None` if the token is invalid.
+pub async fn validate_token(token: &str) -> Result<Option<Subject>, ServiceError> {
     let authority = std::env::var("AUTHORITY").expect("AUTHORITY must be set");
     let jwks = fetch_jwks(&format!(
         "{}{}",
@@ -19,17 +22,21 @@ pub async fn validate_token(token: &str) -> Result<bool, ServiceError> {
     .await
     .expect("failed to fetch jwks");
     let validations = vec![Validation::Issuer(authority), Validation::SubjectPresent];
-    let kid = match token_kid(&token) {
+    let kid = match token_kid(token) {
         Ok(res) => res.expect("failed to decode kid"),
         Err(_) => return Err(ServiceError::JWKSFetchError),
     };
     let jwk = jwks.find(&kid).expect("Specified key not found in set");
-    let res = validate(token, jwk, validations);
-    Ok(res.is_ok())
+    let Ok(valid) = validate(token, jwk, validations) else {
+        return Ok(None);
+    };
+    let claims: Claims = serde_json::from_value(valid.claims)
+        .map_err(|e| ServiceError::BadRequest(format!("invalid token claims: {e}")))?;
+    Ok(Some(Subject(claims.sub)))
 }
 
 async fn fetch_jwks(uri: &str) -> anyhow::Result<JWKS> {
-    let mut res = reqwest::get(uri).await?;
+    let res = reqwest::get(uri).await?;
     let val = res.json::<JWKS>().await?;
-    return Ok(val);
+    Ok(val)
 }
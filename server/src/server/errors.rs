@@ -11,8 +11,13 @@ pub enum ServiceError {
 
     #[display("JWKSFetchError")]
     JWKSFetchError,
+
+    #[display("Unauthorized")]
+    Unauthorized,
 }
 
+impl std::error::Error for ServiceError {}
+
 // impl ResponseError trait allows to convert our errors into http responses with appropriate data
 impl ResponseError for ServiceError {
     fn error_response(&self) -> HttpResponse {
@@ -24,6 +29,7 @@ impl ResponseError for ServiceError {
             ServiceError::JWKSFetchError => {
                 HttpResponse::InternalServerError().json("Could not fetch JWKS")
             }
+            ServiceError::Unauthorized => HttpResponse::Unauthorized().json("Unauthorized"),
         }
     }
 }
@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+
+use juniper::{
+    DefaultScalarValue, Definition, FieldError, GraphQLValue, InputValue, IntoFieldError,
+    Operation, OperationType, Selection, Value, graphql_value, http::GraphQLRequest,
+    parser::parse_document_source,
+};
+
+use crate::api::Schema;
+
+/// Arguments that limit how many items a field returns.
+const LIMIT_ARGUMENTS: [&str; 3] = ["first", "last", "count"];
+
+/// Page size assumed for fields that are queried without any of their [`LIMIT_ARGUMENTS`]
+/// and don't declare a default for them. No such field returns more items than this.
+const DEFAULT_PAGE_SIZE: i64 = 25;
+
+/// Static limits every operation must satisfy before it is executed.
+#[derive(Debug, Clone, Copy)]
+pub struct QueryLimits {
+    pub max_depth: usize,
+    pub max_aliases: usize,
+    pub max_cost: i64,
+    /// Most operations a single batched request may carry. Their summed cost must also stay
+    /// within `max_cost`.
+    pub max_batch_size: usize,
+    /// Most subscriptions a single WebSocket connection may have running at once.
+    pub max_subscriptions: usize,
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 10,
+            max_aliases: 30,
+            max_cost: 10_000,
+            max_batch_size: 10,
+            max_subscriptions: 20,
+        }
+    }
+}
+
+impl QueryLimits {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            max_depth: dotenvy::var("GRAPHQL_MAX_DEPTH")
+                .map(|v| v.parse().expect("GRAPHQL_MAX_DEPTH must be an integer"))
+                .unwrap_or(default.max_depth),
+            max_aliases: dotenvy::var("GRAPHQL_MAX_ALIASES")
+                .map(|v| v.parse().expect("GRAPHQL_MAX_ALIASES must be an integer"))
+                .unwrap_or(default.max_aliases),
+            max_cost: dotenvy::var("GRAPHQL_MAX_COST")
+                .map(|v| v.parse().expect("GRAPHQL_MAX_COST must be an integer"))
+                .unwrap_or(default.max_cost),
+            max_batch_size: dotenvy::var("GRAPHQL_MAX_BATCH_SIZE")
+                .map(|v| {
+                    v.parse()
+                        .expect("GRAPHQL_MAX_BATCH_SIZE must be an integer")
+                })
+                .unwrap_or(default.max_batch_size),
+            max_subscriptions: dotenvy::var("GRAPHQL_MAX_SUBSCRIPTIONS")
+                .map(|v| {
+                    v.parse()
+                        .expect("GRAPHQL_MAX_SUBSCRIPTIONS must be an integer")
+                })
+                .unwrap_or(default.max_subscriptions),
+        }
+    }
+
+    /// Analyzes the operation that `request` would execute. Requests that can't be parsed or
+    /// don't select a single operation are let through so that execution reports the error.
+    pub fn check(&self, schema: &Schema, request: &GraphQLRequest) -> Result<(), LimitExceeded> {
+        self.check_cost(schema, request).map(|_| ())
+    }
+
+    /// Like [`Self::check`], but returns the operation's cost, so that the costs of batched
+    /// operations can be added up.
+    pub fn check_cost(
+        &self,
+        schema: &Schema,
+        request: &GraphQLRequest,
+    ) -> Result<i64, LimitExceeded> {
+        let Ok(document) = parse_document_source(&request.query, &schema.schema) else {
+            return Ok(0);
+        };
+        let Some(operation) = find_operation(&document, request.operation_name.as_deref()) else {
+            return Ok(0);
+        };
+        let root_type = match operation.operation_type {
+            OperationType::Query => GraphQLValue::<DefaultScalarValue>::type_name(
+                &schema.query_type,
+                &schema.query_info,
+            ),
+            OperationType::Mutation => GraphQLValue::<DefaultScalarValue>::type_name(
+                &schema.mutation_type,
+                &schema.mutation_info,
+            ),
+            OperationType::Subscription => GraphQLValue::<DefaultScalarValue>::type_name(
+                &schema.subscription_type,
+                &schema.subscription_info,
+            ),
+        };
+
+        let mut analysis = Analysis {
+            schema,
+            fragments: document
+                .iter()
+                .filter_map(|def| match def {
+                    Definition::Fragment(f) => Some((f.item.name.item, &f.item)),
+                    Definition::Operation(_) => None,
+                })
+                .map(|(name, f)| (name, (f.type_condition.item, &f.selection_set[..])))
+                .collect(),
+            variables: request.variables(),
+            fragment_stack: Vec::new(),
+            fragment_costs: HashMap::new(),
+        };
+        let cost = analysis.visit(&operation.selection_set, root_type);
+
+        if cost.depth > self.max_depth {
+            return Err(LimitExceeded::Depth {
+                limit: self.max_depth,
+                actual: cost.depth,
+            });
+        }
+        if cost.aliases > self.max_aliases {
+            return Err(LimitExceeded::Aliases {
+                limit: self.max_aliases,
+                actual: cost.aliases,
+            });
+        }
+        if cost.cost > self.max_cost {
+            return Err(LimitExceeded::Cost {
+                limit: self.max_cost,
+                actual: cost.cost,
+            });
+        }
+        Ok(cost.cost)
+    }
+}
+
+/// Returns the type of the operation `request` would execute, if it parses and selects one.
+pub fn operation_type(schema: &Schema, request: &GraphQLRequest) -> Option<OperationType> {
+    let document = parse_document_source(&request.query, &schema.schema).ok()?;
+    find_operation(&document, request.operation_name.as_deref()).map(|op| op.operation_type)
+}
+
+fn find_operation<'a, 'd>(
+    document: &'d [Definition<'a, DefaultScalarValue>],
+    operation_name: Option<&str>,
+) -> Option<&'d Operation<'a, DefaultScalarValue>> {
+    let mut operations = document.iter().filter_map(|def| match def {
+        Definition::Operation(op) => Some(&op.item),
+        Definition::Fragment(_) => None,
+    });
+    match operation_name {
+        Some(name) => operations.find(|op| op.name.as_ref().map(|n| n.item) == Some(name)),
+        None => operations.next(),
+    }
+}
+
+type FragmentMap<'a, 'd> = HashMap<&'a str, (&'a str, &'d [Selection<'a, DefaultScalarValue>])>;
+
+/// What a selection set adds up to, as if it were selected once at the root: how many levels
+/// of fields it nests, how many aliases it uses and how many objects it may resolve.
+#[derive(Debug, Clone, Copy, Default)]
+struct Cost {
+    depth: usize,
+    aliases: usize,
+    cost: i64,
+}
+
+impl Cost {
+    fn add(&mut self, other: Cost) {
+        self.depth = self.depth.max(other.depth);
+        self.aliases = self.aliases.saturating_add(other.aliases);
+        self.cost = self.cost.saturating_add(other.cost);
+    }
+}
+
+struct Analysis<'a, 'd> {
+    schema: &'d Schema,
+    fragments: FragmentMap<'a, 'd>,
+    variables: juniper::Variables,
+    fragment_stack: Vec<&'a str>,
+    /// Each fragment's cost, worked out the first time it's spread. Expanding fragments again
+    /// at every spread would let a chain of fragments that each spread the next twice cost
+    /// exponential time to analyze.
+    fragment_costs: HashMap<&'a str, Cost>,
+}
+
+impl<'a, 'd> Analysis<'a, 'd> {
+    fn visit(
+        &mut self,
+        selections: &'d [Selection<'a, DefaultScalarValue>],
+        parent_type: Option<&str>,
+    ) -> Cost {
+        let mut total = Cost::default();
+        for selection in selections {
+            match selection {
+                Selection::Field(field) => {
+                    let field = &field.item;
+                    // introspection is answered from the schema without touching the database
+                    if field.name.item.starts_with("__") {
+                        continue;
+                    }
+                    let meta_field = parent_type
+                        .and_then(|t| self.schema.schema.concrete_type_by_name(t))
+                        .and_then(|t| t.field_by_name(field.name.item));
+                    let field_type = meta_field.map(|f| f.field_type.innermost_name().to_owned());
+                    // fields that return a page of items take `first`, `last` or `count`
+                    let limit_arguments: Vec<_> = meta_field
+                        .and_then(|f| f.arguments.as_deref())
+                        .unwrap_or_default()
+                        .iter()
+                        .filter(|arg| LIMIT_ARGUMENTS.contains(&arg.name.as_str()))
+                        .collect();
+                    let page_size = if limit_arguments.is_empty() {
+                        1
+                    } else {
+                        field
+                            .arguments
+                            .iter()
+                            .flat_map(|args| args.item.items.iter())
+                            .filter(|(name, _)| LIMIT_ARGUMENTS.contains(&name.item))
+                            .filter_map(|(_, value)| self.int_value(&value.item))
+                            .max()
+                            .or_else(|| {
+                                limit_arguments
+                                    .iter()
+                                    .filter_map(|arg| arg.default_value.as_ref())
+                                    .filter_map(|value| self.int_value(value))
+                                    .max()
+                            })
+                            .unwrap_or(DEFAULT_PAGE_SIZE)
+                    };
+                    let children = match &field.selection_set {
+                        Some(children) => self.visit(children, field_type.as_deref()),
+                        None => Cost::default(),
+                    };
+                    total.add(Cost {
+                        depth: children.depth + 1,
+                        aliases: children.aliases + usize::from(field.alias.is_some()),
+                        cost: children
+                            .cost
+                            .saturating_mul(page_size.max(1))
+                            .saturating_add(1),
+                    });
+                }
+                Selection::FragmentSpread(spread) => {
+                    let name = spread.item.name.item;
+                    if let Some(&cost) = self.fragment_costs.get(name) {
+                        total.add(cost);
+                        continue;
+                    }
+                    if self.fragment_stack.contains(&name) {
+                        continue;
+                    }
+                    let Some(&(type_condition, children)) = self.fragments.get(name) else {
+                        continue;
+                    };
+                    self.fragment_stack.push(name);
+                    let cost = self.visit(children, Some(type_condition));
+                    self.fragment_stack.pop();
+                    self.fragment_costs.insert(name, cost);
+                    total.add(cost);
+                }
+                Selection::InlineFragment(fragment) => {
+                    let fragment = &fragment.item;
+                    let type_condition = fragment.type_condition.as_ref().map(|t| t.item);
+                    let cost = self.visit(&fragment.selection_set, type_condition.or(parent_type));
+                    total.add(cost);
+                }
+            }
+        }
+        total
+    }
+
+    fn int_value(&self, value: &InputValue) -> Option<i64> {
+        match value {
+            InputValue::Variable(name) => self.variables.get(name)?.as_int_value(),
+            value => value.as_int_value(),
+        }
+        .map(i64::from)
+    }
+}
+
+/// The limit an operation was rejected for.
+#[derive(Debug, Clone, Copy)]
+pub enum LimitExceeded {
+    Depth {
+        limit: usize,
+        actual: usize,
+    },
+    Aliases {
+        limit: usize,
+        actual: usize,
+    },
+    Cost {
+        limit: i64,
+        actual: i64,
+    },
+    /// The operations of a batch are cheap enough on their own, but not together.
+    BatchCost {
+        limit: i64,
+        actual: i64,
+    },
+}
+
+impl IntoFieldError for LimitExceeded {
+    fn into_field_error(self) -> FieldError {
+        let (message, code, limit, actual) = match self {
+            LimitExceeded::Depth { limit, actual } => (
+                "query is nested too deeply",
+                "QUERY_TOO_DEEP",
+                i32::try_from(limit).unwrap_or(i32::MAX),
+                i32::try_from(actual).unwrap_or(i32::MAX),
+            ),
+            LimitExceeded::Aliases { limit, actual } => (
+                "query uses too many aliases",
+                "TOO_MANY_ALIASES",
+                i32::try_from(limit).unwrap_or(i32::MAX),
+                i32::try_from(actual).unwrap_or(i32::MAX),
+            ),
+            LimitExceeded::Cost { limit, actual } => (
+                "query is too expensive",
+                "QUERY_TOO_COMPLEX",
+                i32::try_from(limit).unwrap_or(i32::MAX),
+                i32::try_from(actual).unwrap_or(i32::MAX),
+            ),
+            LimitExceeded::BatchCost { limit, actual } => (
+                "batch is too expensive",
+                "BATCH_TOO_COMPLEX",
+                i32::try_from(limit).unwrap_or(i32::MAX),
+                i32::try_from(actual).unwrap_or(i32::MAX),
+            ),
+        };
+        let extensions: Value = graphql_value!({
+            "code": code,
+            "limit": limit,
+            "actual": actual,
+        });
+        FieldError::new(
+            format!("{message}: limit is {limit}, got {actual}"),
+            extensions,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use juniper::http::GraphQLRequest;
+
+    use super::*;
+
+    fn check(query: &str) -> Result<i64, LimitExceeded> {
+        let request = GraphQLRequest::new(query.to_owned(), None, None);
+        QueryLimits::default().check_cost(&crate::api::schema(), &request)
+    }
+
+    #[test]
+    fn rejects_deep_queries() {
+        let query = "{ viewer { challenges { edges { node { author { challenges { edges { node { \
+                     author { challenges { edges { node { id } } } } } } } } } } } } }";
+        assert!(matches!(
+            check(query),
+            Err(LimitExceeded::Depth {
+                limit: 10,
+                actual: 13
+            })
+        ));
+        assert!(check("{ viewer { challenges { edges { node { id } } } } }").is_ok());
+    }
+
+    #[test]
+    fn rejects_too_many_aliases() {
+        let aliases: String = (0..31).map(|i| format!("a{i}: viewer {{ id }} ")).collect();
+        assert!(matches!(
+            check(&format!("{{ {aliases} }}")),
+            Err(LimitExceeded::Aliases {
+                limit: 30,
+                actual: 31
+            })
+        ));
+    }
+
+    #[test]
+    fn multiplies_nested_page_sizes() {
+        let query = |first| {
+            format!(
+                "{{ viewer {{ challenges(first: {first}) {{ edges {{ node {{ \
+                 responses(first: {first}) {{ edges {{ node {{ id }} }} }} }} }} }} }} }}"
+            )
+        };
+        let small = check(&query(10)).unwrap();
+        assert!(small > 100);
+        assert!(matches!(
+            check(&query(100)),
+            Err(LimitExceeded::Cost { limit: 10_000, .. })
+        ));
+    }
+
+    #[test]
+    fn counts_fragments_at_every_spread() {
+        let inline = check(
+            "{ viewer { challenges(first: 50) { edges { node { id title } } } } \
+             other: viewer { challenges(first: 50) { edges { node { id title } } } } }",
+        )
+        .unwrap();
+        let spread = check(
+            "{ viewer { ...Challenges } other: viewer { ...Challenges } } \
+             fragment Challenges on User { challenges(first: 50) { edges { node { id title } } } }",
+        )
+        .unwrap();
+        assert_eq!(inline, spread);
+    }
+
+    #[test]
+    fn ignores_recursive_fragments() {
+        let query =
+            "{ viewer { ...A } } fragment A on User { id ...B } fragment B on User { ...A }";
+        assert!(check(query).is_ok());
+    }
+
+    #[test]
+    fn multiplies_list_fields_by_their_count() {
+        let query = |count| {
+            format!(
+                "{{ node(id: \"x\") {{ ... on Challenge {{ topComments(count: {count}) {{ \
+                 comments {{ author {{ challenges(first: 100) {{ edges {{ node {{ id }} }} }} \
+                 }} }} }} }} }} }}"
+            )
+        };
+        assert!(check(&query(1)).is_ok());
+        assert!(matches!(
+            check(&query(100)),
+            Err(LimitExceeded::Cost { limit: 10_000, .. })
+        ));
+    }
+
+    #[test]
+    fn multiplies_similar_challenges_by_their_maximum() {
+        let query = |first: &str, related_first: &str| {
+            format!(
+                "{{ similarChallenges(title: \"t\", body: \"b\"{first}) {{ challenge {{ \
+                 related{related_first} {{ challenge {{ responses(first: 100) {{ \
+                 edges {{ node {{ id }} }} }} }} }} }} }} }}"
+            )
+        };
+        assert!(check(&query(", first: 1", "(first: 1)")).unwrap() > 300);
+        assert!(matches!(
+            check(&query("", "")),
+            Err(LimitExceeded::Cost { limit: 10_000, .. })
+        ));
+    }
+}
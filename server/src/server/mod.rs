@@ -1,8 +1,13 @@
+use std::{io, sync::LazyLock, time::Duration};
+
 use actix_cors::Cors;
+use actix_multipart::Multipart;
 use actix_web::{
-    App, Error, HttpRequest, HttpResponse, HttpServer, Responder,
+    App, Error, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder,
     dev::ServiceRequest,
+    error::{self, JsonPayloadError},
     get,
+    guard::GuardContext,
     http::header,
     middleware, post,
     web::{self, Data},
@@ -10,13 +15,38 @@ use actix_web::{
 use actix_web_httpauth::extractors::AuthenticationError;
 use actix_web_httpauth::extractors::bearer::{BearerAuth, Config};
 use actix_web_httpauth::middleware::HttpAuthentication;
+use diesel::OptionalExtension;
 use dotenvy;
-use juniper_actix::{graphiql_handler, graphql_handler, playground_handler};
+use futures::future::join_all;
+use juniper::{
+    FieldError, IntoFieldError, OperationType, Variables,
+    http::{GraphQLBatchResponse, GraphQLRequest, GraphQLResponse},
+};
+use juniper_actix::{graphiql_handler, playground_handler};
+use juniper_graphql_ws::ConnectionConfig;
+use serde::Serialize;
 
 use crate::api::{Context, Schema, schema};
+use crate::db::{DB_EVENTS, DB_POOL};
+use crate::models;
+use crate::render::{code, identicon::Identicon};
+use crate::server::{
+    errors::ServiceError,
+    limits::{LimitExceeded, QueryLimits},
+    persisted::{GetRequest, PersistedBatchRequest, PersistedQueries, PersistedRequest},
+};
+use crate::storage::{STORAGE, avatars};
 
 mod auth;
-mod errors;
+pub mod errors;
+pub mod limits;
+pub mod multipart;
+pub mod persisted;
+mod tasks;
+mod ws;
+
+/// Keeps idle subscription connections open; GraphQL Playground gives up after 20 seconds.
+const WS_KEEP_ALIVE: Duration = Duration::from_secs(15);
 
 #[get("/")]
 async fn homepage() -> impl Responder {
@@ -30,51 +60,323 @@ async fn homepage() -> impl Responder {
         )
 }
 
-#[post("/api/grapghql")]
+async fn execute_persisted(
+    schema: &Schema,
+    ctx: &Context,
+    limits: &QueryLimits,
+    persisted: &PersistedQueries,
+    request: PersistedRequest,
+) -> GraphQLResponse {
+    let request = match persisted.resolve(request) {
+        Ok(request) => request,
+        Err(e) => return GraphQLResponse::error(e.into_field_error()),
+    };
+    match limits.check(schema, &request) {
+        Ok(()) => request.execute(schema, ctx).await,
+        Err(e) => GraphQLResponse::error(e.into_field_error()),
+    }
+}
+
+/// Executes a request, which may be a batch of operations. Batches are limited in length, and
+/// their operations' summed cost must be within the limit for any of them to run.
+async fn execute_batch(
+    schema: &Schema,
+    ctx: &Context,
+    limits: &QueryLimits,
+    persisted: &PersistedQueries,
+    request: PersistedBatchRequest,
+) -> Result<GraphQLBatchResponse, ServiceError> {
+    let rs = match request {
+        PersistedBatchRequest::Single(r) => {
+            return Ok(GraphQLBatchResponse::Single(
+                execute_persisted(schema, ctx, limits, persisted, r).await,
+            ));
+        }
+        PersistedBatchRequest::Batch(rs) => rs,
+    };
+    if rs.len() > limits.max_batch_size {
+        return Err(ServiceError::BadRequest(format!(
+            "at most {} operations may be batched",
+            limits.max_batch_size
+        )));
+    }
+    let mut requests: Vec<Result<(GraphQLRequest, i64), FieldError>> = rs
+        .into_iter()
+        .map(|r| {
+            let request = persisted.resolve(r).map_err(|e| e.into_field_error())?;
+            let cost = limits
+                .check_cost(schema, &request)
+                .map_err(|e| e.into_field_error())?;
+            Ok((request, cost))
+        })
+        .collect();
+    let total = requests
+        .iter()
+        .filter_map(|r| r.as_ref().ok())
+        .fold(0i64, |total, (_, cost)| total.saturating_add(*cost));
+    if total > limits.max_cost {
+        let error = LimitExceeded::BatchCost {
+            limit: limits.max_cost,
+            actual: total,
+        };
+        for r in requests.iter_mut().filter(|r| r.is_ok()) {
+            *r = Err(error.into_field_error());
+        }
+    }
+    Ok(GraphQLBatchResponse::Batch(
+        join_all(requests.into_iter().map(|r| async move {
+            match r {
+                Ok((request, _)) => request.execute(schema, ctx).await,
+                Err(e) => GraphQLResponse::error(e),
+            }
+        }))
+        .await,
+    ))
+}
+
+fn json_response<T: Serialize>(is_ok: bool, response: &T) -> Result<HttpResponse, Error> {
+    let mut builder = if is_ok {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::BadRequest()
+    };
+    Ok(builder
+        .content_type("application/json")
+        .body(serde_json::to_string(response)?))
+}
+
+#[post("/api/graphql")]
 async fn graphql(
+    req: HttpRequest,
+    body: String,
+    schema: Data<Schema>,
+    limits: Data<QueryLimits>,
+    persisted: Data<PersistedQueries>,
+) -> Result<HttpResponse, Error> {
+    let request = match req.content_type() {
+        "application/json" => serde_json::from_str::<PersistedBatchRequest>(&body)
+            .map_err(JsonPayloadError::Deserialize)?,
+        "application/graphql" => PersistedBatchRequest::Single(PersistedRequest {
+            query: Some(body),
+            operation_name: None,
+            variables: None,
+            extensions: None,
+        }),
+        _ => return Err(JsonPayloadError::ContentType.into()),
+    };
+    let ctx = request_context(&req)?;
+    let response = execute_batch(&schema, &ctx, &limits, &persisted, request).await?;
+    json_response(response.is_ok(), &response)
+}
+
+fn is_multipart(ctx: &GuardContext) -> bool {
+    ctx.header::<header::ContentType>()
+        .is_some_and(|content_type| content_type.essence_str() == "multipart/form-data")
+}
+
+/// Requests that upload files, sent as `multipart/form-data` per the GraphQL multipart request
+/// spec. Only the content type differs from [`graphql`].
+#[post("/api/graphql", guard = "is_multipart")]
+async fn graphql_multipart(
+    req: HttpRequest,
+    payload: Multipart,
+    schema: Data<Schema>,
+    limits: Data<QueryLimits>,
+    persisted: Data<PersistedQueries>,
+) -> Result<HttpResponse, Error> {
+    let (request, uploads) = multipart::read_request(payload).await?;
+    let ctx = request_context(&req)?.with_uploads(uploads);
+    let response = execute_batch(&schema, &ctx, &limits, &persisted, request).await?;
+    json_response(response.is_ok(), &response)
+}
+
+/// Queries sent over GET (typically by hash) can be cached by the browser. Mutations are refused
+/// since GET requests must not have side effects.
+#[get("/api/graphql")]
+async fn graphql_get(
+    req: HttpRequest,
+    query: web::Query<GetRequest>,
+    schema: Data<Schema>,
+    limits: Data<QueryLimits>,
+    persisted: Data<PersistedQueries>,
+) -> Result<HttpResponse, Error> {
+    let request = PersistedRequest::try_from(query.into_inner())
+        .map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+    let request = match persisted.resolve(request) {
+        Ok(request) => request,
+        Err(e) => {
+            let response = GraphQLResponse::error(e.into_field_error());
+            return json_response(response.is_ok(), &response);
+        }
+    };
+    if limits::operation_type(&schema, &request).is_some_and(|t| t != OperationType::Query) {
+        return Err(ServiceError::BadRequest("only queries may be sent over GET".into()).into());
+    }
+    let ctx = request_context(&req)?;
+    let response = match limits.check(&schema, &request) {
+        Ok(()) => request.execute(&schema, &ctx).await,
+        Err(e) => GraphQLResponse::error(e.into_field_error()),
+    };
+    let mut http_response = json_response(response.is_ok(), &response)?;
+    if response.is_ok() {
+        http_response.headers_mut().insert(
+            header::CACHE_CONTROL,
+            header::HeaderValue::from_static("private, max-age=60"),
+        );
+    }
+    Ok(http_response)
+}
+
+/// Serves subscriptions over the `graphql-transport-ws` protocol. Browsers can't set headers on
+/// WebSocket requests, so the bearer token is sent as `Authorization` in the `connection_init`
+/// payload instead. Each subscription is held to the same limits as HTTP requests.
+#[get("/api/graphql/ws")]
+async fn graphql_ws(
     req: HttpRequest,
     payload: web::Payload,
     schema: Data<Schema>,
+    limits: Data<QueryLimits>,
 ) -> Result<HttpResponse, Error> {
-    let ctx = Context::try_new().map_err(|e| Into::<Box<dyn std::error::Error>>::into(e))?;
-    graphql_handler(&schema, &ctx, req, payload).await
+    let limits = **limits;
+    ws::graphql_transport_ws_handler(
+        req,
+        payload,
+        schema.into_inner(),
+        limits,
+        move |params: Variables| async move {
+            let token = params
+                .get("Authorization")
+                .and_then(|v| v.as_string_value())
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .ok_or(ServiceError::Unauthorized)?;
+            let auth::Subject(subject) = auth::validate_token(token)
+                .await?
+                .ok_or(ServiceError::Unauthorized)?;
+            let ctx = Context::try_with_subject(Some(subject))
+                .map_err(|_| ServiceError::InternalServerError)?;
+            Ok::<_, ServiceError>(
+                ConnectionConfig::new(ctx)
+                    .with_keep_alive_interval(WS_KEEP_ALIVE)
+                    .with_max_in_flight_operations(limits.max_subscriptions),
+            )
+        },
+    )
+    .await
+}
+
+/// Long-lived caching for responses that never change.
+const CACHE_FOREVER: &str = "public, max-age=31536000, immutable";
+
+/// Serves uploaded files: attachments and avatars. They're public, like the posts and users
+/// they belong to, and never change, so they may be cached forever. The stored content type is
+/// sent, and browsers are told not to second-guess it or run anything the file contains.
+#[get("/attachments/{key}")]
+async fn attachment(key: web::Path<String>) -> Result<HttpResponse, Error> {
+    let content_type = if avatars::is_avatar_key(&key) {
+        "image/png".to_string()
+    } else {
+        let mut cnx = DB_POOL
+            .get()
+            .await
+            .map_err(|_| ServiceError::InternalServerError)?;
+        models::Attachment::find_by_key(&mut cnx, &key)
+            .await
+            .optional()
+            .map_err(|_| ServiceError::InternalServerError)?
+            .ok_or_else(|| error::ErrorNotFound("attachment not found"))?
+            .content_type
+    };
+    let data = STORAGE.get(&key).await.map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => error::ErrorNotFound("attachment not found"),
+        _ => ServiceError::InternalServerError.into(),
+    })?;
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .insert_header((
+            header::CONTENT_SECURITY_POLICY,
+            "default-src 'none'; sandbox",
+        ))
+        .insert_header((header::CACHE_CONTROL, CACHE_FOREVER))
+        .body(data))
+}
+
+/// A user's identicon as a PNG, in one of the standard avatar sizes. Identicons only depend on
+/// the user's id, so they're drawn on request and may be cached forever.
+#[get("/identicons/{size}/{user_id}.png")]
+async fn identicon_png(path: web::Path<(u32, i32)>) -> Result<HttpResponse, Error> {
+    let (size, user_id) = path.into_inner();
+    if !avatars::AVATAR_SIZES.contains(&size) {
+        return Err(error::ErrorNotFound("no identicons in that size"));
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("image/png")
+        .insert_header((header::CACHE_CONTROL, CACHE_FOREVER))
+        .body(Identicon::for_user(user_id).to_png(size)))
+}
+
+/// A user's identicon as an SVG, for any size.
+#[get("/identicons/{user_id}.svg")]
+async fn identicon_svg(user_id: web::Path<i32>) -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok()
+        .content_type("image/svg+xml")
+        .insert_header((header::CACHE_CONTROL, CACHE_FOREVER))
+        .body(Identicon::for_user(user_id.into_inner()).to_svg()))
 }
 
 #[get("/api/playground")]
 async fn playground() -> Result<HttpResponse, Error> {
-    playground_handler("/api/graphql", None).await
+    playground_handler("/api/graphql", Some("/api/graphql/ws")).await
 }
 
 #[get("/api/graphiql")]
 async fn graphiql() -> Result<HttpResponse, Error> {
-    graphiql_handler("/api/graphql", None).await
+    graphiql_handler("/api/graphql", Some("/api/graphql/ws")).await
 }
 
 async fn validator(
     req: ServiceRequest,
     credentials: BearerAuth,
 ) -> Result<ServiceRequest, (Error, ServiceRequest)> {
-    let config = req
-        .app_data::<Config>()
-        .map(|data| data.clone())
-        .unwrap_or_else(Default::default);
+    let config = req.app_data::<Config>().cloned().unwrap_or_default();
     match auth::validate_token(credentials.token()).await {
-        Ok(res) => {
-            if res == true {
-                Ok(req)
-            } else {
-                Err((AuthenticationError::from(config).into(), req))
-            }
+        Ok(Some(subject)) => {
+            req.extensions_mut().insert(subject);
+            Ok(req)
         }
-        Err(_) => Err((AuthenticationError::from(config).into(), req)),
+        Ok(None) | Err(_) => Err((AuthenticationError::from(config).into(), req)),
     }
 }
 
+/// Builds the context for a request that passed [`validator`], signed in as the token's subject.
+/// The recorded IP is the connection's peer, which is the proxy's if there's one in front.
+fn request_context(req: &HttpRequest) -> Result<Context, ServiceError> {
+    let subject = req
+        .extensions()
+        .get::<auth::Subject>()
+        .map(|auth::Subject(subject)| subject.clone());
+    Ok(Context::try_with_subject(subject)
+        .map_err(|_| ServiceError::InternalServerError)?
+        .with_ip(req.peer_addr().map(|addr| addr.ip().to_string())))
+}
+
 pub async fn serve() -> std::io::Result<()> {
-    HttpServer::new(|| {
+    let limits = Data::new(QueryLimits::from_env());
+    let persisted = Data::new(PersistedQueries::from_env());
+    actix_web::rt::spawn(
+        DB_EVENTS
+            .clone()
+            .listen(dotenvy::var("DATABASE_URL").expect("DATABASE_URL must be set")),
+    );
+    actix_web::rt::spawn(tasks::lift_expired_locks());
+    // load grammars up front, so a broken SYNTAX_DIR stops startup instead of a request
+    LazyLock::force(&code::SYNTAXES);
+    HttpServer::new(move || {
         let auth = HttpAuthentication::bearer(validator);
         App::new()
             .app_data(Data::new(schema()))
+            .app_data(limits.clone())
+            .app_data(persisted.clone())
             .wrap(
                 Cors::default()
                     .allow_any_origin()
@@ -86,11 +388,20 @@ pub async fn serve() -> std::io::Result<()> {
             )
             .wrap(middleware::Compress::default())
             .wrap(middleware::Logger::default())
-            .wrap(auth)
-            .service(graphql)
-            .service(graphiql)
-            .service(playground)
-            .service(homepage)
+            .service(graphql_ws)
+            .service(attachment)
+            .service(identicon_png)
+            .service(identicon_svg)
+            .service(
+                web::scope("")
+                    .wrap(auth)
+                    .service(graphql_multipart)
+                    .service(graphql)
+                    .service(graphql_get)
+                    .service(graphiql)
+                    .service(playground)
+                    .service(homepage),
+            )
     })
     .bind({
         dotenvy::dotenv().expect("could not load environment vars from .env");
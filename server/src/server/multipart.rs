@@ -0,0 +1,115 @@
+//! Reads GraphQL requests sent as `multipart/form-data`, following the GraphQL multipart request
+//! spec (<https://github.com/jaydenseric/graphql-multipart-request-spec>): an `operations` field
+//! holding the usual JSON request with `null` in place of each file, a `map` field saying which
+//! file goes where, then the files.
+
+use std::collections::HashMap;
+
+use actix_multipart::{Field, Multipart};
+use futures::TryStreamExt;
+use serde_json::Value;
+
+use crate::api::upload::UploadedFile;
+use crate::server::{errors::ServiceError, persisted::PersistedBatchRequest};
+use crate::storage::files::UPLOAD_MAX_BYTES;
+
+/// Largest `operations` or `map` field accepted, in bytes.
+const MAX_JSON_FIELD_BYTES: usize = 256 * 1024;
+
+/// Most files accepted in a single request.
+const MAX_FILES: usize = 10;
+
+/// Reads the request's operations and files. Each file's form field name is put in place of the
+/// `null`s it's mapped to, which the `Upload` scalar then resolves through the context.
+pub async fn read_request(
+    mut payload: Multipart,
+) -> Result<(PersistedBatchRequest, HashMap<String, UploadedFile>), ServiceError> {
+    let mut operations = match payload.try_next().await.map_err(bad_request)? {
+        Some(field) if field.name() == Some("operations") => read_json(field, "operations").await?,
+        _ => return Err(bad_request("the first field must be `operations`")),
+    };
+    let map: HashMap<String, Vec<String>> = match payload.try_next().await.map_err(bad_request)? {
+        Some(field) if field.name() == Some("map") => {
+            serde_json::from_value(read_json(field, "map").await?).map_err(bad_request)?
+        }
+        _ => return Err(bad_request("the second field must be `map`")),
+    };
+    if map.len() > MAX_FILES {
+        return Err(bad_request(format!(
+            "at most {MAX_FILES} files may be sent"
+        )));
+    }
+    for (name, paths) in &map {
+        for path in paths {
+            let slot = find_slot(&mut operations, path)
+                .ok_or_else(|| bad_request(format!("`map` path {path} doesn't exist")))?;
+            if !slot.is_null() {
+                return Err(bad_request(format!("`map` path {path} isn't null")));
+            }
+            *slot = Value::String(name.clone());
+        }
+    }
+
+    let mut uploads = HashMap::new();
+    while let Some(field) = payload.try_next().await.map_err(bad_request)? {
+        let name = field.name().unwrap_or_default().to_owned();
+        if !map.contains_key(&name) || uploads.contains_key(&name) {
+            return Err(bad_request(format!("unexpected field `{name}`")));
+        }
+        let filename = field
+            .content_disposition()
+            .and_then(|cd| cd.get_filename())
+            .map(str::to_owned);
+        let data = read_bytes(field, *UPLOAD_MAX_BYTES).await?.ok_or_else(|| {
+            bad_request(format!(
+                "file `{name}` must be at most {} bytes",
+                *UPLOAD_MAX_BYTES
+            ))
+        })?;
+        uploads.insert(name, UploadedFile { filename, data });
+    }
+    if let Some(name) = map.keys().find(|name| !uploads.contains_key(*name)) {
+        return Err(bad_request(format!("file `{name}` is missing")));
+    }
+
+    let request = serde_json::from_value(operations).map_err(bad_request)?;
+    Ok((request, uploads))
+}
+
+/// The value at a `.`-separated path of object keys and array indices, e.g.
+/// `0.variables.files.1` in a batch.
+fn find_slot<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.')
+        .try_fold(value, |value, segment| match value {
+            Value::Object(object) => object.get_mut(segment),
+            Value::Array(array) => array.get_mut(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+}
+
+async fn read_json(field: Field, name: &str) -> Result<Value, ServiceError> {
+    let data = read_bytes(field, MAX_JSON_FIELD_BYTES)
+        .await?
+        .ok_or_else(|| {
+            bad_request(format!(
+                "`{name}` must be at most {MAX_JSON_FIELD_BYTES} bytes"
+            ))
+        })?;
+    serde_json::from_slice(&data).map_err(bad_request)
+}
+
+/// Reads the whole field, or returns `None` if it's longer than `max_bytes`.
+async fn read_bytes(mut field: Field, max_bytes: usize) -> Result<Option<Vec<u8>>, ServiceError> {
+    let mut data = Vec::new();
+    while let Some(chunk) = field.try_next().await.map_err(bad_request)? {
+        if data.len() + chunk.len() > max_bytes {
+            return Ok(None);
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(Some(data))
+}
+
+fn bad_request(message: impl ToString) -> ServiceError {
+    ServiceError::BadRequest(message.to_string())
+}
@@ -0,0 +1,226 @@
+use std::{collections::HashMap, fmt::Display, fs, num::NonZeroUsize, sync::Mutex};
+
+use juniper::{FieldError, InputValue, IntoFieldError, Value, graphql_value, http::GraphQLRequest};
+use lru::LruCache;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+/// Upper bound on automatically registered queries, so that clients can't grow the cache
+/// without limit. Beyond it, the least recently used query is forgotten to make room, so junk
+/// registrations can't lock out the queries clients actually use.
+const MAX_REGISTERED_QUERIES: NonZeroUsize = NonZeroUsize::new(4096).unwrap();
+
+/// A GraphQL request that may reference its query by hash instead of sending it inline.
+#[derive(Debug, Deserialize)]
+pub struct PersistedRequest {
+    pub query: Option<String>,
+    #[serde(rename = "operationName")]
+    pub operation_name: Option<String>,
+    pub variables: Option<InputValue>,
+    pub extensions: Option<RequestExtensions>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum PersistedBatchRequest {
+    Single(PersistedRequest),
+    Batch(Vec<PersistedRequest>),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RequestExtensions {
+    #[serde(rename = "persistedQuery")]
+    pub persisted_query: Option<PersistedQueryExtension>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PersistedQueryExtension {
+    pub version: i32,
+    #[serde(rename = "sha256Hash")]
+    pub sha256_hash: String,
+}
+
+/// The query string parameters of a GET request. `variables` and `extensions` are JSON encoded.
+#[derive(Debug, Deserialize)]
+pub struct GetRequest {
+    query: Option<String>,
+    #[serde(rename = "operationName")]
+    operation_name: Option<String>,
+    variables: Option<String>,
+    extensions: Option<String>,
+}
+
+impl TryFrom<GetRequest> for PersistedRequest {
+    type Error = serde_json::Error;
+
+    fn try_from(value: GetRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            query: value.query,
+            operation_name: value.operation_name,
+            variables: value
+                .variables
+                .map(|v| serde_json::from_str(&v))
+                .transpose()?,
+            extensions: value
+                .extensions
+                .map(|e| serde_json::from_str(&e))
+                .transpose()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Clients may register any query by sending it together with its hash.
+    Automatic,
+    /// Only the queries in the manifest loaded at startup may be executed.
+    Allowlist,
+}
+
+/// Store of queries known by their sha256 hash.
+pub struct PersistedQueries {
+    mode: Mode,
+    /// Unbounded for an allowlist, which never grows after startup.
+    queries: Mutex<LruCache<String, String>>,
+}
+
+impl PersistedQueries {
+    pub fn automatic() -> Self {
+        Self {
+            mode: Mode::Automatic,
+            queries: Mutex::new(LruCache::new(MAX_REGISTERED_QUERIES)),
+        }
+    }
+
+    /// Restricts execution to the queries in `manifest`, a JSON object mapping each query's
+    /// hex-encoded sha256 hash to its text.
+    pub fn allowlist(manifest: HashMap<String, String>) -> anyhow::Result<Self> {
+        for (hash, query) in &manifest {
+            if sha256_hex(query) != hash.to_lowercase() {
+                anyhow::bail!("persisted query manifest entry {hash} does not match its query");
+            }
+        }
+        let mut queries = LruCache::unbounded();
+        for (hash, query) in manifest {
+            queries.put(hash.to_lowercase(), query);
+        }
+        Ok(Self {
+            mode: Mode::Allowlist,
+            queries: Mutex::new(queries),
+        })
+    }
+
+    pub fn from_env() -> Self {
+        match dotenvy::var("GRAPHQL_PERSISTED_QUERIES") {
+            Ok(path) => {
+                let manifest = fs::read_to_string(&path)
+                    .expect("could not read GRAPHQL_PERSISTED_QUERIES manifest");
+                Self::allowlist(
+                    serde_json::from_str(&manifest)
+                        .expect("GRAPHQL_PERSISTED_QUERIES must be a JSON object of hash to query"),
+                )
+                .expect("invalid GRAPHQL_PERSISTED_QUERIES manifest")
+            }
+            Err(_) => Self::automatic(),
+        }
+    }
+
+    fn get(&self, hash: &str) -> Option<String> {
+        self.queries.lock().unwrap().get(hash).cloned()
+    }
+
+    fn register(&self, hash: String, query: &str) -> Result<(), PersistedQueryError> {
+        match self.mode {
+            Mode::Allowlist if self.get(&hash).is_none() => Err(PersistedQueryError::NotAllowed),
+            Mode::Allowlist => Ok(()),
+            Mode::Automatic => {
+                let mut queries = self.queries.lock().unwrap();
+                if queries.get(&hash).is_none() {
+                    queries.put(hash, query.to_owned());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Turns `request` into an executable request, looking up or registering its query.
+    pub fn resolve(
+        &self,
+        request: PersistedRequest,
+    ) -> Result<GraphQLRequest, PersistedQueryError> {
+        let PersistedRequest {
+            query,
+            operation_name,
+            variables,
+            extensions,
+        } = request;
+        let hash = extensions
+            .and_then(|e| e.persisted_query)
+            .map(|pq| match pq.version {
+                1 => Ok(pq.sha256_hash.to_lowercase()),
+                v => Err(PersistedQueryError::UnsupportedVersion(v)),
+            })
+            .transpose()?;
+        let query = match (hash, query) {
+            (Some(hash), Some(query)) => {
+                if sha256_hex(&query) != hash {
+                    return Err(PersistedQueryError::HashMismatch);
+                }
+                self.register(hash, &query)?;
+                query
+            }
+            (Some(hash), None) => self.get(&hash).ok_or(PersistedQueryError::NotFound)?,
+            (None, Some(query)) => {
+                if self.mode == Mode::Allowlist && self.get(&sha256_hex(&query)).is_none() {
+                    return Err(PersistedQueryError::NotAllowed);
+                }
+                query
+            }
+            (None, None) => return Err(PersistedQueryError::MissingQuery),
+        };
+        Ok(GraphQLRequest::new(query, operation_name, variables))
+    }
+}
+
+fn sha256_hex(query: &str) -> String {
+    format!("{:x}", Sha256::digest(query.as_bytes()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersistedQueryError {
+    /// The hash isn't known yet; the client should retry with the full query.
+    NotFound,
+    HashMismatch,
+    NotAllowed,
+    UnsupportedVersion(i32),
+    MissingQuery,
+}
+
+impl Display for PersistedQueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            // clients match on this exact message to trigger the registration round-trip
+            Self::NotFound => write!(f, "PersistedQueryNotFound"),
+            Self::HashMismatch => write!(f, "provided sha256Hash does not match query"),
+            Self::NotAllowed => write!(f, "query is not in the persisted query allowlist"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported persisted query version: {v}"),
+            Self::MissingQuery => write!(f, "request has neither a query nor a persisted query"),
+        }
+    }
+}
+
+impl std::error::Error for PersistedQueryError {}
+
+impl IntoFieldError for PersistedQueryError {
+    fn into_field_error(self) -> FieldError {
+        let code = match self {
+            PersistedQueryError::NotFound => "PERSISTED_QUERY_NOT_FOUND",
+            PersistedQueryError::HashMismatch => "PERSISTED_QUERY_HASH_MISMATCH",
+            PersistedQueryError::NotAllowed => "PERSISTED_QUERY_NOT_ALLOWED",
+            PersistedQueryError::UnsupportedVersion(_) => "PERSISTED_QUERY_UNSUPPORTED_VERSION",
+            PersistedQueryError::MissingQuery => "BAD_REQUEST",
+        };
+        let extensions: Value = graphql_value!({ "code": code });
+        FieldError::new(self, extensions)
+    }
+}
@@ -0,0 +1,32 @@
+use std::{sync::LazyLock, time::Duration};
+
+use crate::{db::DB_POOL, models::PostLock};
+
+/// How often expired post locks are lifted.
+static LOCK_EXPIRY_INTERVAL: LazyLock<Duration> = LazyLock::new(|| {
+    Duration::from_secs(
+        dotenvy::var("LOCK_EXPIRY_INTERVAL_SECS")
+            .map(|v| {
+                v.parse()
+                    .expect("LOCK_EXPIRY_INTERVAL_SECS must be an integer")
+            })
+            .unwrap_or(60),
+    )
+});
+
+/// Marks post locks as lifted once they expire. Runs forever.
+pub async fn lift_expired_locks() {
+    let mut interval = actix_web::rt::time::interval(*LOCK_EXPIRY_INTERVAL);
+    loop {
+        interval.tick().await;
+        let lifted = match DB_POOL.get().await {
+            Ok(mut cnx) => PostLock::lift_expired(&mut cnx, None)
+                .await
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = lifted {
+            log::error!("lifting expired post locks failed: {e}");
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Serves subscriptions over the `graphql-transport-ws` protocol. This mirrors
+//! `juniper_actix::subscriptions::graphql_transport_ws_handler`, but checks each `subscribe`
+//! message against the [`QueryLimits`] before it reaches the connection, since
+//! `juniper_graphql_ws` has no hook for that.
+
+use std::{pin::pin, sync::Arc};
+
+use actix_web::{
+    HttpRequest, HttpResponse,
+    http::header::{HeaderName, HeaderValue},
+    web,
+};
+use derive_more::Display;
+use futures::{SinkExt, StreamExt, future};
+use juniper::{
+    DefaultScalarValue, InputValue, IntoFieldError, Spanning,
+    http::{GraphQLRequest, GraphQLResponse},
+};
+use juniper_graphql_ws::{
+    ArcSchema, Init,
+    graphql_transport_ws::{ClientMessage, Connection, Input, Output},
+};
+
+use crate::api::{Context, Schema};
+use crate::server::limits::QueryLimits;
+
+pub async fn graphql_transport_ws_handler<I>(
+    req: HttpRequest,
+    stream: web::Payload,
+    schema: Arc<Schema>,
+    limits: QueryLimits,
+    init: I,
+) -> Result<HttpResponse, actix_web::Error>
+where
+    I: Init<DefaultScalarValue, Context> + Send,
+{
+    let (mut resp, mut ws_tx, mut ws_rx) = actix_ws::handle(&req, stream)?;
+    let (mut s_tx, mut s_rx) = Connection::new(ArcSchema(schema.clone()), init).split();
+    let mut replies = ws_tx.clone();
+
+    actix_web::rt::spawn(async move {
+        let input = pin!(async move {
+            while let Some(Ok(msg)) = ws_rx.next().await {
+                let msg = Incoming::from(msg);
+                if let Ok(Input::Message(ClientMessage::Subscribe { id, payload })) = &msg.0 {
+                    let variables = payload
+                        .variables
+                        .iter()
+                        .map(|(k, v)| {
+                            (
+                                Spanning::unlocated(k.clone()),
+                                Spanning::unlocated(v.clone()),
+                            )
+                        })
+                        .collect();
+                    let request = GraphQLRequest::new(
+                        payload.query.clone(),
+                        payload.operation_name.clone(),
+                        Some(InputValue::Object(variables)),
+                    );
+                    if let Err(e) = limits.check(&schema, &request) {
+                        // An `error` message ends the operation, so it never reaches the
+                        // connection.
+                        let response = GraphQLResponse::error(e.into_field_error());
+                        let reply = serde_json::json!({
+                            "type": "error",
+                            "id": id,
+                            "payload": serde_json::to_value(&response)
+                                .map(|mut r| r["errors"].take())
+                                .unwrap_or_default(),
+                        });
+                        if replies.text(reply.to_string()).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                }
+                if s_tx.send(msg).await.is_err() {
+                    return;
+                }
+            }
+        });
+        let output = pin!(async move {
+            while let Some(output) = s_rx.next().await {
+                match output {
+                    Output::Message(msg) => match serde_json::to_string(&msg) {
+                        Ok(m) => {
+                            if ws_tx.text(m).await.is_err() {
+                                return;
+                            }
+                        }
+                        Err(e) => {
+                            _ = ws_tx
+                                .close(Some(actix_ws::CloseReason {
+                                    code: actix_ws::CloseCode::Error,
+                                    description: Some(format!("error serializing response: {e}")),
+                                }))
+                                .await;
+                            return;
+                        }
+                    },
+                    Output::Close { code, message } => {
+                        _ = ws_tx
+                            .close(Some(actix_ws::CloseReason {
+                                code: code.into(),
+                                description: Some(message),
+                            }))
+                            .await;
+                        return;
+                    }
+                }
+            }
+            _ = ws_tx
+                .close(Some((actix_ws::CloseCode::Normal, "Normal Closure").into()))
+                .await;
+        });
+
+        _ = future::select(input, output).await;
+    });
+
+    resp.headers_mut().insert(
+        HeaderName::from_static("sec-websocket-protocol"),
+        HeaderValue::from_static("graphql-transport-ws"),
+    );
+    Ok(resp)
+}
+
+/// A message from the client, parsed up front so `subscribe` messages can be checked. Parse
+/// errors are handed to the connection, which closes with the matching code.
+struct Incoming(Result<Input<DefaultScalarValue>, MessageError>);
+
+impl From<actix_ws::Message> for Incoming {
+    fn from(msg: actix_ws::Message) -> Self {
+        Self(match msg {
+            actix_ws::Message::Text(text) => serde_json::from_slice(text.as_bytes())
+                .map(Input::Message)
+                .map_err(MessageError::Serde),
+            actix_ws::Message::Binary(bytes) => serde_json::from_slice(bytes.as_ref())
+                .map(Input::Message)
+                .map_err(MessageError::Serde),
+            actix_ws::Message::Close(_) => Ok(Input::Close),
+            other => Err(MessageError::Unexpected(other)),
+        })
+    }
+}
+
+impl TryFrom<Incoming> for Input<DefaultScalarValue> {
+    type Error = MessageError;
+
+    fn try_from(msg: Incoming) -> Result<Self, Self::Error> {
+        msg.0
+    }
+}
+
+#[derive(Debug, Display)]
+enum MessageError {
+    #[display("serde error: {}", _0)]
+    Serde(serde_json::Error),
+
+    #[display("unexpected message received from client: {:?}", _0)]
+    Unexpected(actix_ws::Message),
+}
+
+impl std::error::Error for MessageError {}
@@ -0,0 +1,93 @@
+use std::io;
+
+use futures::future::join_all;
+use image::{DynamicImage, ImageFormat, imageops::FilterType};
+
+use crate::storage::{
+    STORAGE,
+    files::{self, FileError},
+};
+
+/// The sizes avatars are stored and served in, in pixels, smallest first. Avatars are square.
+pub const AVATAR_SIZES: [u32; 4] = [32, 64, 128, 256];
+
+/// The smallest standard size at least as big as `requested`, or the biggest if none is.
+pub fn standard_size(requested: u32) -> u32 {
+    AVATAR_SIZES
+        .into_iter()
+        .find(|&size| size >= requested)
+        .unwrap_or(AVATAR_SIZES[AVATAR_SIZES.len() - 1])
+}
+
+/// The storage key of the given size of the avatar stored under `avatar_key`.
+pub fn key(avatar_key: &str, size: u32) -> String {
+    format!("{avatar_key}.{size}.png")
+}
+
+/// Whether `key` is one made by [`key`], so the file under it is a PNG avatar.
+pub fn is_avatar_key(key: &str) -> bool {
+    let mut parts = key.split('.');
+    matches!(
+        (parts.next(), parts.next(), parts.next(), parts.next()),
+        (Some(id), Some(size), Some("png"), None)
+            if !id.is_empty() && size.parse().is_ok_and(|size| AVATAR_SIZES.contains(&size))
+    )
+}
+
+/// Crops an uploaded image to a centered square and scales it to each standard size, encoded
+/// as PNGs in the order of [`AVATAR_SIZES`]. Like attachments, the image's metadata is dropped.
+pub fn resize(data: &[u8]) -> Result<Vec<Vec<u8>>, FileError> {
+    let image = files::decode_image(data)?;
+    let side = image.width().min(image.height());
+    let square = image.crop_imm(
+        (image.width() - side) / 2,
+        (image.height() - side) / 2,
+        side,
+        side,
+    );
+    AVATAR_SIZES
+        .into_iter()
+        .map(|size| {
+            let mut out = io::Cursor::new(Vec::new());
+            DynamicImage::from(
+                square
+                    .resize_exact(size, size, FilterType::Lanczos3)
+                    .to_rgba8(),
+            )
+            .write_to(&mut out, ImageFormat::Png)?;
+            Ok(out.into_inner())
+        })
+        .collect()
+}
+
+/// Stores the images [`resize`] made, returning the new avatar's key. If any fails to store,
+/// the others are removed again.
+pub async fn store(images: &[Vec<u8>]) -> io::Result<String> {
+    let avatar_key = uuid::Uuid::new_v4().simple().to_string();
+    let results = join_all(
+        AVATAR_SIZES
+            .into_iter()
+            .zip(images)
+            .map(|(size, data)| (key(&avatar_key, size), data))
+            .map(|(key, data)| async move { STORAGE.put(&key, data).await }),
+    )
+    .await;
+    match results.into_iter().find_map(Result::err) {
+        Some(e) => {
+            delete(&avatar_key).await;
+            Err(e)
+        }
+        None => Ok(avatar_key),
+    }
+}
+
+/// Removes every size of the avatar stored under `avatar_key`, as far as possible. A file left
+/// behind is only wasted space, so failures are ignored.
+pub async fn delete(avatar_key: &str) {
+    join_all(
+        AVATAR_SIZES
+            .into_iter()
+            .map(|size| async move { STORAGE.delete(&key(avatar_key, size)).await }),
+    )
+    .await;
+}
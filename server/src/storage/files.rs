@@ -0,0 +1,162 @@
+use std::{fmt::Display, io::Cursor, sync::LazyLock};
+
+use image::{
+    DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageReader, Limits,
+    codecs::jpeg::JpegEncoder,
+};
+
+/// Largest file accepted, in bytes. Read from `UPLOAD_MAX_BYTES`.
+pub static UPLOAD_MAX_BYTES: LazyLock<usize> = LazyLock::new(|| {
+    dotenvy::var("UPLOAD_MAX_BYTES")
+        .map(|v| v.parse().expect("UPLOAD_MAX_BYTES must be an integer"))
+        .unwrap_or(5 * 1024 * 1024)
+});
+
+/// Widest and tallest image accepted, in pixels, so small files can't decode into huge images.
+const MAX_IMAGE_DIMENSION: u32 = 8192;
+
+const JPEG_QUALITY: u8 = 90;
+
+/// Length of the random part of storage keys, in hex digits.
+const KEY_ID_LENGTH: usize = 32;
+
+/// An uploaded file that's been checked and cleaned, ready to be stored.
+#[derive(Debug, Clone)]
+pub struct CleanFile {
+    pub data: Vec<u8>,
+    pub content_type: &'static str,
+    pub extension: &'static str,
+    /// Width and height, for images.
+    pub dimensions: Option<(u32, u32)>,
+}
+
+#[derive(Debug)]
+pub enum FileError {
+    Empty,
+    TooLarge,
+    UnsupportedType,
+    /// Something other than an image where only images are accepted.
+    NotAnImage,
+    InvalidImage(ImageError),
+}
+
+impl Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => f.write_str("file is empty"),
+            Self::TooLarge => write!(f, "file must be at most {} bytes", *UPLOAD_MAX_BYTES),
+            Self::UnsupportedType => {
+                f.write_str("only PNG, JPEG, GIF and WebP images and UTF-8 text are accepted")
+            }
+            Self::NotAnImage => f.write_str("only PNG, JPEG, GIF and WebP images are accepted"),
+            Self::InvalidImage(e) => write!(f, "invalid image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
+
+impl From<ImageError> for FileError {
+    fn from(value: ImageError) -> Self {
+        Self::InvalidImage(value)
+    }
+}
+
+/// Works out what an upload is from its contents, ignoring whatever type the client claimed, and
+/// cleans it. Images are decoded and re-encoded, which drops EXIF and any other metadata after
+/// applying the orientation it recorded. JPEGs stay JPEGs; other images become PNGs, keeping only
+/// the first frame of animations. Text must be UTF-8 and is kept as is.
+pub fn clean(data: &[u8]) -> Result<CleanFile, FileError> {
+    if data.is_empty() {
+        return Err(FileError::Empty);
+    }
+    if data.len() > *UPLOAD_MAX_BYTES {
+        return Err(FileError::TooLarge);
+    }
+    match image::guess_format(data) {
+        Ok(
+            format @ (ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP),
+        ) => clean_image(data, format),
+        Ok(_) => Err(FileError::UnsupportedType),
+        Err(_) if std::str::from_utf8(data).is_ok_and(|text| !text.contains('\0')) => {
+            Ok(CleanFile {
+                data: data.to_vec(),
+                content_type: "text/plain; charset=utf-8",
+                extension: "txt",
+                dimensions: None,
+            })
+        }
+        Err(_) => Err(FileError::UnsupportedType),
+    }
+}
+
+/// Decodes an uploaded image, checking it the way [`clean`] does, with the orientation its EXIF
+/// data recorded applied. Anything but a PNG, JPEG, GIF or WebP image is refused.
+pub fn decode_image(data: &[u8]) -> Result<DynamicImage, FileError> {
+    if data.is_empty() {
+        return Err(FileError::Empty);
+    }
+    if data.len() > *UPLOAD_MAX_BYTES {
+        return Err(FileError::TooLarge);
+    }
+    match image::guess_format(data) {
+        Ok(
+            format @ (ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP),
+        ) => decode(data, format),
+        _ => Err(FileError::NotAnImage),
+    }
+}
+
+fn decode(data: &[u8], format: ImageFormat) -> Result<DynamicImage, FileError> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+fn clean_image(data: &[u8], format: ImageFormat) -> Result<CleanFile, FileError> {
+    let image = decode(data, format)?;
+    let mut out = Cursor::new(Vec::new());
+    let (content_type, extension) = if format == ImageFormat::Jpeg {
+        image.write_with_encoder(JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY))?;
+        ("image/jpeg", "jpg")
+    } else {
+        image.write_to(&mut out, ImageFormat::Png)?;
+        ("image/png", "png")
+    };
+    Ok(CleanFile {
+        data: out.into_inner(),
+        content_type,
+        extension,
+        dimensions: Some((image.width(), image.height())),
+    })
+}
+
+/// A new, unguessable key to store a file with the given extension under.
+pub fn new_key(extension: &str) -> String {
+    format!("{}.{extension}", uuid::Uuid::new_v4().simple())
+}
+
+/// The storage keys that appear in `text`, e.g. as part of attachment URLs.
+pub fn find_keys(text: &str) -> Vec<&str> {
+    let mut keys: Vec<&str> = text
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '.')
+        .filter(|token| {
+            token.split_once('.').is_some_and(|(id, extension)| {
+                id.len() == KEY_ID_LENGTH
+                    && id.chars().all(|c| c.is_ascii_hexdigit())
+                    && !extension.is_empty()
+                    && extension.chars().all(|c| c.is_ascii_alphanumeric())
+            })
+        })
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys
+}
@@ -0,0 +1,68 @@
+use std::{io, path::PathBuf};
+
+use futures::{FutureExt, future::BoxFuture};
+
+use crate::storage::StorageBackend;
+
+/// Keeps files in a directory, each under its key as file name. The server serves them itself,
+/// at `/attachments/{key}`, unless a base URL pointing elsewhere is configured.
+pub struct LocalStorage {
+    root: PathBuf,
+    base_url: String,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>, base_url: impl Into<String>) -> Self {
+        Self {
+            root: root.into(),
+            base_url: base_url.into(),
+        }
+    }
+
+    /// Reads the directory from `STORAGE_DIR` (default `uploads`) and the base URL from
+    /// `STORAGE_BASE_URL` (default `/attachments/`).
+    pub fn from_env() -> Self {
+        Self::new(
+            dotenvy::var("STORAGE_DIR").unwrap_or("uploads".to_string()),
+            dotenvy::var("STORAGE_BASE_URL").unwrap_or("/attachments/".to_string()),
+        )
+    }
+
+    /// Where the file under `key` lives. Keys that could reach outside the directory are refused.
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        let is_safe = !key.is_empty()
+            && !key.starts_with('.')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '.');
+        if is_safe {
+            Ok(self.root.join(key))
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid storage key {key:?}"),
+            ))
+        }
+    }
+}
+
+impl StorageBackend for LocalStorage {
+    fn put<'a>(&'a self, key: &'a str, data: &'a [u8]) -> BoxFuture<'a, io::Result<()>> {
+        async move {
+            let path = self.path(key)?;
+            tokio::fs::create_dir_all(&self.root).await?;
+            tokio::fs::write(path, data).await
+        }
+        .boxed()
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<Vec<u8>>> {
+        async move { tokio::fs::read(self.path(key)?).await }.boxed()
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<()>> {
+        async move { tokio::fs::remove_file(self.path(key)?).await }.boxed()
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}{key}", self.base_url)
+    }
+}
@@ -0,0 +1,30 @@
+//! Where uploaded files are kept, and what's done to them before they are.
+
+use std::{io, sync::LazyLock};
+
+use futures::future::BoxFuture;
+
+use crate::storage::local::LocalStorage;
+
+pub mod avatars;
+pub mod files;
+pub mod local;
+
+/// Somewhere to keep uploaded files. Files are addressed by keys the server generates, made of
+/// ASCII letters, digits and `.`, so backends can use them as file names or object keys as is.
+pub trait StorageBackend: Send + Sync {
+    /// Stores `data` under `key`, replacing anything already there.
+    fn put<'a>(&'a self, key: &'a str, data: &'a [u8]) -> BoxFuture<'a, io::Result<()>>;
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<Vec<u8>>>;
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<()>>;
+
+    /// The public URL the file under `key` is served at.
+    fn url(&self, key: &str) -> String;
+}
+
+/// The backend uploads go to. Files are kept on the local filesystem, in the directory named by
+/// `STORAGE_DIR`, and served under `STORAGE_BASE_URL`.
+pub static STORAGE: LazyLock<Box<dyn StorageBackend>> =
+    LazyLock::new(|| Box::new(LocalStorage::from_env()));